@@ -0,0 +1,185 @@
+//! Saving and loading the intermediate stages of the build process.
+
+use alloc::vec::Vec;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    CompactHeightfield, ContourSet, DetailNavmesh, Heightfield, NavmeshFile,
+    NavmeshFileCompression, NavmeshFileError, PolygonNavmesh,
+    nav_file::{read_container, write_container},
+};
+
+/// A stage of the build process that can be saved and loaded on its own.
+///
+/// This allows caching the expensive rasterization of each tile as a [`Heightfield`] or [`CompactHeightfield`]
+/// and re-running the later stages with different region or contour parameters without rasterizing again.
+///
+/// Checkpoints use the container format of [`NavmeshFile`] with their own [`Checkpoint::MAGIC`],
+/// so they are versioned and checksummed the same way.
+///
+/// ```
+/// # use rerecast::*;
+/// # fn example(heightfield: Heightfield) -> Result<(), NavmeshFileError> {
+/// let bytes = heightfield.to_checkpoint(NavmeshFileCompression::Deflate)?;
+/// let heightfield = Heightfield::from_checkpoint(&bytes)?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Checkpoint: Sized + Serialize + DeserializeOwned {
+    /// The magic bytes at the start of every checkpoint of this stage.
+    const MAGIC: [u8; 4];
+    /// The name of the stage, used in [`NavmeshFileError::StageMismatch`].
+    const STAGE: &'static str;
+
+    /// Encodes the stage, compressing the payload with `compression`.
+    fn to_checkpoint(
+        &self,
+        compression: NavmeshFileCompression,
+    ) -> Result<Vec<u8>, NavmeshFileError> {
+        write_container(Self::MAGIC, self, compression)
+    }
+
+    /// Decodes a stage written by [`Self::to_checkpoint`].
+    ///
+    /// Returns [`NavmeshFileError::StageMismatch`] if the data holds a different stage.
+    fn from_checkpoint(bytes: &[u8]) -> Result<Self, NavmeshFileError> {
+        if let Some(found) = stage_of(bytes)
+            && found != Self::STAGE
+        {
+            return Err(NavmeshFileError::StageMismatch {
+                expected: Self::STAGE,
+                found,
+            });
+        }
+        read_container(Self::MAGIC, bytes)
+    }
+}
+
+impl Checkpoint for Heightfield {
+    const MAGIC: [u8; 4] = *b"RRHF";
+    const STAGE: &'static str = "heightfield";
+}
+
+impl Checkpoint for CompactHeightfield {
+    const MAGIC: [u8; 4] = *b"RRCH";
+    const STAGE: &'static str = "compact heightfield";
+}
+
+impl Checkpoint for ContourSet {
+    const MAGIC: [u8; 4] = *b"RRCS";
+    const STAGE: &'static str = "contour set";
+}
+
+impl Checkpoint for PolygonNavmesh {
+    const MAGIC: [u8; 4] = *b"RRPM";
+    const STAGE: &'static str = "polygon navmesh";
+}
+
+impl Checkpoint for DetailNavmesh {
+    const MAGIC: [u8; 4] = *b"RRDM";
+    const STAGE: &'static str = "detail navmesh";
+}
+
+/// Returns the name of the stage written to `bytes`, if it is known.
+fn stage_of(bytes: &[u8]) -> Option<&'static str> {
+    let magic: [u8; 4] = bytes.get(..4)?.try_into().ok()?;
+    [
+        (Heightfield::MAGIC, Heightfield::STAGE),
+        (CompactHeightfield::MAGIC, CompactHeightfield::STAGE),
+        (ContourSet::MAGIC, ContourSet::STAGE),
+        (PolygonNavmesh::MAGIC, PolygonNavmesh::STAGE),
+        (DetailNavmesh::MAGIC, DetailNavmesh::STAGE),
+        (NavmeshFile::MAGIC, "navmesh file"),
+    ]
+    .into_iter()
+    .find_map(|(stage_magic, stage)| (stage_magic == magic).then_some(stage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildContoursFlags, HeightfieldBuilder, math::Aabb3d, test_meshes};
+    use glam::Vec3;
+
+    fn heightfield() -> Heightfield {
+        let mut heightfield = HeightfieldBuilder {
+            aabb: Aabb3d {
+                min: Vec3::new(0.0, -1.0, 0.0),
+                max: Vec3::new(10.0, 5.0, 10.0),
+            },
+            cell_size: 0.3,
+            cell_height: 0.2,
+        }
+        .build()
+        .unwrap();
+        let trimesh = test_meshes::flat_ground(Aabb3d {
+            min: Vec3::ZERO,
+            max: Vec3::new(10.0, 0.0, 10.0),
+        });
+        heightfield.populate_from_trimesh(trimesh, 10, 4).unwrap();
+        heightfield
+    }
+
+    /// Runs the stages after rasterization with the given minimum region area.
+    fn resume(heightfield: Heightfield, min_region_area: u16) -> (ContourSet, PolygonNavmesh) {
+        let mut compact = heightfield.into_compact(10, 4).unwrap();
+        compact.erode_walkable_area(2);
+        compact.build_distance_field();
+        compact.build_regions(0, min_region_area, 400).unwrap();
+        let contours = compact.build_contours(1.3, 12, BuildContoursFlags::default());
+        let poly_mesh = contours.clone().into_polygon_mesh(6).unwrap();
+        (contours, poly_mesh)
+    }
+
+    #[test]
+    fn resumes_from_heightfield_checkpoint() {
+        let heightfield = heightfield();
+        let bytes = heightfield
+            .to_checkpoint(NavmeshFileCompression::Deflate)
+            .unwrap();
+        let loaded = Heightfield::from_checkpoint(&bytes).unwrap();
+        assert_eq!(loaded.inverse_cell_size(), heightfield.inverse_cell_size());
+        assert_eq!(
+            loaded.inverse_cell_height(),
+            heightfield.inverse_cell_height()
+        );
+
+        for min_region_area in [0, 64] {
+            let expected = resume(heightfield.clone(), min_region_area);
+            assert_eq!(resume(loaded.clone(), min_region_area), expected);
+            assert!(expected.1.polygon_count() > 0);
+        }
+
+        let compact = heightfield.into_compact(10, 4).unwrap();
+        let bytes = compact.to_checkpoint(NavmeshFileCompression::None).unwrap();
+        assert_eq!(
+            CompactHeightfield::from_checkpoint(&bytes).unwrap(),
+            compact
+        );
+    }
+
+    #[test]
+    fn rejects_checkpoints_of_other_stages() {
+        let (contours, poly_mesh) = resume(heightfield(), 0);
+        let bytes = contours
+            .to_checkpoint(NavmeshFileCompression::Deflate)
+            .unwrap();
+        assert_eq!(ContourSet::from_checkpoint(&bytes).unwrap(), contours);
+
+        let bytes = poly_mesh
+            .to_checkpoint(NavmeshFileCompression::Deflate)
+            .unwrap();
+        assert_eq!(PolygonNavmesh::from_checkpoint(&bytes).unwrap(), poly_mesh);
+        assert_eq!(
+            DetailNavmesh::from_checkpoint(&bytes),
+            Err(NavmeshFileError::StageMismatch {
+                expected: "detail navmesh",
+                found: "polygon navmesh"
+            })
+        );
+        assert_eq!(
+            Heightfield::from_checkpoint(b"not a checkpoint").unwrap_err(),
+            NavmeshFileError::InvalidMagic
+        );
+    }
+}
@@ -77,7 +77,7 @@ use alloc::vec::Vec;
 ///     }
 /// }
 /// ```
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct CompactHeightfield {
     /// The width of the heightfield along the x-axis in cell units
@@ -1,5 +1,5 @@
 use crate::ops::*;
-use crate::{Aabb3d, BuildContoursFlags, ConvexVolume};
+use crate::{Aabb3d, BuildContoursFlags, ConvexVolume, JumpLinkConfig, OffMeshConnection};
 use alloc::vec::Vec;
 
 /// Specifies a configuration to use when performing Recast builds. Usually built using [`ConfigBuilder`].
@@ -198,6 +198,17 @@ pub struct Config {
 
     /// Volumes that define areas with specific areas IDs.
     pub area_volumes: Vec<ConvexVolume>,
+
+    /// Links between positions that are not connected by walkable surface, snapped to the polygons of the tile containing their start.
+    ///
+    /// Only tile generation with [`TiledNavmeshConfig`](crate::TiledNavmeshConfig) uses them; building a single [`PolygonNavmesh`](crate::PolygonNavmesh) ignores them.
+    /// Connections whose end lies in another tile are linked by [`TiledNavmesh`](crate::TiledNavmesh) once both tiles are added,
+    /// connections that leave the navmesh are dropped.
+    pub off_mesh_connections: Vec<OffMeshConnection>,
+
+    /// If set, drop-down and jump links are generated with [`PolygonNavmesh::generate_jump_links`](crate::PolygonNavmesh::generate_jump_links)
+    /// and added to the [`Self::off_mesh_connections`] of each tile.
+    pub jump_links: Option<JumpLinkConfig>,
 }
 
 /// A builder for [`Config`]. The config has lots of interdependent configurations,
@@ -208,8 +219,13 @@ pub struct Config {
 /// - [`Self::agent_height`]
 ///
 /// The default values are chosen to be reasonable for an agent resembling and adult human.
+/// When deserialized, missing fields keep their default values.
 #[derive(Debug, Clone, PartialEq)]
-#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct ConfigBuilder {
     /// How many cells should fit in the [`Self::agent_radius`] on the xz-plane to use for fields. `[Limit: > 0]`.
     ///
@@ -335,6 +351,11 @@ pub struct ConfigBuilder {
     pub tiling: bool,
     /// Volumes that define areas with specific areas IDs.
     pub area_volumes: Vec<ConvexVolume>,
+    /// Links between positions that are not connected by walkable surface, like jumps, ladders or teleporters.
+    /// See [`Config::off_mesh_connections`] for their limitations.
+    pub off_mesh_connections: Vec<OffMeshConnection>,
+    /// If set, drop-down and jump links are generated automatically for agents with the given capabilities.
+    pub jump_links: Option<JumpLinkConfig>,
 }
 
 impl Default for ConfigBuilder {
@@ -358,6 +379,8 @@ impl Default for ConfigBuilder {
             contour_flags: BuildContoursFlags::default(),
             tiling: false,
             area_volumes: Vec::new(),
+            off_mesh_connections: Vec::new(),
+            jump_links: None,
         }
     }
 }
@@ -403,6 +426,8 @@ impl ConfigBuilder {
             detail_sample_max_error: cell_height * self.detail_sample_max_error,
             contour_flags: self.contour_flags,
             area_volumes: self.area_volumes,
+            off_mesh_connections: self.off_mesh_connections,
+            jump_links: self.jump_links,
         }
     }
 }
@@ -519,6 +519,7 @@ fn remove_degenerate_segments(simplified: &mut Vec<(U16Vec3, u32)>) {
 ///
 /// The standard process for building a contour set is to use [`CompactHeightfield::build_contours`].
 #[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct ContourSet {
     /// An array of the contours in the set.
     pub contours: Vec<Contour>,
@@ -541,6 +542,7 @@ pub struct ContourSet {
 bitflags::bitflags! {
     /// Flags used by [`Contour::vertices`]
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
     pub struct RegionVertexId: u32 {
         ///No flags
         const NONE = 0;
@@ -607,6 +609,7 @@ impl From<RegionVertexId> for RegionId {
 /// };
 /// ```
 #[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct Contour {
     /// Simplified contour vertex and connection data.
     ///
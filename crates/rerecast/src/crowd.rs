@@ -0,0 +1,1328 @@
+//! Simulation of many agents moving over a navmesh, modelled after Detour's `dtCrowd`.
+
+use alloc::{collections::VecDeque, vec::Vec};
+use glam::{Vec3, Vec3Swizzles as _};
+use slotmap::SlotMap;
+use thiserror::Error;
+
+use crate::{
+    NavmeshQuery, NavmeshQueryError, ObstacleAvoidanceParams, ObstacleAvoidanceQuery, PathCorridor,
+    PolyRef, QueryFilter, SlicedFindPath, SlicedFindPathStatus, StraightPathFlags,
+    StraightPathVertex,
+    math::triangle_area_xz,
+    obstacle_avoidance::navmesh_walls_around,
+    ops::{FloatPow as _, abs},
+    proximity_grid::ProximityGrid,
+};
+
+slotmap::new_key_type! {
+    /// A handle to an agent of a [`Crowd`].
+    pub struct CrowdAgentId;
+}
+
+bitflags::bitflags! {
+    /// Selects which steering behaviors a [`Crowd`] applies to an agent.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+    #[repr(transparent)]
+    pub struct CrowdUpdateFlags: u8 {
+        /// Steer smoothly towards the corner after the next one instead of heading straight for the next corner.
+        const ANTICIPATE_TURNS = 0x01;
+        /// Avoid other agents and walls with [`ObstacleAvoidanceQuery::sample_velocity`].
+        const OBSTACLE_AVOIDANCE = 0x02;
+        /// Keep a distance to the neighboring agents, weighted by [`CrowdAgentParams::separation_weight`].
+        const SEPARATION = 0x04;
+        /// Shorten the path every update with [`PathCorridor::optimize_path_visibility`].
+        const OPTIMIZE_VISIBILITY = 0x08;
+        /// Shorten the path periodically with [`PathCorridor::optimize_path_topology`].
+        const OPTIMIZE_TOPOLOGY = 0x10;
+    }
+}
+
+impl Default for CrowdUpdateFlags {
+    fn default() -> Self {
+        Self::ANTICIPATE_TURNS
+            | Self::OBSTACLE_AVOIDANCE
+            | Self::OPTIMIZE_VISIBILITY
+            | Self::OPTIMIZE_TOPOLOGY
+    }
+}
+
+/// The configuration of an agent of a [`Crowd`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrowdAgentParams {
+    /// The radius of the agent.
+    pub radius: f32,
+    /// The height of the agent.
+    pub height: f32,
+    /// The maximum acceleration of the agent.
+    pub max_acceleration: f32,
+    /// The maximum speed of the agent.
+    pub max_speed: f32,
+    /// How far away other agents and walls are taken into account for steering.
+    pub collision_query_range: f32,
+    /// How far ahead [`PathCorridor::optimize_path_visibility`] looks for shortcuts.
+    pub path_optimization_range: f32,
+    /// How strongly the agent keeps its distance to its neighbors when [`CrowdUpdateFlags::SEPARATION`] is set.
+    pub separation_weight: f32,
+    /// The steering behaviors applied to the agent.
+    pub update_flags: CrowdUpdateFlags,
+    /// The parameters of the obstacle avoidance when [`CrowdUpdateFlags::OBSTACLE_AVOIDANCE`] is set.
+    pub obstacle_avoidance: ObstacleAvoidanceParams,
+}
+
+impl Default for CrowdAgentParams {
+    fn default() -> Self {
+        let radius = 0.6;
+        Self {
+            radius,
+            height: 2.0,
+            max_acceleration: 8.0,
+            max_speed: 3.5,
+            collision_query_range: radius * 12.0,
+            path_optimization_range: radius * 30.0,
+            separation_weight: 2.0,
+            update_flags: CrowdUpdateFlags::default(),
+            obstacle_avoidance: ObstacleAvoidanceParams::default(),
+        }
+    }
+}
+
+/// The state of an agent of a [`Crowd`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrowdAgentState {
+    /// The agent is not on the navmesh and is not simulated.
+    Invalid,
+    /// The agent moves over the navmesh.
+    Walking,
+    /// The agent traverses an off-mesh connection, see [`CrowdAgent::off_mesh_connection`].
+    OffMeshConnection,
+}
+
+/// The state of the move request of an agent of a [`Crowd`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveRequestState {
+    /// The agent has no target and stands still.
+    None,
+    /// No path to the target could be found.
+    Failed,
+    /// The agent follows a path to its target.
+    Valid,
+    /// A path to the target will be searched in the next update.
+    Requesting,
+    /// A path to the target is being searched over one or more updates.
+    WaitingForPath,
+    /// The agent moves with a requested velocity instead of towards a target.
+    Velocity,
+}
+
+/// A nearby agent of an agent of a [`Crowd`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrowdNeighbor {
+    /// The neighboring agent.
+    pub agent: CrowdAgentId,
+    /// The squared distance to the neighboring agent on the xz-plane.
+    pub distance_squared: f32,
+}
+
+/// Errors that can occur when controlling the agents of a [`Crowd`].
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CrowdError {
+    /// The agent does not exist in the crowd.
+    #[error("The agent does not exist in the crowd")]
+    InvalidAgent,
+    /// The agent is not on the navmesh.
+    #[error("The agent is not on the navmesh")]
+    AgentNotOnNavmesh,
+    /// A navmesh query failed.
+    #[error(transparent)]
+    Query(#[from] NavmeshQueryError),
+}
+
+/// The walls around an agent, cached until the agent has moved far enough.
+#[derive(Debug, Clone, Default)]
+struct LocalBoundary {
+    center: Option<Vec3>,
+    segments: Vec<(Vec3, Vec3)>,
+    polygons: Vec<PolyRef>,
+}
+
+impl LocalBoundary {
+    const MAX_SEGMENTS: usize = 8;
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn update(
+        &mut self,
+        polygon: u16,
+        position: Vec3,
+        collision_query_range: f32,
+        query: &NavmeshQuery,
+        filter: &QueryFilter,
+    ) -> Result<(), NavmeshQueryError> {
+        self.reset();
+        self.center = Some(position);
+        let (polygons, mut segments) =
+            navmesh_walls_around(query, polygon, position, collision_query_range, filter)?;
+        // Keep the closest segments.
+        segments.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.segments.extend(
+            segments
+                .into_iter()
+                .take(Self::MAX_SEGMENTS)
+                .map(|(_, segment)| segment),
+        );
+        self.polygons
+            .extend(polygons.iter().map(|p| query.poly_ref(*p)));
+        Ok(())
+    }
+
+    fn is_valid(&self, query: &NavmeshQuery, filter: &QueryFilter) -> bool {
+        self.center.is_some()
+            && self.polygons.iter().all(|poly_ref| {
+                query
+                    .polygon_by_ref(*poly_ref)
+                    .is_ok_and(|polygon| query.passes_filter(polygon, filter))
+            })
+    }
+}
+
+/// An agent of a [`Crowd`].
+#[derive(Debug, Clone)]
+pub struct CrowdAgent {
+    params: CrowdAgentParams,
+    state: CrowdAgentState,
+    corridor: PathCorridor,
+    boundary: LocalBoundary,
+    topology_optimization_time: f32,
+    neighbors: Vec<CrowdNeighbor>,
+    desired_speed: f32,
+    position: Vec3,
+    displacement: Vec3,
+    desired_velocity: Vec3,
+    new_velocity: Vec3,
+    velocity: Vec3,
+    corners: Vec<StraightPathVertex>,
+    target_state: MoveRequestState,
+    target_polygon: PolyRef,
+    target_position: Vec3,
+    target_velocity: Vec3,
+    target_replan_time: f32,
+    search: Option<SlicedFindPath>,
+    is_partial: bool,
+    animation: Option<OffMeshAnimation>,
+}
+
+/// The movement of an agent along an off-mesh connection.
+#[derive(Debug, Clone)]
+struct OffMeshAnimation {
+    /// The connection that is traversed.
+    connection: PolyRef,
+    /// The position of the agent when the connection was triggered.
+    initial_position: Vec3,
+    start: Vec3,
+    end: Vec3,
+    /// The time since the connection was triggered.
+    t: f32,
+    /// The duration of the traversal.
+    t_max: f32,
+}
+
+impl CrowdAgent {
+    /// The configuration of the agent.
+    #[inline]
+    pub fn params(&self) -> &CrowdAgentParams {
+        &self.params
+    }
+
+    /// The state of the agent.
+    #[inline]
+    pub fn state(&self) -> CrowdAgentState {
+        self.state
+    }
+
+    /// The path of the agent.
+    #[inline]
+    pub fn corridor(&self) -> &PathCorridor {
+        &self.corridor
+    }
+
+    /// The position of the agent.
+    #[inline]
+    pub fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    /// The velocity of the agent.
+    #[inline]
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    /// The velocity the agent would like to move with, before avoiding obstacles.
+    #[inline]
+    pub fn desired_velocity(&self) -> Vec3 {
+        self.desired_velocity
+    }
+
+    /// The next corners of the path the agent steers towards.
+    #[inline]
+    pub fn corners(&self) -> &[StraightPathVertex] {
+        &self.corners
+    }
+
+    /// The nearby agents, sorted by distance.
+    #[inline]
+    pub fn neighbors(&self) -> &[CrowdNeighbor] {
+        &self.neighbors
+    }
+
+    /// The walls around the agent that are taken into account for obstacle avoidance.
+    #[inline]
+    pub fn boundary_segments(&self) -> &[(Vec3, Vec3)] {
+        &self.boundary.segments
+    }
+
+    /// The state of the move request of the agent.
+    #[inline]
+    pub fn target_state(&self) -> MoveRequestState {
+        self.target_state
+    }
+
+    /// The target position of the agent.
+    #[inline]
+    pub fn target_position(&self) -> Vec3 {
+        self.target_position
+    }
+
+    /// Whether the path of the agent does not reach the requested target.
+    #[inline]
+    pub fn is_partial(&self) -> bool {
+        self.is_partial
+    }
+
+    /// The off-mesh connection the agent currently traverses, if any.
+    ///
+    /// Use [`NavmeshQuery::off_mesh_connection`] to look up e.g. its [`OffMeshConnection::user_id`](crate::OffMeshConnection::user_id)
+    /// and play the matching animation. The crowd moves the agent along the connection in a straight line.
+    #[inline]
+    pub fn off_mesh_connection(&self) -> Option<PolyRef> {
+        self.animation
+            .as_ref()
+            .map(|animation| animation.connection)
+    }
+
+    /// Returns whether the agent is within `radius` of the start of an off-mesh connection at the end of its corners.
+    fn is_over_off_mesh_connection(&self, radius: f32) -> bool {
+        self.corners.last().is_some_and(|corner| {
+            corner
+                .flags
+                .contains(StraightPathFlags::OFF_MESH_CONNECTION)
+                && corner.position.xz().distance_squared(self.position.xz()) < radius.squared()
+        })
+    }
+
+    /// Returns the distance to the end of the path, clamped to `range`.
+    fn distance_to_goal(&self, range: f32) -> f32 {
+        match self.corners.last() {
+            Some(corner) if corner.flags.contains(StraightPathFlags::END) => {
+                self.position.xz().distance(corner.position.xz()).min(range)
+            }
+            _ => range,
+        }
+    }
+
+    /// Returns the direction towards the next corner, bending towards the corner after it.
+    fn smooth_steer_direction(&self) -> Vec3 {
+        let Some(first) = self.corners.first() else {
+            return Vec3::ZERO;
+        };
+        let second = self.corners[1.min(self.corners.len() - 1)];
+        let dir0 = (first.position - self.position).with_y(0.0);
+        let dir1 = (second.position - self.position)
+            .with_y(0.0)
+            .normalize_or_zero();
+        let len0 = dir0.length();
+        (dir0 - dir1 * len0 * 0.5).normalize_or_zero()
+    }
+
+    /// Returns the direction towards the next corner.
+    fn straight_steer_direction(&self) -> Vec3 {
+        let Some(first) = self.corners.first() else {
+            return Vec3::ZERO;
+        };
+        (first.position - self.position)
+            .with_y(0.0)
+            .normalize_or_zero()
+    }
+}
+
+/// Moves a group of agents over a navmesh, modelled after Detour's `dtCrowd`.
+///
+/// Every call to [`Crowd::update`] plans paths with a bounded budget of sliced path searches,
+/// keeps the [`PathCorridor`] of each agent up to date, finds neighbors with a proximity grid,
+/// applies separation and obstacle avoidance, and moves the agents constrained to the navmesh.
+///
+/// The simulation does not depend on any global state, so the same sequence of calls always produces the same result.
+///
+/// Agents move over the [`NavmeshQuery`] passed to [`Crowd::update`], i.e. one [`PolygonNavmesh`](crate::PolygonNavmesh)
+/// or the tiles of a [`TiledNavmesh`](crate::TiledNavmesh) covered by [`TiledNavmesh::query`](crate::TiledNavmesh::query).
+#[derive(Debug, Clone)]
+pub struct Crowd {
+    agents: SlotMap<CrowdAgentId, CrowdAgent>,
+    filter: QueryFilter,
+    agent_placement_half_extents: Vec3,
+    grid: ProximityGrid<CrowdAgentId>,
+    path_queue: VecDeque<CrowdAgentId>,
+    max_path_iterations_per_update: usize,
+    obstacle_query: ObstacleAvoidanceQuery,
+}
+
+impl Crowd {
+    /// The maximum number of neighbors taken into account per agent.
+    pub const MAX_NEIGHBORS: usize = 6;
+    /// The maximum number of corners each agent looks ahead.
+    pub const MAX_CORNERS: usize = 4;
+    /// The number of path polygons ahead of each agent that are checked for validity every update.
+    const CHECK_LOOKAHEAD: usize = 10;
+    /// The time in seconds before an agent whose path does not reach the target searches a new path.
+    const TARGET_REPLAN_DELAY: f32 = 1.0;
+    /// The time in seconds between topology optimizations of an agent's path.
+    const TOPOLOGY_OPTIMIZATION_TIME: f32 = 0.5;
+    /// The number of iterations to resolve collisions between agents.
+    const COLLISION_ITERATIONS: usize = 4;
+    const COLLISION_RESOLVE_FACTOR: f32 = 0.7;
+    /// The smallest `max_agent_radius` accepted by [`Crowd::new`], which keeps the cells of the proximity grid from collapsing.
+    pub const MIN_AGENT_RADIUS: f32 = 0.01;
+
+    /// Creates an empty crowd for agents of radius up to `max_agent_radius`.
+    ///
+    /// Radii below [`Crowd::MIN_AGENT_RADIUS`], including zero, negative and NaN values, are clamped to it.
+    pub fn new(max_agent_radius: f32) -> Self {
+        let max_agent_radius = max_agent_radius.max(Self::MIN_AGENT_RADIUS);
+        Self {
+            agents: SlotMap::with_key(),
+            filter: QueryFilter::default(),
+            agent_placement_half_extents: Vec3::new(
+                max_agent_radius * 2.0,
+                max_agent_radius * 1.5,
+                max_agent_radius * 2.0,
+            ),
+            grid: ProximityGrid::new(max_agent_radius * 3.0),
+            path_queue: VecDeque::new(),
+            max_path_iterations_per_update: 100,
+            obstacle_query: ObstacleAvoidanceQuery::new(),
+        }
+    }
+
+    /// The filter used for all queries of the crowd.
+    #[inline]
+    pub fn filter(&self) -> &QueryFilter {
+        &self.filter
+    }
+
+    /// The filter used for all queries of the crowd.
+    #[inline]
+    pub fn filter_mut(&mut self) -> &mut QueryFilter {
+        &mut self.filter
+    }
+
+    /// Sets the number of path search iterations shared by all agents per update. Defaults to `100`.
+    pub fn set_max_path_iterations_per_update(&mut self, iterations: usize) {
+        self.max_path_iterations_per_update = iterations;
+    }
+
+    /// The half extents of the box in which agents are placed onto the navmesh.
+    #[inline]
+    pub fn agent_placement_half_extents(&self) -> Vec3 {
+        self.agent_placement_half_extents
+    }
+
+    /// Adds an agent at the nearest position on the navmesh.
+    ///
+    /// If there is no navmesh near `position`, the agent is added in the [`CrowdAgentState::Invalid`] state.
+    pub fn add_agent(
+        &mut self,
+        position: Vec3,
+        params: CrowdAgentParams,
+        query: &NavmeshQuery,
+    ) -> CrowdAgentId {
+        let nearest =
+            query.find_nearest_polygon(position, self.agent_placement_half_extents, &self.filter);
+        let mut corridor = PathCorridor::default();
+        let (state, position) = match nearest {
+            Some((polygon, nearest)) => {
+                corridor.reset(query.poly_ref(polygon), nearest);
+                (CrowdAgentState::Walking, nearest)
+            }
+            None => {
+                corridor.reset(PolyRef::NULL, position);
+                (CrowdAgentState::Invalid, position)
+            }
+        };
+        self.agents.insert(CrowdAgent {
+            params,
+            state,
+            corridor,
+            boundary: LocalBoundary::default(),
+            topology_optimization_time: 0.0,
+            neighbors: Vec::new(),
+            desired_speed: 0.0,
+            position,
+            displacement: Vec3::ZERO,
+            desired_velocity: Vec3::ZERO,
+            new_velocity: Vec3::ZERO,
+            velocity: Vec3::ZERO,
+            corners: Vec::new(),
+            target_state: MoveRequestState::None,
+            target_polygon: PolyRef::NULL,
+            target_position: Vec3::ZERO,
+            target_velocity: Vec3::ZERO,
+            target_replan_time: 0.0,
+            search: None,
+            is_partial: false,
+            animation: None,
+        })
+    }
+
+    /// Removes an agent from the crowd.
+    pub fn remove_agent(&mut self, agent: CrowdAgentId) -> Option<CrowdAgent> {
+        self.path_queue.retain(|queued| *queued != agent);
+        self.agents.remove(agent)
+    }
+
+    /// Returns an agent of the crowd.
+    #[inline]
+    pub fn agent(&self, agent: CrowdAgentId) -> Option<&CrowdAgent> {
+        self.agents.get(agent)
+    }
+
+    /// Returns all agents of the crowd.
+    pub fn agents(&self) -> impl Iterator<Item = (CrowdAgentId, &CrowdAgent)> {
+        self.agents.iter()
+    }
+
+    /// The number of agents in the crowd.
+    #[inline]
+    pub fn agent_count(&self) -> usize {
+        self.agents.len()
+    }
+
+    /// Changes the configuration of an agent.
+    pub fn set_agent_params(
+        &mut self,
+        agent: CrowdAgentId,
+        params: CrowdAgentParams,
+    ) -> Result<(), CrowdError> {
+        self.agents
+            .get_mut(agent)
+            .ok_or(CrowdError::InvalidAgent)?
+            .params = params;
+        Ok(())
+    }
+
+    /// Requests the agent to move to `position` on `polygon`. The path is searched during the following updates.
+    pub fn request_move_target(
+        &mut self,
+        agent: CrowdAgentId,
+        polygon: PolyRef,
+        position: Vec3,
+    ) -> Result<(), CrowdError> {
+        let agent = self.agents.get_mut(agent).ok_or(CrowdError::InvalidAgent)?;
+        if agent.state == CrowdAgentState::Invalid {
+            return Err(CrowdError::AgentNotOnNavmesh);
+        }
+        agent.target_polygon = polygon;
+        agent.target_position = position;
+        agent.target_state = MoveRequestState::Requesting;
+        agent.target_replan_time = 0.0;
+        agent.search = None;
+        Ok(())
+    }
+
+    /// Requests the agent to move with the given velocity, ignoring any target.
+    pub fn request_move_velocity(
+        &mut self,
+        agent: CrowdAgentId,
+        velocity: Vec3,
+    ) -> Result<(), CrowdError> {
+        let agent = self.agents.get_mut(agent).ok_or(CrowdError::InvalidAgent)?;
+        agent.target_polygon = PolyRef::NULL;
+        agent.target_velocity = velocity;
+        agent.target_state = MoveRequestState::Velocity;
+        agent.search = None;
+        Ok(())
+    }
+
+    /// Cancels the move request of the agent, which then comes to a halt.
+    pub fn reset_move_target(&mut self, agent: CrowdAgentId) -> Result<(), CrowdError> {
+        let agent = self.agents.get_mut(agent).ok_or(CrowdError::InvalidAgent)?;
+        agent.target_polygon = PolyRef::NULL;
+        agent.target_velocity = Vec3::ZERO;
+        agent.target_state = MoveRequestState::None;
+        agent.search = None;
+        Ok(())
+    }
+
+    /// Advances the simulation by `dt` seconds.
+    ///
+    /// `query` must operate on the navmesh the agents were added to. Polygons that disappeared since the last update,
+    /// e.g. because a tile was replaced, are detected and the affected agents replan their paths.
+    pub fn update(&mut self, dt: f32, query: &NavmeshQuery) {
+        self.check_path_validity(dt, query);
+        self.update_move_requests(query);
+        self.update_topology_optimization(dt, query);
+
+        let mut walking: Vec<_> = self
+            .agents
+            .iter()
+            .filter(|(_, agent)| agent.state == CrowdAgentState::Walking)
+            .map(|(id, _)| id)
+            .collect();
+
+        // Register agents to the proximity grid.
+        self.grid.clear();
+        for &id in &walking {
+            let agent = &self.agents[id];
+            let radius = Vec3::new(agent.params.radius, 0.0, agent.params.radius);
+            self.grid
+                .add_item(id, agent.position - radius, agent.position + radius);
+        }
+
+        // Get nearby navmesh segments and agents to collide with.
+        for &id in &walking {
+            let neighbors = self.find_neighbors(id);
+            let agent = &mut self.agents[id];
+            agent.neighbors = neighbors;
+            // Update the collision boundary after a certain distance has been passed or if it has become invalid.
+            let update_threshold = agent.params.collision_query_range * 0.25;
+            let moved_far = agent.boundary.center.is_none_or(|center| {
+                center.xz().distance_squared(agent.position.xz()) > update_threshold.squared()
+            });
+            if moved_far || !agent.boundary.is_valid(query, &self.filter) {
+                let updated = query
+                    .polygon_by_ref(agent.corridor.first_polygon())
+                    .and_then(|polygon| {
+                        agent.boundary.update(
+                            polygon,
+                            agent.position,
+                            agent.params.collision_query_range,
+                            query,
+                            &self.filter,
+                        )
+                    });
+                if updated.is_err() {
+                    agent.boundary.reset();
+                }
+            }
+        }
+
+        // Find next corner to steer to.
+        for &id in &walking {
+            let agent = &mut self.agents[id];
+            if matches!(
+                agent.target_state,
+                MoveRequestState::None | MoveRequestState::Velocity
+            ) {
+                agent.corners.clear();
+                continue;
+            }
+            agent.corners = agent
+                .corridor
+                .find_corners(Self::MAX_CORNERS, query)
+                .unwrap_or_default();
+
+            // Check to see if the corner after the next corner is directly visible, and short cut to there.
+            if agent
+                .params
+                .update_flags
+                .contains(CrowdUpdateFlags::OPTIMIZE_VISIBILITY)
+                && let Some(target) = agent.corners.get(1).or(agent.corners.first())
+            {
+                // Failing to optimize leaves the path as it is.
+                let _ = agent.corridor.optimize_path_visibility(
+                    target.position,
+                    agent.params.path_optimization_range,
+                    query,
+                    &self.filter,
+                );
+            }
+        }
+
+        // Trigger off-mesh connections (depends on corners).
+        for &id in &walking {
+            let agent = &mut self.agents[id];
+            let trigger_radius = agent.params.radius * 2.25;
+            if !agent.is_over_off_mesh_connection(trigger_radius) {
+                continue;
+            }
+            let Some(connection) = agent.corners.last().and_then(|corner| corner.polygon) else {
+                continue;
+            };
+            let connection = query.poly_ref(connection);
+            let Ok((start, end)) = agent
+                .corridor
+                .move_over_off_mesh_connection(connection, query)
+            else {
+                continue;
+            };
+            agent.animation = Some(OffMeshAnimation {
+                connection,
+                initial_position: agent.position,
+                start,
+                end,
+                t: 0.0,
+                t_max: start.distance(end) / agent.params.max_speed * 0.5,
+            });
+            agent.state = CrowdAgentState::OffMeshConnection;
+            agent.corners.clear();
+            agent.neighbors.clear();
+            agent.velocity = Vec3::ZERO;
+        }
+        walking.retain(|id| self.agents[*id].state == CrowdAgentState::Walking);
+
+        // Calculate steering.
+        for &id in &walking {
+            let agent = &self.agents[id];
+            let desired_velocity = match agent.target_state {
+                MoveRequestState::None | MoveRequestState::Failed => Vec3::ZERO,
+                MoveRequestState::Velocity => agent.target_velocity,
+                _ => {
+                    // Calculate steering direction.
+                    let direction = if agent
+                        .params
+                        .update_flags
+                        .contains(CrowdUpdateFlags::ANTICIPATE_TURNS)
+                    {
+                        agent.smooth_steer_direction()
+                    } else {
+                        agent.straight_steer_direction()
+                    };
+                    // Calculate speed scale, which tells the agent to slow down at the end of the path.
+                    let slow_down_radius = agent.params.radius * 2.0;
+                    let speed_scale = agent.distance_to_goal(slow_down_radius) / slow_down_radius;
+                    direction * agent.params.max_speed * speed_scale
+                }
+            };
+            let desired_speed = match agent.target_state {
+                MoveRequestState::Velocity => agent.target_velocity.length(),
+                _ => agent.params.max_speed,
+            };
+            let desired_velocity = self.separate(id, desired_velocity, desired_speed);
+            let agent = &mut self.agents[id];
+            agent.desired_speed = desired_speed;
+            agent.desired_velocity = desired_velocity;
+        }
+
+        // Velocity planning.
+        for &id in &walking {
+            let agent = &self.agents[id];
+            let new_velocity = if agent
+                .params
+                .update_flags
+                .contains(CrowdUpdateFlags::OBSTACLE_AVOIDANCE)
+            {
+                let obstacles = &mut self.obstacle_query;
+                obstacles.reset();
+                // Add neighbors as obstacles.
+                for neighbor in &agent.neighbors {
+                    let other = &self.agents[neighbor.agent];
+                    obstacles.add_circle(
+                        other.position,
+                        other.params.radius,
+                        other.velocity,
+                        other.desired_velocity,
+                    );
+                }
+                // Append neighbor segments as obstacles.
+                for &(p, q) in &agent.boundary.segments {
+                    if triangle_area_xz(agent.position, p, q) < 0.0 {
+                        continue;
+                    }
+                    obstacles.add_segment(p, q);
+                }
+                obstacles.sample_velocity(
+                    agent.position,
+                    agent.params.radius,
+                    agent.desired_speed,
+                    agent.velocity,
+                    agent.desired_velocity,
+                    &agent.params.obstacle_avoidance,
+                )
+            } else {
+                agent.desired_velocity
+            };
+            self.agents[id].new_velocity = new_velocity;
+        }
+
+        // Integrate.
+        for &id in &walking {
+            let agent = &mut self.agents[id];
+            // Fake dynamic constraint.
+            let max_delta = agent.params.max_acceleration * dt;
+            let delta = agent.new_velocity - agent.velocity;
+            let length = delta.length();
+            agent.velocity += if length > max_delta {
+                delta * (max_delta / length)
+            } else {
+                delta
+            };
+            // Integrate
+            if agent.velocity.length() > 0.0001 {
+                agent.position += agent.velocity * dt;
+            } else {
+                agent.velocity = Vec3::ZERO;
+            }
+        }
+
+        // Handle collisions.
+        for _ in 0..Self::COLLISION_ITERATIONS {
+            for &id in &walking {
+                let displacement = self.collision_displacement(id);
+                self.agents[id].displacement = displacement;
+            }
+            for &id in &walking {
+                let agent = &mut self.agents[id];
+                agent.position += agent.displacement;
+            }
+        }
+
+        // Move along navmesh.
+        for &id in &walking {
+            let agent = &mut self.agents[id];
+            if agent
+                .corridor
+                .move_position(agent.position, query, &self.filter)
+                .is_ok()
+            {
+                // Get valid constrained position back.
+                agent.position = agent.corridor.position();
+            }
+            // If not using path, truncate the corridor to just one polygon.
+            if matches!(
+                agent.target_state,
+                MoveRequestState::None | MoveRequestState::Velocity
+            ) {
+                let first = agent.corridor.first_polygon();
+                agent.corridor.reset(first, agent.position);
+                agent.is_partial = false;
+            }
+        }
+
+        // Update agents using off-mesh connections.
+        for agent in self.agents.values_mut() {
+            let Some(animation) = &mut agent.animation else {
+                continue;
+            };
+            animation.t += dt;
+            if animation.t > animation.t_max {
+                // Reset animation.
+                agent.position = animation.end;
+                agent.animation = None;
+                // Prepare agent for walking.
+                agent.state = CrowdAgentState::Walking;
+                continue;
+            }
+            // Update position.
+            let ta = animation.t_max * 0.15;
+            agent.position = if animation.t < ta {
+                let u = (animation.t / ta).clamp(0.0, 1.0);
+                animation.initial_position.lerp(animation.start, u)
+            } else {
+                let u = ((animation.t - ta) / (animation.t_max - ta)).clamp(0.0, 1.0);
+                animation.start.lerp(animation.end, u)
+            };
+            // Update velocity.
+            agent.velocity = Vec3::ZERO;
+            agent.desired_velocity = Vec3::ZERO;
+        }
+    }
+
+    /// Recovers agents and targets whose polygons have disappeared and requests new paths where necessary.
+    fn check_path_validity(&mut self, dt: f32, query: &NavmeshQuery) {
+        for agent in self.agents.values_mut() {
+            if agent.state != CrowdAgentState::Walking {
+                continue;
+            }
+            agent.target_replan_time += dt;
+            let mut replan = false;
+
+            // First check that the current location is valid.
+            let is_valid = |poly_ref| {
+                query
+                    .polygon_by_ref(poly_ref)
+                    .is_ok_and(|polygon| query.passes_filter(polygon, &self.filter))
+            };
+            if !is_valid(agent.corridor.first_polygon()) {
+                // Current location is not valid, try to reposition.
+                let nearest = query.find_nearest_polygon(
+                    agent.position,
+                    self.agent_placement_half_extents,
+                    &self.filter,
+                );
+                let Some((polygon, nearest)) = nearest else {
+                    // Could not find location in navmesh, set state to invalid.
+                    agent.corridor.reset(PolyRef::NULL, agent.position);
+                    agent.boundary.reset();
+                    agent.is_partial = false;
+                    agent.state = CrowdAgentState::Invalid;
+                    continue;
+                };
+                // Make sure the first polygon is valid, but leave other valid polygons in the path so that the replanner can adjust the path better.
+                agent
+                    .corridor
+                    .fix_path_start(query.poly_ref(polygon), nearest);
+                agent.boundary.reset();
+                agent.position = nearest;
+                replan = true;
+            }
+
+            // If the agent does not have move target or is controlled by velocity, no need to recover the target nor replan.
+            if matches!(
+                agent.target_state,
+                MoveRequestState::None | MoveRequestState::Velocity
+            ) {
+                continue;
+            }
+
+            // Try to recover move request position.
+            if agent.target_state != MoveRequestState::Failed && !is_valid(agent.target_polygon) {
+                // Current target is not valid, try to reposition.
+                let nearest = query.find_nearest_polygon(
+                    agent.target_position,
+                    self.agent_placement_half_extents,
+                    &self.filter,
+                );
+                let Some((polygon, nearest)) = nearest else {
+                    // Failed to reposition target, fail move request.
+                    let first = agent.corridor.first_polygon();
+                    agent.corridor.reset(first, agent.position);
+                    agent.is_partial = false;
+                    agent.target_state = MoveRequestState::None;
+                    agent.search = None;
+                    continue;
+                };
+                agent.target_polygon = query.poly_ref(polygon);
+                agent.target_position = nearest;
+                replan = true;
+            }
+
+            // If nearby corridor is not valid, replan.
+            if !agent
+                .corridor
+                .is_valid(Self::CHECK_LOOKAHEAD, query, &self.filter)
+            {
+                replan = true;
+            }
+
+            // If the end of the path is near and it is not the requested location, replan.
+            if agent.target_state == MoveRequestState::Valid
+                && agent.target_replan_time > Self::TARGET_REPLAN_DELAY
+                && agent.corridor.path().len() < Self::CHECK_LOOKAHEAD
+                && agent.corridor.last_polygon() != agent.target_polygon
+            {
+                replan = true;
+            }
+
+            // Try to replan path to goal.
+            if replan {
+                agent.target_state = MoveRequestState::Requesting;
+                agent.target_replan_time = 0.0;
+                agent.search = None;
+            }
+        }
+    }
+
+    /// Starts the requested path searches and advances the queued ones within the iteration budget.
+    fn update_move_requests(&mut self, query: &NavmeshQuery) {
+        for (id, agent) in &mut self.agents {
+            if agent.state != CrowdAgentState::Walking
+                || agent.target_state != MoveRequestState::Requesting
+            {
+                continue;
+            }
+            let search = query
+                .polygon_by_ref(agent.corridor.first_polygon())
+                .and_then(|start| Ok((start, query.polygon_by_ref(agent.target_polygon)?)))
+                .and_then(|(start, end)| {
+                    query.init_sliced_find_path(
+                        start,
+                        end,
+                        agent.corridor.position(),
+                        agent.target_position,
+                        self.filter.clone(),
+                        NavmeshQuery::DEFAULT_MAX_NODES,
+                    )
+                });
+            match search {
+                Ok(search) => {
+                    agent.search = Some(search);
+                    agent.target_state = MoveRequestState::WaitingForPath;
+                    self.path_queue.push_back(id);
+                }
+                Err(_) => agent.target_state = MoveRequestState::Failed,
+            }
+        }
+
+        let mut budget = self.max_path_iterations_per_update;
+        while budget > 0
+            && let Some(&id) = self.path_queue.front()
+        {
+            let Some(agent) = self.agents.get_mut(id) else {
+                self.path_queue.pop_front();
+                continue;
+            };
+            let Some(search) = agent
+                .search
+                .as_mut()
+                .filter(|_| agent.target_state == MoveRequestState::WaitingForPath)
+            else {
+                self.path_queue.pop_front();
+                continue;
+            };
+            let (status, iterations) = query.update_sliced_find_path(search, budget);
+            budget = budget.saturating_sub(iterations);
+            if status == SlicedFindPathStatus::InProgress {
+                break;
+            }
+            self.path_queue.pop_front();
+            let search = agent.search.take().unwrap();
+            let Ok(result) = query.finalize_sliced_find_path(search) else {
+                agent.target_state = MoveRequestState::Failed;
+                continue;
+            };
+
+            // The agent may have moved on while the path was searched, so the path has to start at its current polygon.
+            let mut path: Vec<_> = result.path.iter().map(|p| query.poly_ref(*p)).collect();
+            let Some(start) = path
+                .iter()
+                .position(|p| *p == agent.corridor.first_polygon())
+            else {
+                agent.target_state = MoveRequestState::Requesting;
+                continue;
+            };
+            path.drain(..start);
+
+            // Move the target to the reachable end of a partial path.
+            let mut target = agent.target_position;
+            if result.is_partial
+                && let Some(&last) = result.path.last()
+            {
+                target = query.closest_point_on_polygon(last, target).0;
+            }
+            agent.corridor.set_corridor(target, &path);
+            agent.boundary.reset();
+            agent.is_partial = result.is_partial;
+            agent.target_state = MoveRequestState::Valid;
+            agent.target_replan_time = 0.0;
+        }
+    }
+
+    /// Periodically shortens the paths of the agents that follow a path.
+    fn update_topology_optimization(&mut self, dt: f32, query: &NavmeshQuery) {
+        for agent in self.agents.values_mut() {
+            if agent.state != CrowdAgentState::Walking
+                || agent.target_state != MoveRequestState::Valid
+                || !agent
+                    .params
+                    .update_flags
+                    .contains(CrowdUpdateFlags::OPTIMIZE_TOPOLOGY)
+            {
+                continue;
+            }
+            agent.topology_optimization_time += dt;
+            if agent.topology_optimization_time >= Self::TOPOLOGY_OPTIMIZATION_TIME {
+                // Failing to optimize leaves the path as it is.
+                let _ = agent.corridor.optimize_path_topology(query, &self.filter);
+                agent.topology_optimization_time = 0.0;
+            }
+        }
+    }
+
+    /// Returns the closest agents within the collision query range of the agent.
+    fn find_neighbors(&self, id: CrowdAgentId) -> Vec<CrowdNeighbor> {
+        let agent = &self.agents[id];
+        let range = agent.params.collision_query_range;
+        let extents = Vec3::new(range, 0.0, range);
+        let mut neighbors: Vec<_> = self
+            .grid
+            .query_items(agent.position - extents, agent.position + extents)
+            .into_iter()
+            .filter(|other| *other != id)
+            .filter_map(|other| {
+                let diff = agent.position - self.agents[other].position;
+                // Check for overlap.
+                if abs(diff.y) >= (agent.params.height + self.agents[other].params.height) * 0.5 {
+                    return None;
+                }
+                let distance_squared = diff.xz().length_squared();
+                (distance_squared <= range.squared()).then_some(CrowdNeighbor {
+                    agent: other,
+                    distance_squared,
+                })
+            })
+            .collect();
+        neighbors.sort_by(|a, b| {
+            a.distance_squared
+                .total_cmp(&b.distance_squared)
+                .then(a.agent.cmp(&b.agent))
+        });
+        neighbors.truncate(Self::MAX_NEIGHBORS);
+        neighbors
+    }
+
+    /// Adds the separation from the neighbors to the desired velocity of the agent, if enabled.
+    fn separate(&self, id: CrowdAgentId, desired_velocity: Vec3, desired_speed: f32) -> Vec3 {
+        let agent = &self.agents[id];
+        if !agent
+            .params
+            .update_flags
+            .contains(CrowdUpdateFlags::SEPARATION)
+        {
+            return desired_velocity;
+        }
+        let separation_distance = agent.params.collision_query_range;
+        let mut displacement = Vec3::ZERO;
+        let mut weight = 0.0;
+        for neighbor in &agent.neighbors {
+            let diff = (agent.position - self.agents[neighbor.agent].position).with_y(0.0);
+            let distance_squared = diff.length_squared();
+            if distance_squared < 0.00001 || distance_squared > separation_distance.squared() {
+                continue;
+            }
+            let distance = diff.length();
+            let separation_weight =
+                agent.params.separation_weight * (1.0 - (distance / separation_distance).squared());
+            displacement += diff * (separation_weight / distance);
+            weight += 1.0;
+        }
+        if weight < 0.0001 {
+            return desired_velocity;
+        }
+        // Adjust desired velocity.
+        let velocity = desired_velocity + displacement / weight;
+        // Clamp desired velocity to desired speed.
+        let speed_squared = velocity.length_squared();
+        if speed_squared > desired_speed.squared() {
+            velocity * (desired_speed.squared() / speed_squared)
+        } else {
+            velocity
+        }
+    }
+
+    /// Returns how far the agent has to move to resolve its overlaps with its neighbors.
+    fn collision_displacement(&self, id: CrowdAgentId) -> Vec3 {
+        let agent = &self.agents[id];
+        let mut displacement = Vec3::ZERO;
+        let mut weight = 0.0;
+        for neighbor in &agent.neighbors {
+            let other = &self.agents[neighbor.agent];
+            let mut diff = (agent.position - other.position).with_y(0.0);
+            let distance_squared = diff.length_squared();
+            let radius = agent.params.radius + other.params.radius;
+            if distance_squared > radius.squared() {
+                continue;
+            }
+            let distance = diff.length();
+            let penetration = if distance < 0.0001 {
+                // Agents on top of each other, try to choose diverging separation directions.
+                let velocity = agent.desired_velocity;
+                diff = if id > neighbor.agent {
+                    Vec3::new(-velocity.z, 0.0, velocity.x)
+                } else {
+                    Vec3::new(velocity.z, 0.0, -velocity.x)
+                };
+                0.01
+            } else {
+                (1.0 / distance) * ((radius - distance) * 0.5) * Self::COLLISION_RESOLVE_FACTOR
+            };
+            displacement += diff * penetration;
+            weight += 1.0;
+        }
+        if weight > 0.0001 {
+            displacement / weight
+        } else {
+            displacement
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OffMeshConnection, PolygonNavmesh, test_meshes};
+
+    fn run(crowd: &mut Crowd, query: &NavmeshQuery, steps: usize) {
+        for _ in 0..steps {
+            crowd.update(0.1, query);
+        }
+    }
+
+    fn move_to(crowd: &mut Crowd, agent: CrowdAgentId, target: Vec3, query: &NavmeshQuery) {
+        let (polygon, target) = query
+            .find_nearest_polygon(target, Vec3::ONE, crowd.filter())
+            .unwrap();
+        crowd
+            .request_move_target(agent, query.poly_ref(polygon), target)
+            .unwrap();
+    }
+
+    #[test]
+    fn agent_walks_to_target() {
+        let mesh = test_meshes::grid(4, 4);
+        let query = NavmeshQuery::new(&mesh);
+        let mut crowd = Crowd::new(0.6);
+        let agent = crowd.add_agent(
+            Vec3::new(5.0, 0.0, 5.0),
+            CrowdAgentParams::default(),
+            &query,
+        );
+        assert_eq!(
+            crowd.agent(agent).unwrap().state(),
+            CrowdAgentState::Walking
+        );
+
+        let target = Vec3::new(35.0, 0.0, 35.0);
+        move_to(&mut crowd, agent, target, &query);
+        run(&mut crowd, &query, 200);
+
+        let agent = crowd.agent(agent).unwrap();
+        assert_eq!(agent.target_state(), MoveRequestState::Valid);
+        assert!(
+            agent.position().distance(target) < 0.5,
+            "{}",
+            agent.position()
+        );
+        assert!(agent.velocity().length() < 0.5);
+    }
+
+    #[test]
+    fn clamps_non_positive_max_agent_radius() {
+        let mesh = test_meshes::grid(4, 4);
+        let query = NavmeshQuery::new(&mesh);
+        for max_agent_radius in [0.0, -1.0, f32::NAN] {
+            let mut crowd = Crowd::new(max_agent_radius);
+            let agent = crowd.add_agent(
+                Vec3::new(5.0, 0.0, 5.0),
+                CrowdAgentParams {
+                    radius: Crowd::MIN_AGENT_RADIUS,
+                    collision_query_range: Crowd::MIN_AGENT_RADIUS * 12.0,
+                    ..Default::default()
+                },
+                &query,
+            );
+            move_to(&mut crowd, agent, Vec3::new(15.0, 0.0, 5.0), &query);
+            run(&mut crowd, &query, 10);
+            assert!(crowd.agent(agent).unwrap().position().x > 6.0);
+        }
+    }
+
+    #[test]
+    fn agent_traverses_off_mesh_connection() {
+        let mut mesh = test_meshes::grid(4, 4);
+        // Cut the mesh into two halves along x = 20.
+        let nvp = mesh.max_vertices_per_polygon as usize;
+        for z in 0..4 {
+            mesh.polygon_neighbors[(z * 4 + 1) * nvp + 1] = PolygonNavmesh::NO_INDEX;
+            mesh.polygon_neighbors[(z * 4 + 2) * nvp + 3] = PolygonNavmesh::NO_INDEX;
+        }
+        let connection = OffMeshConnection {
+            start: Vec3::new(18.0, 0.0, 20.0),
+            end: Vec3::new(22.0, 0.0, 20.0),
+            ..Default::default()
+        };
+        let connections = [connection.snap(&NavmeshQuery::new(&mesh), 1.0).unwrap()];
+        let query = NavmeshQuery::new(&mesh).with_off_mesh_connections(&connections);
+
+        let mut crowd = Crowd::new(0.6);
+        let agent = crowd.add_agent(
+            Vec3::new(5.0, 0.0, 20.0),
+            CrowdAgentParams::default(),
+            &query,
+        );
+        let target = Vec3::new(35.0, 0.0, 20.0);
+        move_to(&mut crowd, agent, target, &query);
+
+        let mut traversed = false;
+        for _ in 0..200 {
+            crowd.update(0.1, &query);
+            let agent = crowd.agent(agent).unwrap();
+            if agent.state() == CrowdAgentState::OffMeshConnection {
+                assert_eq!(agent.off_mesh_connection(), Some(query.poly_ref(16)));
+                traversed = true;
+            }
+        }
+        assert!(traversed);
+        let agent = crowd.agent(agent).unwrap();
+        assert_eq!(agent.state(), CrowdAgentState::Walking);
+        assert!(
+            agent.position().distance(target) < 0.5,
+            "{}",
+            agent.position()
+        );
+    }
+
+    #[test]
+    fn agents_pass_each_other_deterministically() {
+        let mesh = test_meshes::grid(4, 4);
+        let query = NavmeshQuery::new(&mesh);
+        let simulate = || {
+            let mut crowd = Crowd::new(0.6);
+            let params = CrowdAgentParams {
+                update_flags: CrowdUpdateFlags::default() | CrowdUpdateFlags::SEPARATION,
+                ..Default::default()
+            };
+            let a = crowd.add_agent(Vec3::new(5.0, 0.0, 20.0), params, &query);
+            let b = crowd.add_agent(Vec3::new(35.0, 0.0, 20.0), params, &query);
+            move_to(&mut crowd, a, Vec3::new(35.0, 0.0, 20.0), &query);
+            move_to(&mut crowd, b, Vec3::new(5.0, 0.0, 20.0), &query);
+
+            let mut min_distance = f32::MAX;
+            for _ in 0..200 {
+                crowd.update(0.1, &query);
+                let (a, b) = (crowd.agent(a).unwrap(), crowd.agent(b).unwrap());
+                min_distance = min_distance.min(a.position().distance(b.position()));
+            }
+            let positions: Vec<_> = crowd.agents().map(|(_, agent)| agent.position()).collect();
+            (positions, min_distance)
+        };
+
+        let (positions, min_distance) = simulate();
+        assert!(min_distance > 0.6 * 2.0 * 0.8, "{min_distance}");
+        assert!(positions[0].distance(Vec3::new(35.0, 0.0, 20.0)) < 1.0);
+        assert!(positions[1].distance(Vec3::new(5.0, 0.0, 20.0)) < 1.0);
+        assert_eq!(simulate().0, positions);
+    }
+
+    #[test]
+    fn replaced_tile_triggers_replanning() {
+        let mesh = test_meshes::grid(4, 4);
+        let query = NavmeshQuery::new(&mesh);
+        let mut crowd = Crowd::new(0.6);
+        let agent = crowd.add_agent(
+            Vec3::new(5.0, 0.0, 5.0),
+            CrowdAgentParams::default(),
+            &query,
+        );
+        move_to(&mut crowd, agent, Vec3::new(35.0, 0.0, 5.0), &query);
+        run(&mut crowd, &query, 5);
+
+        // The same tile with a new salt, as after regenerating it.
+        let query = NavmeshQuery::new(&mesh).with_tile_ref(PolyRef::new(2, 0, 0));
+        crowd.update(0.1, &query);
+        let state = crowd.agent(agent).unwrap();
+        assert_eq!(state.state(), CrowdAgentState::Walking);
+        assert!(
+            query
+                .polygon_by_ref(state.corridor().first_polygon())
+                .is_ok()
+        );
+
+        run(&mut crowd, &query, 200);
+        let state = crowd.agent(agent).unwrap();
+        assert!(state.position().distance(Vec3::new(35.0, 0.0, 5.0)) < 0.5);
+    }
+}
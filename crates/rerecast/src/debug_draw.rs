@@ -0,0 +1,599 @@
+//! Renderer-agnostic visualization of the stages of the build process, modelled after Recast's `RecastDebugDraw`.
+
+use alloc::vec::Vec;
+use glam::{U16Vec3, Vec3};
+
+use crate::{
+    AreaType, CompactHeightfield, Contour, ContourSet, DetailNavmesh, Heightfield, PolygonNavmesh,
+    RegionVertexId, SubMesh,
+};
+
+/// A color in RGBA order.
+pub type DebugColor = [u8; 4];
+
+/// The kind of primitive drawn between [`DebugDraw::begin`] and [`DebugDraw::end`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugDrawPrimitive {
+    /// Every vertex is a point.
+    Points,
+    /// Every two vertices form a line.
+    Lines,
+    /// Every three vertices form a triangle.
+    Triangles,
+    /// Every four vertices form a quad.
+    Quads,
+}
+
+/// A renderer the `debug_draw` methods of the build stages can draw to, e.g. a gizmo system or an immediate mode renderer.
+///
+/// Every drawing starts with [`DebugDraw::begin`], followed by the vertices of the primitives and ends with [`DebugDraw::end`].
+pub trait DebugDraw {
+    /// Starts drawing primitives of the given kind.
+    /// `size` is the size of points or the width of lines, and `1.0` for triangles and quads.
+    fn begin(&mut self, primitive: DebugDrawPrimitive, size: f32);
+
+    /// Adds a vertex of the current primitive.
+    fn vertex(&mut self, position: Vec3, color: DebugColor);
+
+    /// Finishes the primitives started with [`DebugDraw::begin`].
+    fn end(&mut self);
+
+    /// Returns the color used for an area type. Defaults to [`AreaType::debug_color`].
+    fn area_color(&self, area: AreaType) -> DebugColor {
+        area.debug_color()
+    }
+}
+
+/// The colors of all area types, looked up before drawing starts.
+fn area_palette(dd: &impl DebugDraw) -> [DebugColor; 256] {
+    core::array::from_fn(|area| dd.area_color(AreaType(area as u8)))
+}
+
+/// The translucent color of walkable surfaces with the given area type.
+fn surface_color(palette: &[DebugColor; 256], area: AreaType) -> DebugColor {
+    match area {
+        AreaType::DEFAULT_WALKABLE => [0, 192, 255, 64],
+        AreaType::NOT_WALKABLE => [0, 0, 0, 64],
+        area => with_alpha(palette[area.0 as usize], 64),
+    }
+}
+
+impl AreaType {
+    /// The color Recast uses to visualize an area type: light blue for [`AreaType::DEFAULT_WALKABLE`],
+    /// transparent for [`AreaType::NOT_WALKABLE`] and a distinct color for all other areas.
+    pub fn debug_color(self) -> DebugColor {
+        match self {
+            AreaType::DEFAULT_WALKABLE => [0, 192, 255, 255],
+            AreaType::NOT_WALKABLE => [0, 0, 0, 0],
+            AreaType(area) => int_to_color(area.into(), 255),
+        }
+    }
+}
+
+/// Recast's `duIntToCol`, which gives nearby integers clearly different colors.
+pub(crate) fn int_to_color(i: u32, alpha: u8) -> DebugColor {
+    let bit = |shift: u32| ((i >> shift) & 1) as u8;
+    [
+        (bit(1) + bit(3) * 2 + 1) * 63,
+        (bit(2) + bit(4) * 2 + 1) * 63,
+        (bit(0) + bit(5) * 2 + 1) * 63,
+        alpha,
+    ]
+}
+
+/// Scales the RGB channels of `color` by `factor / 255`.
+fn multiply(color: DebugColor, factor: u32) -> DebugColor {
+    let scale = |channel: u8| (channel as u32 * factor / 255) as u8;
+    [scale(color[0]), scale(color[1]), scale(color[2]), color[3]]
+}
+
+/// Interpolates between `a` and `b`, with `t` from `0` (`a`) to `255` (`b`).
+fn lerp(a: DebugColor, b: DebugColor, t: u32) -> DebugColor {
+    core::array::from_fn(|i| ((a[i] as u32 * (255 - t) + b[i] as u32 * t) / 255) as u8)
+}
+
+fn with_alpha(color: DebugColor, alpha: u8) -> DebugColor {
+    [color[0], color[1], color[2], alpha]
+}
+
+/// Recast's `duCalcBoxColors`: the top face in `top`, the sides shaded from `side`.
+fn box_colors(top: DebugColor, side: DebugColor) -> [DebugColor; 6] {
+    [
+        multiply(top, 250),
+        multiply(side, 140),
+        multiply(side, 165),
+        multiply(side, 217),
+        multiply(side, 165),
+        multiply(side, 217),
+    ]
+}
+
+/// Recast's `duAppendBox`: appends the six faces of a box as quads.
+fn append_box(dd: &mut impl DebugDraw, min: Vec3, max: Vec3, colors: &[DebugColor; 6]) {
+    let corner = |i: usize| {
+        Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 4 == 0 { min.y } else { max.y },
+            if i & 2 == 0 { min.z } else { max.z },
+        )
+    };
+    const FACES: [[usize; 4]; 6] = [
+        [7, 6, 4, 5],
+        [0, 1, 3, 2],
+        [6, 2, 0, 4],
+        [4, 0, 1, 5],
+        [5, 1, 3, 7],
+        [7, 3, 2, 6],
+    ];
+    for (face, color) in FACES.iter().zip(colors) {
+        for i in face {
+            dd.vertex(corner(*i), *color);
+        }
+    }
+}
+
+impl Heightfield {
+    /// Draws every span as a white box.
+    pub fn debug_draw(&self, dd: &mut impl DebugDraw) {
+        let colors = box_colors([255; 4], [217, 217, 217, 255]);
+        self.draw_spans(dd, |_| colors);
+    }
+
+    /// Draws every span as a box colored by whether it is walkable.
+    pub fn debug_draw_walkable(&self, dd: &mut impl DebugDraw) {
+        let palette = area_palette(dd);
+        self.draw_spans(dd, |area| {
+            let top = match area {
+                AreaType::DEFAULT_WALKABLE => [64, 128, 160, 255],
+                AreaType::NOT_WALKABLE => [64, 64, 64, 255],
+                area => multiply(palette[area.0 as usize], 200),
+            };
+            box_colors(top, [217, 217, 217, 255])
+        });
+    }
+
+    fn draw_spans(
+        &self,
+        dd: &mut impl DebugDraw,
+        mut colors: impl FnMut(AreaType) -> [DebugColor; 6],
+    ) {
+        dd.begin(DebugDrawPrimitive::Quads, 1.0);
+        for z in 0..self.height {
+            for x in 0..self.width {
+                let min_x = self.aabb.min.x + x as f32 * self.cell_size;
+                let min_z = self.aabb.min.z + z as f32 * self.cell_size;
+                let mut span_key = self.span_key_at(x, z);
+                while let Some(key) = span_key {
+                    let span = self.span(key);
+                    append_box(
+                        dd,
+                        Vec3::new(
+                            min_x,
+                            self.aabb.min.y + span.min as f32 * self.cell_height,
+                            min_z,
+                        ),
+                        Vec3::new(
+                            min_x + self.cell_size,
+                            self.aabb.min.y + span.max as f32 * self.cell_height,
+                            min_z + self.cell_size,
+                        ),
+                        &colors(span.area),
+                    );
+                    span_key = span.next;
+                }
+            }
+        }
+        dd.end();
+    }
+}
+
+impl CompactHeightfield {
+    /// Draws the top of every span, colored by its area type.
+    pub fn debug_draw(&self, dd: &mut impl DebugDraw) {
+        let palette = area_palette(dd);
+        self.draw_span_tops(dd, |i| surface_color(&palette, self.areas[i]));
+    }
+
+    /// Draws the top of every span, colored by its region.
+    pub fn debug_draw_regions(&self, dd: &mut impl DebugDraw) {
+        self.draw_span_tops(dd, |i| {
+            let region = self.spans[i].region.bits();
+            if region == 0 {
+                [0, 0, 0, 64]
+            } else {
+                int_to_color(region.into(), 192)
+            }
+        });
+    }
+
+    /// Draws the top of every span in a shade of gray by its distance to the border,
+    /// as calculated by [`CompactHeightfield::build_distance_field`].
+    pub fn debug_draw_distance(&self, dd: &mut impl DebugDraw) {
+        if self.dist.len() != self.spans.len() {
+            return;
+        }
+        let max_distance = self.max_distance.max(1) as u32;
+        self.draw_span_tops(dd, |i| {
+            let shade = (self.dist[i] as u32 * 255 / max_distance).min(255) as u8;
+            [shade, shade, shade, 255]
+        });
+    }
+
+    fn draw_span_tops(&self, dd: &mut impl DebugDraw, mut color: impl FnMut(usize) -> DebugColor) {
+        dd.begin(DebugDrawPrimitive::Quads, 1.0);
+        for z in 0..self.height {
+            for x in 0..self.width {
+                let min_x = self.aabb.min.x + x as f32 * self.cell_size;
+                let min_z = self.aabb.min.z + z as f32 * self.cell_size;
+                let cell = &self.cells[x as usize + z as usize * self.width as usize];
+                for i in cell.index_range() {
+                    let y = self.aabb.min.y + (self.spans[i].y + 1) as f32 * self.cell_height;
+                    let color = color(i);
+                    dd.vertex(Vec3::new(min_x, y, min_z), color);
+                    dd.vertex(Vec3::new(min_x, y, min_z + self.cell_size), color);
+                    dd.vertex(
+                        Vec3::new(min_x + self.cell_size, y, min_z + self.cell_size),
+                        color,
+                    );
+                    dd.vertex(Vec3::new(min_x + self.cell_size, y, min_z), color);
+                }
+            }
+        }
+        dd.end();
+    }
+}
+
+impl ContourSet {
+    /// Draws the raw contours, colored by region. `alpha` is the opacity of the lines.
+    pub fn debug_draw_raw(&self, dd: &mut impl DebugDraw, alpha: u8) {
+        self.draw_contours(
+            dd,
+            alpha,
+            |contour| {
+                contour
+                    .raw_vertices
+                    .iter()
+                    .map(|(vertex, id)| (*vertex, id.contains(RegionVertexId::BORDER_VERTEX)))
+                    .collect()
+            },
+            1.0,
+        );
+    }
+
+    /// Draws the simplified contours, colored by region. `alpha` is the opacity of the lines.
+    pub fn debug_draw(&self, dd: &mut impl DebugDraw, alpha: u8) {
+        self.draw_contours(
+            dd,
+            alpha,
+            |contour| {
+                contour
+                    .vertices
+                    .iter()
+                    .map(|(vertex, id)| (*vertex, id & RegionVertexId::BORDER_VERTEX.bits() != 0))
+                    .collect()
+            },
+            2.5,
+        );
+    }
+
+    fn draw_contours(
+        &self,
+        dd: &mut impl DebugDraw,
+        alpha: u8,
+        vertices: impl Fn(&Contour) -> Vec<(U16Vec3, bool)>,
+        line_width: f32,
+    ) {
+        // Raise the contours slightly above the surface they outline.
+        let to_world = |v: U16Vec3| {
+            Vec3::new(
+                self.aabb.min.x + v.x as f32 * self.cell_size,
+                self.aabb.min.y + (v.y + 1) as f32 * self.cell_height + 0.01,
+                self.aabb.min.z + v.z as f32 * self.cell_size,
+            )
+        };
+        let contours: Vec<_> = self
+            .contours
+            .iter()
+            .map(|contour| {
+                let color = int_to_color(contour.region.bits().into(), alpha);
+                (lerp(color, [255, 255, 255, alpha], 128), vertices(contour))
+            })
+            .collect();
+
+        dd.begin(DebugDrawPrimitive::Lines, line_width);
+        for (color, vertices) in &contours {
+            for (i, (vertex, _)) in vertices.iter().enumerate() {
+                let (next, _) = vertices[(i + 1) % vertices.len()];
+                dd.vertex(to_world(*vertex), *color);
+                dd.vertex(to_world(next), *color);
+            }
+        }
+        dd.end();
+
+        dd.begin(DebugDrawPrimitive::Points, 2.0 * line_width);
+        for (color, vertices) in &contours {
+            let color = multiply(*color, 128);
+            for (vertex, border) in vertices {
+                let color = if *border {
+                    [255, 255, 255, alpha]
+                } else {
+                    color
+                };
+                dd.vertex(to_world(*vertex), color);
+            }
+        }
+        dd.end();
+    }
+}
+
+impl PolygonNavmesh {
+    /// Draws the polygons colored by area type, their edges, portals to neighboring tiles in white, and their vertices.
+    pub fn debug_draw(&self, dd: &mut impl DebugDraw) {
+        let nvp = self.max_vertices_per_polygon as usize;
+        // Raise the mesh slightly above the surface, as the vertices are at the height of the spans.
+        let to_world = |i: u16| {
+            let v = self.vertices[i as usize];
+            Vec3::new(
+                self.aabb.min.x + v.x as f32 * self.cell_size,
+                self.aabb.min.y + (v.y + 1) as f32 * self.cell_height,
+                self.aabb.min.z + v.z as f32 * self.cell_size,
+            )
+        };
+        let polygons: Vec<Vec<u16>> = self.polygons().map(Iterator::collect).collect();
+        let palette = area_palette(dd);
+
+        dd.begin(DebugDrawPrimitive::Triangles, 1.0);
+        for (polygon, indices) in polygons.iter().enumerate() {
+            let area = self.areas.get(polygon).copied().unwrap_or_default();
+            let color = surface_color(&palette, area);
+            for j in 2..indices.len() {
+                for index in [indices[0], indices[j - 1], indices[j]] {
+                    dd.vertex(to_world(index), color);
+                }
+            }
+        }
+        dd.end();
+
+        // Internal edges are drawn faintly, borders opaque and portals to other tiles in white.
+        let edges = |polygon: usize| {
+            let indices = &polygons[polygon];
+            (0..indices.len()).map(move |j| {
+                let neighbor = self
+                    .polygon_neighbors
+                    .get(polygon * nvp + j)
+                    .copied()
+                    .unwrap_or(Self::NO_CONNECTION);
+                (indices[j], indices[(j + 1) % indices.len()], neighbor)
+            })
+        };
+        dd.begin(DebugDrawPrimitive::Lines, 1.5);
+        for polygon in 0..polygons.len() {
+            for (a, b, neighbor) in edges(polygon) {
+                if neighbor & 0x8000 == 0 && (polygon as u16) < neighbor {
+                    dd.vertex(to_world(a), [0, 48, 64, 32]);
+                    dd.vertex(to_world(b), [0, 48, 64, 32]);
+                }
+            }
+        }
+        dd.end();
+        dd.begin(DebugDrawPrimitive::Lines, 2.5);
+        for polygon in 0..polygons.len() {
+            for (a, b, neighbor) in edges(polygon) {
+                if neighbor & 0x8000 == 0 {
+                    continue;
+                }
+                let color = if neighbor != Self::NO_CONNECTION && neighbor & 0xf != 0xf {
+                    [255, 255, 255, 128]
+                } else {
+                    [0, 48, 64, 220]
+                };
+                dd.vertex(to_world(a), color);
+                dd.vertex(to_world(b), color);
+            }
+        }
+        dd.end();
+
+        dd.begin(DebugDrawPrimitive::Points, 3.0);
+        for i in 0..self.vertices.len() {
+            dd.vertex(to_world(i as u16), [0, 0, 0, 220]);
+        }
+        dd.end();
+    }
+}
+
+impl DetailNavmesh {
+    /// Draws the triangles of each sub-mesh in a distinct color, their edges and vertices.
+    pub fn debug_draw(&self, dd: &mut impl DebugDraw) {
+        dd.begin(DebugDrawPrimitive::Triangles, 1.0);
+        for (i, submesh) in self.meshes.iter().enumerate() {
+            let color = int_to_color(i as u32, 192);
+            for (triangle, _) in self.submesh_triangles(submesh) {
+                for index in triangle {
+                    dd.vertex(self.vertices[index], color);
+                }
+            }
+        }
+        dd.end();
+
+        // Internal edges are shared by two triangles, so only draw them once.
+        dd.begin(DebugDrawPrimitive::Lines, 1.0);
+        for (a, b, external) in self.edges() {
+            if !external && a < b {
+                dd.vertex(self.vertices[a], [0, 0, 0, 64]);
+                dd.vertex(self.vertices[b], [0, 0, 0, 64]);
+            }
+        }
+        dd.end();
+        dd.begin(DebugDrawPrimitive::Lines, 2.0);
+        for (a, b, external) in self.edges() {
+            if external {
+                dd.vertex(self.vertices[a], [0, 0, 0, 64]);
+                dd.vertex(self.vertices[b], [0, 0, 0, 64]);
+            }
+        }
+        dd.end();
+
+        dd.begin(DebugDrawPrimitive::Points, 3.0);
+        for vertex in &self.vertices {
+            dd.vertex(*vertex, [0, 0, 0, 64]);
+        }
+        dd.end();
+    }
+
+    /// Iterates over the triangles of a sub-mesh as global vertex indices, with their edge flags.
+    fn submesh_triangles(&self, submesh: &SubMesh) -> impl Iterator<Item = ([usize; 3], u8)> {
+        let base = submesh.base_vertex_index as usize;
+        let range = submesh.base_triangle_index as usize..;
+        let count = submesh.triangle_count as usize;
+        self.triangles[range.clone()][..count]
+            .iter()
+            .zip(
+                self.triangle_flags
+                    .get(range)
+                    .unwrap_or_default()
+                    .iter()
+                    .chain(core::iter::repeat(&0)),
+            )
+            .map(move |(triangle, flags)| (triangle.map(|i| base + i as usize), *flags))
+    }
+
+    /// Iterates over the edges of all triangles as global vertex indices, and whether they are external to their sub-mesh.
+    fn edges(&self) -> impl Iterator<Item = (usize, usize, bool)> {
+        self.meshes
+            .iter()
+            .flat_map(|submesh| self.submesh_triangles(submesh))
+            .flat_map(|(triangle, flags)| {
+                (0..3).map(move |k| {
+                    (
+                        triangle[k],
+                        triangle[(k + 1) % 3],
+                        (flags >> (k * 2)) & 0x3 != 0,
+                    )
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Aabb3d, HeightfieldBuilder};
+
+    /// Records every batch of primitives.
+    #[derive(Default)]
+    struct Recorder {
+        batches: Vec<(DebugDrawPrimitive, Vec<(Vec3, DebugColor)>)>,
+        open: bool,
+    }
+
+    impl DebugDraw for Recorder {
+        fn begin(&mut self, primitive: DebugDrawPrimitive, _size: f32) {
+            assert!(!self.open, "begin called twice");
+            self.open = true;
+            self.batches.push((primitive, Vec::new()));
+        }
+
+        fn vertex(&mut self, position: Vec3, color: DebugColor) {
+            assert!(self.open, "vertex called outside of begin and end");
+            self.batches.last_mut().unwrap().1.push((position, color));
+        }
+
+        fn end(&mut self) {
+            assert!(self.open, "end called without begin");
+            self.open = false;
+        }
+    }
+
+    #[test]
+    fn draws_heightfield_spans_as_boxes() {
+        let mut heightfield = HeightfieldBuilder {
+            aabb: Aabb3d {
+                min: Vec3::ZERO,
+                max: Vec3::new(2.0, 4.0, 1.0),
+            },
+            cell_size: 1.0,
+            cell_height: 0.5,
+        }
+        .build()
+        .unwrap();
+        heightfield.spans = vec![None; 2];
+        for (x, area) in [(0, AreaType::DEFAULT_WALKABLE), (1, AreaType(3))] {
+            let key = heightfield.allocated_spans.insert(crate::Span {
+                min: 0,
+                max: 2,
+                area,
+                next: None,
+            });
+            heightfield.spans[x] = Some(key);
+        }
+
+        let mut dd = Recorder::default();
+        heightfield.debug_draw_walkable(&mut dd);
+        let [(DebugDrawPrimitive::Quads, vertices)] = dd.batches.as_slice() else {
+            panic!("expected a single batch of quads");
+        };
+        assert_eq!(vertices.len(), 2 * 6 * 4);
+        // The top face is drawn first.
+        assert!(vertices[..4].iter().all(|(v, _)| v.y == 1.0 && v.x <= 1.0));
+        assert_eq!(vertices[0].1, multiply([64, 128, 160, 255], 250));
+        assert_eq!(
+            vertices[24].1,
+            multiply(multiply(AreaType(3).debug_color(), 200), 250)
+        );
+    }
+
+    #[test]
+    fn draws_portals_and_borders_of_polygons() {
+        // Two quads sharing an edge, the right one with a portal to the next tile in +x.
+        let mesh = PolygonNavmesh {
+            vertices: vec![
+                U16Vec3::new(0, 0, 0),
+                U16Vec3::new(0, 0, 1),
+                U16Vec3::new(1, 0, 1),
+                U16Vec3::new(1, 0, 0),
+                U16Vec3::new(2, 0, 1),
+                U16Vec3::new(2, 0, 0),
+            ],
+            polygons: vec![0, 1, 2, 3, 3, 2, 4, 5],
+            polygon_neighbors: vec![
+                PolygonNavmesh::NO_CONNECTION,
+                PolygonNavmesh::NO_CONNECTION,
+                1,
+                PolygonNavmesh::NO_CONNECTION,
+                0,
+                PolygonNavmesh::NO_CONNECTION,
+                // Portals are stored as `0x8000 | side`, where side 2 is +x.
+                0x8000 | 2,
+                PolygonNavmesh::NO_CONNECTION,
+            ],
+            areas: vec![AreaType::DEFAULT_WALKABLE; 2],
+            max_vertices_per_polygon: 4,
+            cell_size: 1.0,
+            cell_height: 1.0,
+            ..Default::default()
+        };
+        let mut dd = Recorder::default();
+        mesh.debug_draw(&mut dd);
+        let primitives: Vec<_> = dd.batches.iter().map(|(p, v)| (*p, v.len())).collect();
+        assert_eq!(
+            primitives,
+            [
+                (DebugDrawPrimitive::Triangles, 12),
+                // The shared edge is drawn once.
+                (DebugDrawPrimitive::Lines, 2),
+                (DebugDrawPrimitive::Lines, 12),
+                (DebugDrawPrimitive::Points, 6),
+            ]
+        );
+        let portals: Vec<_> = dd.batches[2]
+            .1
+            .iter()
+            .filter(|(_, color)| *color == [255, 255, 255, 128])
+            .map(|(position, _)| *position)
+            .collect();
+        assert_eq!(
+            portals,
+            [Vec3::new(2.0, 1.0, 1.0), Vec3::new(2.0, 1.0, 0.0)]
+        );
+    }
+}
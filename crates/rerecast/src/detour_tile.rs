@@ -0,0 +1,997 @@
+//! Conversion of [`NavmeshTile`]s to and from the tile data produced by Detour's `dtCreateNavMeshData`.
+
+use alloc::vec::Vec;
+use core::iter;
+use glam::{U16Vec3, Vec3};
+use thiserror::Error;
+
+use crate::{
+    AreaType, Config, DetailNavmesh, NavmeshQuery, NavmeshTile, OffMeshConnection, PolygonNavmesh,
+    SubMesh, TileCoord,
+    ops::{ceil, floor},
+};
+
+/// `DT_NAVMESH_MAGIC`
+const MAGIC: i32 = (b'D' as i32) << 24 | (b'N' as i32) << 16 | (b'A' as i32) << 8 | b'V' as i32;
+/// `DT_NAVMESH_VERSION`
+const VERSION: i32 = 7;
+/// `DT_VERTS_PER_POLYGON`
+const VERTS_PER_POLYGON: usize = 6;
+/// `DT_EXT_LINK`
+const EXT_LINK: u16 = 0x8000;
+/// `DT_OFFMESH_CON_BIDIR`
+const OFFMESH_CON_BIDIR: u8 = 1;
+/// `DT_POLYTYPE_OFFMESH_CONNECTION`
+const POLYTYPE_OFFMESH_CONNECTION: u8 = 1;
+/// The area type Recast's samples use for walkable polygons, `RC_WALKABLE_AREA`.
+const WALKABLE_AREA: u8 = 63;
+
+/// The directions of the sides of a tile in Detour, as `(x, z)` steps to the neighboring tile.
+const SIDE_DIRECTIONS: [(i32, i32); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+/// `sizeof(dtMeshHeader)`
+const HEADER_SIZE: usize = 100;
+/// `sizeof(dtLink)` with 32 bit polygon references.
+const LINK_SIZE: usize = 12;
+/// `sizeof(dtBVNode)`
+const BV_NODE_SIZE: usize = 16;
+
+impl NavmeshTile {
+    /// Encodes the tile in the byte layout of Detour's `dtCreateNavMeshData`, so that it can be added to a `dtNavMesh` with `addTile`.
+    ///
+    /// The data is little-endian, uses 32 bit polygon references and contains a BV tree.
+    /// `config` must be the configuration the tile was generated with, it provides the agent dimensions of the tile header.
+    ///
+    /// Area types are limited to 6 bits in Detour. [`AreaType::DEFAULT_WALKABLE`] is stored as Recast's walkable area `63`,
+    /// other area types above `63` cannot be encoded.
+    ///
+    /// An empty detail mesh is written as a fan triangulation of each polygon, like Detour does. Any other detail mesh must have one sub-mesh per polygon.
+    pub fn to_detour_tile_data(&self, config: &Config) -> Result<Vec<u8>, DetourTileDataError> {
+        let mesh = &self.poly_mesh;
+        let nvp = mesh.max_vertices_per_polygon as usize;
+        if nvp > VERTS_PER_POLYGON {
+            return Err(DetourTileDataError::TooManyVerticesPerPolygon(nvp));
+        }
+        let ground_polygons: Vec<Vec<u16>> = mesh.polygons().map(Iterator::collect).collect();
+        let poly_count = ground_polygons.len();
+        let connections = &self.off_mesh_connections;
+        let vert_count = mesh.vertices.len() + connections.len() * 2;
+        if vert_count > u16::MAX as usize {
+            return Err(DetourTileDataError::TooManyVertices(vert_count));
+        }
+        let areas = mesh
+            .areas
+            .iter()
+            .copied()
+            .chain(connections.iter().map(|c| c.connection.area))
+            .map(area_to_detour)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Links are created by Detour when the tile is added, but their space is part of the data.
+        let mut edge_count = 0;
+        let mut portal_count = 0;
+        for (polygon, vertices) in ground_polygons.iter().enumerate() {
+            edge_count += vertices.len();
+            portal_count += mesh.polygon_neighbors[polygon * nvp..][..vertices.len()]
+                .iter()
+                .filter(|&&neighbor| neighbor & 0x8000 != 0 && neighbor & 0xf != 0xf)
+                .count();
+        }
+        // Each off-mesh connection needs up to two links at each of its end points.
+        let max_link_count = edge_count + portal_count * 2 + connections.len() * 4;
+
+        // Without a detail mesh, Detour triangulates each polygon as a fan.
+        let detail = &self.detail_mesh;
+        let has_detail = !detail.meshes.is_empty();
+        if has_detail && detail.meshes.len() != poly_count {
+            return Err(DetourTileDataError::Malformed(
+                "the detail mesh does not have one sub-mesh per polygon",
+            ));
+        }
+        let (detail_vert_count, detail_tri_count) = if has_detail {
+            let unique_vertices = detail
+                .meshes
+                .iter()
+                .zip(&ground_polygons)
+                .map(|(submesh, vertices)| submesh.vertex_count as usize - vertices.len())
+                .sum();
+            (unique_vertices, detail.triangles.len())
+        } else {
+            let triangles = ground_polygons.iter().map(|v| v.len() - 2).sum();
+            (0, triangles)
+        };
+
+        let bv_tree = self.build_bv_tree(&ground_polygons, has_detail);
+
+        let mut out = Writer(Vec::new());
+        // dtMeshHeader
+        out.i32(MAGIC);
+        out.i32(VERSION);
+        out.i32(self.coord.x as i32);
+        out.i32(self.coord.z as i32);
+        out.i32(0); // layer
+        out.u32(0); // userId
+        out.i32((poly_count + connections.len()) as i32);
+        out.i32(vert_count as i32);
+        out.i32(max_link_count as i32);
+        out.i32(poly_count as i32); // detailMeshCount
+        out.i32(detail_vert_count as i32);
+        out.i32(detail_tri_count as i32);
+        out.i32(bv_tree.len() as i32);
+        out.i32(connections.len() as i32);
+        out.i32(poly_count as i32); // offMeshBase
+        out.f32(config.walkable_height as f32 * config.cell_height);
+        out.f32(config.walkable_radius as f32 * config.cell_size);
+        out.f32(config.walkable_climb as f32 * config.cell_height);
+        out.vec3(mesh.aabb.min);
+        out.vec3(mesh.aabb.max);
+        out.f32(1.0 / mesh.cell_size); // bvQuantFactor
+        debug_assert_eq!(out.0.len(), HEADER_SIZE);
+
+        // Vertices, followed by the two end points of each off-mesh connection.
+        for i in 0..mesh.vertices.len() {
+            out.vec3(mesh.world_vertex(i as u16));
+        }
+        for connection in connections {
+            out.vec3(connection.connection.start);
+            out.vec3(connection.connection.end);
+        }
+
+        // dtPoly
+        for (polygon, vertices) in ground_polygons.iter().enumerate() {
+            out.u32(0); // firstLink, set by Detour
+            out.padded_u16s(vertices.iter().copied());
+            out.padded_u16s(
+                mesh.polygon_neighbors[polygon * nvp..][..vertices.len()]
+                    .iter()
+                    .map(|&neighbor| neighbor_to_detour(neighbor)),
+            );
+            out.u16(mesh.flags[polygon]);
+            out.u8(vertices.len() as u8);
+            out.u8(areas[polygon]);
+        }
+        let connection_vertex_base = mesh.vertices.len() as u16;
+        for (i, connection) in connections.iter().enumerate() {
+            let first = connection_vertex_base + i as u16 * 2;
+            out.u32(0);
+            out.padded_u16s([first, first + 1].into_iter());
+            out.padded_u16s(iter::empty());
+            out.u16(connection.connection.flags);
+            out.u8(2);
+            out.u8(areas[poly_count + i] | POLYTYPE_OFFMESH_CONNECTION << 6);
+        }
+
+        // dtLink
+        out.zeros(max_link_count * LINK_SIZE);
+
+        // dtPolyDetail, without the polygon vertices which Detour takes from the polygons.
+        if has_detail {
+            let mut vertex_base = 0;
+            for (submesh, vertices) in detail.meshes.iter().zip(&ground_polygons) {
+                let unique_vertices = submesh.vertex_count as usize - vertices.len();
+                out.u32(vertex_base as u32);
+                out.u32(submesh.base_triangle_index);
+                out.u8(unique_vertices as u8);
+                out.u8(submesh.triangle_count as u8);
+                out.zeros(2);
+                vertex_base += unique_vertices;
+            }
+            for (submesh, vertices) in detail.meshes.iter().zip(&ground_polygons) {
+                let start = submesh.base_vertex_index as usize + vertices.len();
+                let end = (submesh.base_vertex_index + submesh.vertex_count) as usize;
+                for &vertex in &detail.vertices[start..end] {
+                    out.vec3(vertex);
+                }
+            }
+            for (triangle, &flags) in detail.triangles.iter().zip(&detail.triangle_flags) {
+                out.0.extend_from_slice(triangle);
+                out.u8(flags);
+            }
+        } else {
+            // A dummy sub-mesh per polygon, without vertices of its own.
+            let mut triangle_base = 0;
+            for vertices in &ground_polygons {
+                let triangle_count = vertices.len() - 2;
+                out.u32(0);
+                out.u32(triangle_base as u32);
+                out.u8(0);
+                out.u8(triangle_count as u8);
+                out.zeros(2);
+                triangle_base += triangle_count;
+            }
+            for vertices in &ground_polygons {
+                let count = vertices.len() as u8;
+                for j in 2..count {
+                    // Flag the edges on the polygon boundary.
+                    let mut flags = 1 << 2;
+                    if j == 2 {
+                        flags |= 1;
+                    }
+                    if j == count - 1 {
+                        flags |= 1 << 4;
+                    }
+                    out.0.extend_from_slice(&[0, j - 1, j, flags]);
+                }
+            }
+        }
+
+        // dtBVNode
+        for node in &bv_tree {
+            node.min.iter().for_each(|&v| out.u16(v));
+            node.max.iter().for_each(|&v| out.u16(v));
+            out.i32(node.index);
+        }
+
+        // dtOffMeshConnection
+        for (i, connection) in connections.iter().enumerate() {
+            out.vec3(connection.connection.start);
+            out.vec3(connection.connection.end);
+            out.f32(connection.connection.radius);
+            out.u16((poly_count + i) as u16);
+            out.u8(if connection.connection.bidirectional {
+                OFFMESH_CON_BIDIR
+            } else {
+                0
+            });
+            out.u8(side_to_detour(self.coord, connection.end_tile));
+            out.u32(connection.connection.user_id);
+        }
+
+        Ok(out.0)
+    }
+
+    /// Decodes tile data in the byte layout of Detour's `dtCreateNavMeshData`, as written by [`Self::to_detour_tile_data`].
+    ///
+    /// The data must be little-endian and use 32 bit polygon references.
+    /// Detour does not store the cell height, so the vertices are quantized with the cell size and cell height of `config`.
+    /// Polygons may have up to [`Config::max_vertices_per_polygon`] vertices.
+    ///
+    /// Off-mesh connections are snapped to the decoded polygons like during generation and dropped if they cannot be snapped.
+    /// Connections that lead into another tile are snapped at their start, with the neighboring tile on their side as
+    /// [`SnappedOffMeshConnection::end_tile`](crate::SnappedOffMeshConnection::end_tile).
+    /// The polygon regions are not part of the data and are left empty.
+    pub fn from_detour_tile_data(
+        data: &[u8],
+        config: &Config,
+    ) -> Result<Self, DetourTileDataError> {
+        let mut data = Reader(data);
+        if data.i32()? != MAGIC {
+            return Err(DetourTileDataError::InvalidMagic);
+        }
+        let version = data.i32()?;
+        if version != VERSION {
+            return Err(DetourTileDataError::UnsupportedVersion(version));
+        }
+        let (x, z) = (data.i32()?, data.i32()?);
+        let coord = match (u16::try_from(x), u16::try_from(z)) {
+            (Ok(x), Ok(z)) => TileCoord { x, z },
+            _ => return Err(DetourTileDataError::InvalidTileCoord { x, z }),
+        };
+        let _layer = data.i32()?;
+        let _user_id = data.u32()?;
+        let poly_count = data.count()?;
+        let vert_count = data.count()?;
+        let max_link_count = data.count()?;
+        let detail_mesh_count = data.count()?;
+        let detail_vert_count = data.count()?;
+        let detail_tri_count = data.count()?;
+        let bv_node_count = data.count()?;
+        let connection_count = data.count()?;
+        let off_mesh_base = data.count()?;
+        let _walkable_height = data.f32()?;
+        let _walkable_radius = data.f32()?;
+        let walkable_climb = data.f32()?;
+        let aabb_min = data.vec3()?;
+        let aabb_max = data.vec3()?;
+        let _bv_quant_factor = data.f32()?;
+
+        let ground_vert_count = vert_count
+            .checked_sub(connection_count * 2)
+            .ok_or(DetourTileDataError::Malformed("too few vertices"))?;
+        if off_mesh_base + connection_count != poly_count {
+            return Err(DetourTileDataError::Malformed(
+                "off-mesh connections are not stored after the polygons",
+            ));
+        }
+        let vertices = (0..vert_count)
+            .map(|_| data.vec3())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        struct Poly {
+            vertices: Vec<u16>,
+            neighbors: Vec<u16>,
+            flags: u16,
+            area: u8,
+        }
+        // The counts are not trusted for pre-allocation, as the data may be truncated.
+        let mut polys = Vec::new();
+        for _ in 0..poly_count {
+            let _first_link = data.u32()?;
+            let poly_vertices = data.u16s::<VERTS_PER_POLYGON>()?;
+            let neighbors = data.u16s::<VERTS_PER_POLYGON>()?;
+            let flags = data.u16()?;
+            let count = (data.u8()? as usize).min(VERTS_PER_POLYGON);
+            let area = data.u8()? & 0x3f;
+            polys.push(Poly {
+                vertices: poly_vertices[..count].to_vec(),
+                neighbors: neighbors[..count].to_vec(),
+                flags,
+                area,
+            });
+        }
+        data.skip(max_link_count * LINK_SIZE)?;
+
+        let mut detail_meshes = Vec::new();
+        for _ in 0..detail_mesh_count {
+            let vertex_base = data.count()?;
+            let triangle_base = data.u32()?;
+            let vertex_count = data.u8()? as usize;
+            let triangle_count = data.u8()? as u32;
+            data.skip(2)?;
+            detail_meshes.push((vertex_base, vertex_count, triangle_base, triangle_count));
+        }
+        let detail_vertices = (0..detail_vert_count)
+            .map(|_| data.vec3())
+            .collect::<Result<Vec<_>, _>>()?;
+        let detail_triangles = (0..detail_tri_count)
+            .map(|_| data.u8s::<4>())
+            .collect::<Result<Vec<_>, _>>()?;
+        data.skip(bv_node_count * BV_NODE_SIZE)?;
+
+        let mut connections = Vec::new();
+        for _ in 0..connection_count {
+            let start = data.vec3()?;
+            let end = data.vec3()?;
+            let radius = data.f32()?;
+            let poly = data.u16()? as usize;
+            let flags = data.u8()?;
+            let side = data.u8()?;
+            let user_id = data.u32()?;
+            let poly = polys.get(poly).ok_or(DetourTileDataError::Malformed(
+                "off-mesh connection polygon out of range",
+            ))?;
+            let connection = OffMeshConnection {
+                start,
+                end,
+                radius,
+                bidirectional: flags & OFFMESH_CON_BIDIR != 0,
+                area: area_from_detour(poly.area),
+                flags: poly.flags,
+                user_id,
+            };
+            connections.push((connection, side_from_detour(coord, side)));
+        }
+
+        // Polygon mesh
+        let nvp = config.max_vertices_per_polygon as usize;
+        let ground = &polys[..off_mesh_base];
+        let quantize =
+            |value: f32, cell: f32| floor(value / cell + 0.5).clamp(0.0, u16::MAX as f32) as u16;
+        let mut poly_mesh = PolygonNavmesh {
+            vertices: vertices[..ground_vert_count]
+                .iter()
+                .map(|&v| {
+                    let v = v - aabb_min;
+                    U16Vec3::new(
+                        quantize(v.x, config.cell_size),
+                        quantize(v.y, config.cell_height),
+                        quantize(v.z, config.cell_size),
+                    )
+                })
+                .collect(),
+            polygons: Vec::with_capacity(ground.len() * nvp),
+            polygon_neighbors: Vec::with_capacity(ground.len() * nvp),
+            flags: ground.iter().map(|poly| poly.flags).collect(),
+            regions: vec![Default::default(); ground.len()],
+            areas: ground
+                .iter()
+                .map(|poly| area_from_detour(poly.area))
+                .collect(),
+            max_vertices_per_polygon: config.max_vertices_per_polygon,
+            aabb: crate::Aabb3d {
+                min: aabb_min,
+                max: aabb_max,
+            },
+            cell_size: config.cell_size,
+            cell_height: config.cell_height,
+            border_size: 0,
+            max_edge_error: 0.0,
+        };
+        for poly in ground {
+            if poly.vertices.len() > nvp {
+                return Err(DetourTileDataError::TooManyVerticesPerPolygon(
+                    poly.vertices.len(),
+                ));
+            }
+            if poly.vertices.len() < 3 {
+                return Err(DetourTileDataError::Malformed(
+                    "polygon with fewer than 3 vertices",
+                ));
+            }
+            if poly
+                .vertices
+                .iter()
+                .any(|&v| v as usize >= ground_vert_count)
+            {
+                return Err(DetourTileDataError::Malformed(
+                    "polygon vertex out of range",
+                ));
+            }
+            if poly.neighbors.iter().any(|&neighbor| {
+                neighbor & EXT_LINK == 0
+                    && neighbor != 0
+                    && usize::from(neighbor - 1) >= ground.len()
+            }) {
+                return Err(DetourTileDataError::Malformed(
+                    "polygon neighbor out of range",
+                ));
+            }
+            let padding = nvp - poly.vertices.len();
+            poly_mesh.polygons.extend(&poly.vertices);
+            poly_mesh
+                .polygons
+                .extend(iter::repeat_n(PolygonNavmesh::NO_INDEX, padding));
+            poly_mesh.polygon_neighbors.extend(
+                poly.neighbors
+                    .iter()
+                    .map(|&neighbor| neighbor_from_detour(neighbor)),
+            );
+            poly_mesh
+                .polygon_neighbors
+                .extend(iter::repeat_n(PolygonNavmesh::NO_CONNECTION, padding));
+        }
+
+        // Detail mesh, with the polygon vertices in front of each sub-mesh's own vertices.
+        let mut detail_mesh = DetailNavmesh::default();
+        for (poly, &(vertex_base, vertex_count, triangle_base, triangle_count)) in
+            ground.iter().zip(&detail_meshes)
+        {
+            let own_vertices = detail_vertices
+                .get(vertex_base..vertex_base + vertex_count)
+                .ok_or(DetourTileDataError::Malformed("detail vertex out of range"))?;
+            detail_mesh.meshes.push(SubMesh {
+                base_vertex_index: detail_mesh.vertices.len() as u32,
+                vertex_count: (poly.vertices.len() + vertex_count) as u32,
+                base_triangle_index: triangle_base,
+                triangle_count,
+            });
+            detail_mesh
+                .vertices
+                .extend(poly.vertices.iter().map(|&v| vertices[v as usize]));
+            detail_mesh.vertices.extend_from_slice(own_vertices);
+        }
+        detail_mesh.triangles = detail_triangles
+            .iter()
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+        detail_mesh.triangle_flags = detail_triangles.iter().map(|t| t[3]).collect();
+        for submesh in &detail_mesh.meshes {
+            let triangles = detail_mesh
+                .triangles
+                .get(submesh.base_triangle_index as usize..)
+                .and_then(|triangles| triangles.get(..submesh.triangle_count as usize))
+                .ok_or(DetourTileDataError::Malformed(
+                    "detail triangle out of range",
+                ))?;
+            if triangles
+                .iter()
+                .flatten()
+                .any(|&vertex| u32::from(vertex) >= submesh.vertex_count)
+            {
+                return Err(DetourTileDataError::Malformed(
+                    "detail triangle vertex out of range",
+                ));
+            }
+        }
+
+        let query = NavmeshQuery::new(&poly_mesh).with_detail_mesh(&detail_mesh);
+        let off_mesh_connections = connections
+            .iter()
+            .filter_map(|(connection, end_tile)| match *end_tile {
+                Some(end_tile) => connection.snap_start(&query, walkable_climb, end_tile),
+                None => connection.snap(&query, walkable_climb),
+            })
+            .collect();
+
+        Ok(NavmeshTile {
+            coord,
+            poly_mesh,
+            detail_mesh,
+            off_mesh_connections,
+        })
+    }
+
+    /// Builds the bounding volume tree of the ground polygons, like `createBVTree` in `DetourNavMeshBuilder.cpp`.
+    fn build_bv_tree(&self, polygons: &[Vec<u16>], has_detail: bool) -> Vec<BvNode> {
+        let mesh = &self.poly_mesh;
+        let quant_factor = 1.0 / mesh.cell_size;
+        let quantize =
+            |value: f32, min: f32| ((value - min) * quant_factor).clamp(0.0, 65535.0) as u16;
+        let mut items: Vec<BvNode> = polygons
+            .iter()
+            .enumerate()
+            .map(|(polygon, vertices)| {
+                let (min, max) = if has_detail {
+                    // Use the detail mesh for the bounds, quantized with the cell size on all axes.
+                    let submesh = &self.detail_mesh.meshes[polygon];
+                    let start = submesh.base_vertex_index as usize;
+                    let detail_vertices =
+                        &self.detail_mesh.vertices[start..start + submesh.vertex_count as usize];
+                    let (min, max) = detail_vertices
+                        .iter()
+                        .fold((Vec3::MAX, Vec3::MIN), |(min, max), &v| {
+                            (min.min(v), max.max(v))
+                        });
+                    let (origin, min, max) = (mesh.aabb.min, min.to_array(), max.to_array());
+                    let origin = origin.to_array();
+                    (
+                        [0, 1, 2].map(|axis| quantize(min[axis], origin[axis])),
+                        [0, 1, 2].map(|axis| quantize(max[axis], origin[axis])),
+                    )
+                } else {
+                    let (min, max) =
+                        vertices
+                            .iter()
+                            .fold((U16Vec3::MAX, U16Vec3::MIN), |(min, max), &v| {
+                                let v = mesh.vertices[v as usize];
+                                (min.min(v), max.max(v))
+                            });
+                    // Remap the height to the cell size.
+                    let scale = mesh.cell_height / mesh.cell_size;
+                    (
+                        [min.x, floor(min.y as f32 * scale) as u16, min.z],
+                        [max.x, ceil(max.y as f32 * scale) as u16, max.z],
+                    )
+                };
+                BvNode {
+                    min,
+                    max,
+                    index: polygon as i32,
+                }
+            })
+            .collect();
+
+        let mut nodes = Vec::with_capacity(items.len() * 2);
+        subdivide(&mut items, &mut nodes);
+        // Detour reserves two nodes per polygon, leaving the last one unused.
+        nodes.resize(items.len() * 2, BvNode::default());
+        nodes
+    }
+}
+
+/// `dtBVNode`
+#[derive(Debug, Clone, Copy, Default)]
+struct BvNode {
+    min: [u16; 3],
+    max: [u16; 3],
+    /// The polygon index of a leaf, or the negated number of nodes to skip to escape the subtree.
+    index: i32,
+}
+
+fn subdivide(items: &mut [BvNode], nodes: &mut Vec<BvNode>) {
+    let current = nodes.len();
+    if let [item] = items {
+        nodes.push(*item);
+        return;
+    }
+    if items.is_empty() {
+        return;
+    }
+    let mut node = BvNode {
+        min: [u16::MAX; 3],
+        max: [0; 3],
+        index: 0,
+    };
+    for item in items.iter() {
+        for axis in 0..3 {
+            node.min[axis] = node.min[axis].min(item.min[axis]);
+            node.max[axis] = node.max[axis].max(item.max[axis]);
+        }
+    }
+    let extents = [0, 1, 2].map(|axis| node.max[axis] - node.min[axis]);
+    let mut axis = 0;
+    if extents[1] > extents[axis] {
+        axis = 1;
+    }
+    if extents[2] > extents[axis] {
+        axis = 2;
+    }
+    items.sort_by_key(|item| item.min[axis]);
+    nodes.push(node);
+
+    let (left, right) = items.split_at_mut(items.len() / 2);
+    subdivide(left, nodes);
+    subdivide(right, nodes);
+    // Negative index means escape.
+    nodes[current].index = -((nodes.len() - current) as i32);
+}
+
+fn neighbor_to_detour(neighbor: u16) -> u16 {
+    if neighbor & 0x8000 == 0 {
+        return neighbor + 1;
+    }
+    // Portals to neighboring tiles are stored with the side they lead to.
+    match neighbor & 0xf {
+        0 => EXT_LINK | 4,
+        1 => EXT_LINK | 2,
+        2 => EXT_LINK,
+        3 => EXT_LINK | 6,
+        _ => 0,
+    }
+}
+
+fn neighbor_from_detour(neighbor: u16) -> u16 {
+    if neighbor == 0 {
+        return PolygonNavmesh::NO_CONNECTION;
+    }
+    if neighbor & EXT_LINK == 0 {
+        return neighbor - 1;
+    }
+    match neighbor & 0xff {
+        4 => 0x8000,
+        2 => 0x8000 | 1,
+        0 => 0x8000 | 2,
+        6 => 0x8000 | 3,
+        _ => PolygonNavmesh::NO_CONNECTION,
+    }
+}
+
+/// Returns the side of the tile at `coord` on which an off-mesh connection leading into `end_tile` ends,
+/// or `0xff` if it ends in the same tile.
+fn side_to_detour(coord: TileCoord, end_tile: Option<TileCoord>) -> u8 {
+    let Some(end_tile) = end_tile else {
+        return 0xff;
+    };
+    let direction = (
+        (i32::from(end_tile.x) - i32::from(coord.x)).signum(),
+        (i32::from(end_tile.z) - i32::from(coord.z)).signum(),
+    );
+    SIDE_DIRECTIONS
+        .iter()
+        .position(|&side| side == direction)
+        .map_or(0xff, |side| side as u8)
+}
+
+/// Returns the tile next to the tile at `coord` on the given side, or `None` if the side is `0xff` or leads out of the grid.
+fn side_from_detour(coord: TileCoord, side: u8) -> Option<TileCoord> {
+    let (x, z) = SIDE_DIRECTIONS.get(usize::from(side))?;
+    Some(TileCoord {
+        x: coord.x.checked_add_signed(*x as i16)?,
+        z: coord.z.checked_add_signed(*z as i16)?,
+    })
+}
+
+fn area_to_detour(area: AreaType) -> Result<u8, DetourTileDataError> {
+    match area {
+        AreaType::DEFAULT_WALKABLE => Ok(WALKABLE_AREA),
+        AreaType(area) if area < WALKABLE_AREA => Ok(area),
+        area => Err(DetourTileDataError::UnsupportedArea(area)),
+    }
+}
+
+fn area_from_detour(area: u8) -> AreaType {
+    match area {
+        WALKABLE_AREA => AreaType::DEFAULT_WALKABLE,
+        area => AreaType(area),
+    }
+}
+
+/// Writes little-endian values.
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn vec3(&mut self, value: Vec3) {
+        value.to_array().iter().for_each(|&v| self.f32(v));
+    }
+
+    /// Writes `DT_VERTS_PER_POLYGON` values, padded with zeros.
+    fn padded_u16s(&mut self, values: impl Iterator<Item = u16>) {
+        let mut count = 0;
+        for value in values {
+            self.u16(value);
+            count += 1;
+        }
+        self.zeros((VERTS_PER_POLYGON - count) * 2);
+    }
+
+    fn zeros(&mut self, count: usize) {
+        self.0.resize(self.0.len() + count, 0);
+    }
+}
+
+/// Reads little-endian values.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn u8s<const N: usize>(&mut self) -> Result<[u8; N], DetourTileDataError> {
+        let (bytes, rest) = self
+            .0
+            .split_first_chunk::<N>()
+            .ok_or(DetourTileDataError::Truncated)?;
+        self.0 = rest;
+        Ok(*bytes)
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), DetourTileDataError> {
+        self.0 = self.0.get(count..).ok_or(DetourTileDataError::Truncated)?;
+        Ok(())
+    }
+
+    fn u8(&mut self) -> Result<u8, DetourTileDataError> {
+        Ok(self.u8s::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DetourTileDataError> {
+        self.u8s().map(u16::from_le_bytes)
+    }
+
+    fn u16s<const N: usize>(&mut self) -> Result<[u16; N], DetourTileDataError> {
+        let mut values = [0; N];
+        for value in &mut values {
+            *value = self.u16()?;
+        }
+        Ok(values)
+    }
+
+    fn u32(&mut self) -> Result<u32, DetourTileDataError> {
+        self.u8s().map(u32::from_le_bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, DetourTileDataError> {
+        self.u8s().map(i32::from_le_bytes)
+    }
+
+    /// Reads a count or offset, which must not be negative.
+    fn count(&mut self) -> Result<usize, DetourTileDataError> {
+        usize::try_from(self.i32()?).map_err(|_| DetourTileDataError::Malformed("negative count"))
+    }
+
+    fn f32(&mut self) -> Result<f32, DetourTileDataError> {
+        self.u8s().map(f32::from_le_bytes)
+    }
+
+    fn vec3(&mut self) -> Result<Vec3, DetourTileDataError> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+}
+
+/// Errors that can occur when converting a [`NavmeshTile`] to or from Detour tile data.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum DetourTileDataError {
+    /// The data does not start with `DT_NAVMESH_MAGIC`.
+    #[error("Not Detour tile data")]
+    InvalidMagic,
+    /// The data was written for a different `DT_NAVMESH_VERSION`.
+    #[error("Detour tile data version {0} is not supported, expected version {VERSION}")]
+    UnsupportedVersion(i32),
+    /// The data ends before all of its contents do.
+    #[error("Detour tile data is truncated")]
+    Truncated,
+    /// The data is inconsistent.
+    #[error("Detour tile data is malformed: {0}")]
+    Malformed(&'static str),
+    /// A polygon has more vertices than supported, either by Detour or by the configuration.
+    #[error("Polygons with {0} vertices are not supported")]
+    TooManyVerticesPerPolygon(usize),
+    /// The tile has more vertices than can be referenced by Detour polygons.
+    #[error("Detour tiles cannot have {0} vertices")]
+    TooManyVertices(usize),
+    /// The area type does not fit into the 6 bits Detour stores per polygon.
+    #[error("Area type {0:?} cannot be represented in Detour tile data")]
+    UnsupportedArea(AreaType),
+    /// The tile coordinate is negative or too large.
+    #[error("Tile coordinate ({x}, {z}) is not supported")]
+    InvalidTileCoord {
+        /// The x coordinate of the tile.
+        x: i32,
+        /// The z coordinate of the tile, called y in Detour.
+        z: i32,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Aabb3d, ConfigBuilder, TiledNavmeshConfig, TriMesh, test_meshes};
+
+    /// A tile with two platforms separated by a gap, linked by an off-mesh connection.
+    fn tile() -> (NavmeshTile, Config) {
+        let config = ConfigBuilder {
+            tiling: true,
+            tile_size: 64,
+            aabb: Aabb3d {
+                min: Vec3::new(0.0, -1.0, 0.0),
+                max: Vec3::new(10.0, 4.0, 10.0),
+            },
+            off_mesh_connections: vec![OffMeshConnection {
+                start: Vec3::new(3.5, 0.0, 5.0),
+                end: Vec3::new(6.5, 0.0, 5.0),
+                bidirectional: false,
+                user_id: 3,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+        .build();
+        let mut trimesh = TriMesh::default();
+        for (min_x, max_x) in [(0.0, 4.0), (6.0, 10.0)] {
+            trimesh.extend(test_meshes::flat_ground(Aabb3d {
+                min: Vec3::new(min_x, 0.0, 0.0),
+                max: Vec3::new(max_x, 0.0, 10.0),
+            }));
+        }
+        let tiles = TiledNavmeshConfig::new(config.clone())
+            .unwrap()
+            .generate_tiles(&trimesh)
+            .unwrap();
+        (tiles.into_iter().next().unwrap(), config)
+    }
+
+    #[test]
+    fn writes_detour_layout() {
+        let (tile, config) = tile();
+        let data = tile.to_detour_tile_data(&config).unwrap();
+        assert_eq!(&data[..4], b"VAND");
+        assert_eq!(data[4..8], VERSION.to_le_bytes());
+        let poly_count = tile.poly_mesh.polygon_count() + 1;
+        assert_eq!(data[24..28], (poly_count as i32).to_le_bytes());
+        // Two BV tree nodes per ground polygon, followed by the off-mesh connection count.
+        assert_eq!(data[48..52], (((poly_count - 1) * 2) as i32).to_le_bytes());
+        assert_eq!(data[52..56], 1_i32.to_le_bytes());
+        // One link per edge, two per portal and four per off-mesh connection.
+        let mesh = &tile.poly_mesh;
+        let edge_count: usize = mesh.polygons().map(Iterator::count).sum();
+        let portal_count = mesh
+            .polygon_neighbors
+            .iter()
+            .filter(|&&neighbor| neighbor & 0x8000 != 0 && neighbor & 0xf != 0xf)
+            .count();
+        let max_link_count = edge_count + portal_count * 2 + 4;
+        assert_eq!(data[32..36], (max_link_count as i32).to_le_bytes());
+
+        let mut newer = data.clone();
+        newer[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(
+            NavmeshTile::from_detour_tile_data(&newer, &config),
+            Err(DetourTileDataError::UnsupportedVersion(VERSION + 1))
+        );
+        assert_eq!(
+            NavmeshTile::from_detour_tile_data(&data[..data.len() - 1], &config),
+            Err(DetourTileDataError::Truncated)
+        );
+    }
+
+    #[test]
+    fn round_trips_through_detour_layout() {
+        let (tile, config) = tile();
+        assert_eq!(tile.off_mesh_connections.len(), 1);
+        let data = tile.to_detour_tile_data(&config).unwrap();
+        let decoded = NavmeshTile::from_detour_tile_data(&data, &config).unwrap();
+
+        assert_eq!(decoded.coord, tile.coord);
+        let (original, mesh) = (&tile.poly_mesh, &decoded.poly_mesh);
+        assert_eq!(mesh.vertices, original.vertices);
+        assert_eq!(mesh.polygons, original.polygons);
+        assert_eq!(mesh.polygon_neighbors, original.polygon_neighbors);
+        assert_eq!(mesh.areas, original.areas);
+        assert_eq!(mesh.aabb, original.aabb);
+        assert_eq!(decoded.detail_mesh.meshes, tile.detail_mesh.meshes);
+        assert_eq!(decoded.detail_mesh.triangles, tile.detail_mesh.triangles);
+        assert_eq!(
+            decoded.detail_mesh.triangle_flags,
+            tile.detail_mesh.triangle_flags
+        );
+        assert_eq!(decoded.off_mesh_connections, tile.off_mesh_connections);
+
+        // Connections into other tiles are stored with the side of the tile they leave through.
+        let mut tile = tile;
+        let connection = &mut tile.off_mesh_connections[0];
+        connection.end = connection.connection.end;
+        connection.end_polygon = PolygonNavmesh::NO_INDEX;
+        connection.end_tile = Some(TileCoord { x: 1, z: 0 });
+        let data = tile.to_detour_tile_data(&config).unwrap();
+        // The side is followed by the user id.
+        assert_eq!(data[data.len() - 5], 0);
+        let decoded = NavmeshTile::from_detour_tile_data(&data, &config).unwrap();
+        assert_eq!(decoded.off_mesh_connections, tile.off_mesh_connections);
+    }
+
+    #[test]
+    fn rejects_corrupt_detour_tile_data() {
+        let (tile, config) = tile();
+        let data = tile.to_detour_tile_data(&config).unwrap();
+        let count = |offset: usize| {
+            i32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+        };
+        let polygons = HEADER_SIZE + count(28) * 12;
+        let detail_triangles =
+            polygons + count(24) * 32 + count(32) * LINK_SIZE + count(36) * 12 + count(40) * 12;
+        let corrupt = |offset: usize, bytes: &[u8]| {
+            let mut data = data.clone();
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            NavmeshTile::from_detour_tile_data(&data, &config)
+        };
+
+        let ground_count = tile.poly_mesh.polygon_count() as u16;
+        assert_eq!(
+            corrupt(polygons + 16, &(ground_count + 1).to_le_bytes()),
+            Err(DetourTileDataError::Malformed(
+                "polygon neighbor out of range"
+            ))
+        );
+        assert_eq!(
+            corrupt(polygons + 30, &[2]),
+            Err(DetourTileDataError::Malformed(
+                "polygon with fewer than 3 vertices"
+            ))
+        );
+        assert_eq!(
+            corrupt(detail_triangles, &[u8::MAX]),
+            Err(DetourTileDataError::Malformed(
+                "detail triangle vertex out of range"
+            ))
+        );
+    }
+
+    #[test]
+    fn writes_fan_detail_meshes_without_detail_mesh() {
+        let (mut tile, config) = tile();
+        let mut mismatched_detail_mesh = core::mem::take(&mut tile.detail_mesh);
+        mismatched_detail_mesh.meshes.pop();
+        let data = tile.to_detour_tile_data(&config).unwrap();
+        let poly_count = tile.poly_mesh.polygon_count();
+        let triangle_count: usize = tile.poly_mesh.polygons().map(|v| v.count() - 2).sum();
+        assert_eq!(data[36..40], (poly_count as i32).to_le_bytes());
+        assert_eq!(data[40..44], 0_i32.to_le_bytes());
+        assert_eq!(data[44..48], (triangle_count as i32).to_le_bytes());
+
+        let decoded = NavmeshTile::from_detour_tile_data(&data, &config).unwrap();
+        assert_eq!(decoded.poly_mesh.polygons, tile.poly_mesh.polygons);
+        let detail = &decoded.detail_mesh;
+        assert_eq!(detail.meshes.len(), poly_count);
+        assert_eq!(detail.triangles.len(), triangle_count);
+        for (submesh, vertices) in detail.meshes.iter().zip(tile.poly_mesh.polygons()) {
+            let vertex_count = vertices.count() as u32;
+            assert_eq!(submesh.vertex_count, vertex_count);
+            assert_eq!(submesh.triangle_count, vertex_count - 2);
+        }
+        assert_eq!(detail.triangles[0], [0, 1, 2]);
+
+        tile.detail_mesh = mismatched_detail_mesh;
+        assert_eq!(
+            tile.to_detour_tile_data(&config),
+            Err(DetourTileDataError::Malformed(
+                "the detail mesh does not have one sub-mesh per polygon"
+            ))
+        );
+    }
+}
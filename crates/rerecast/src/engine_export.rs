@@ -0,0 +1,212 @@
+//! Writing of navmeshes in formats that game engines without Rust bindings can load, namely Godot and Unity.
+
+use alloc::{
+    string::{String, ToString as _},
+    vec::Vec,
+};
+use core::fmt::Write as _;
+use glam::Vec3;
+use thiserror::Error;
+
+use crate::{AreaType, PolygonNavmesh, TiledNavmesh};
+
+impl PolygonNavmesh {
+    /// Writes the polygons in world space as a Godot 4 `NavigationMesh` resource in the text (`.tres`) format.
+    ///
+    /// The winding of the polygons is reversed, as Godot expects the opposite order of Recast.
+    pub fn to_godot_tres(&self) -> String {
+        write_godot_tres(&EngineMesh::new([self]))
+    }
+
+    /// Writes the polygons in world space as JSON in the layout of Unity's `NavMeshTriangulation`,
+    /// which can be loaded with `JsonUtility.FromJson`.
+    ///
+    /// The document has the fields
+    /// - `vertices`: objects with `x`, `y` and `z` fields,
+    /// - `indices`: three vertex indices per triangle, where polygons are triangulated as fans,
+    /// - `areas`: the Unity area index of each triangle, where [`AreaType::DEFAULT_WALKABLE`] becomes Unity's built-in walkable area `0`
+    ///   and other area types are kept as they are.
+    ///
+    /// Unity supports 32 areas, so area types from `32` up, apart from [`AreaType::DEFAULT_WALKABLE`], cannot be exported.
+    pub fn to_unity_json(&self) -> Result<String, UnityExportError> {
+        write_unity_json(&EngineMesh::new([self]))
+    }
+}
+
+impl TiledNavmesh {
+    /// Writes the polygons of all tiles as a single Godot 4 `NavigationMesh` resource. See [`PolygonNavmesh::to_godot_tres`].
+    pub fn to_godot_tres(&self) -> String {
+        write_godot_tres(&EngineMesh::new(
+            self.tiles().map(|(_, tile)| &tile.poly_mesh),
+        ))
+    }
+
+    /// Writes the polygons of all tiles as a single Unity triangulation. See [`PolygonNavmesh::to_unity_json`].
+    pub fn to_unity_json(&self) -> Result<String, UnityExportError> {
+        write_unity_json(&EngineMesh::new(
+            self.tiles().map(|(_, tile)| &tile.poly_mesh),
+        ))
+    }
+}
+
+/// Errors that can occur when writing a navmesh in the layout of Unity's `NavMeshTriangulation`.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum UnityExportError {
+    /// The area type does not fit into Unity's 32 areas.
+    #[error("Area type {0:?} cannot be represented as a Unity area")]
+    UnsupportedArea(AreaType),
+}
+
+/// The number of areas Unity supports.
+const UNITY_AREA_COUNT: u8 = 32;
+
+/// The polygons of one or more polygon meshes with their vertices in world space.
+#[derive(Default)]
+struct EngineMesh {
+    vertices: Vec<Vec3>,
+    polygons: Vec<(Vec<u32>, AreaType)>,
+    cell_size: f32,
+    cell_height: f32,
+}
+
+impl EngineMesh {
+    fn new<'a>(poly_meshes: impl IntoIterator<Item = &'a PolygonNavmesh>) -> Self {
+        let mut mesh = Self::default();
+        for poly_mesh in poly_meshes {
+            let first = mesh.vertices.len() as u32;
+            mesh.vertices
+                .extend((0..poly_mesh.vertices.len()).map(|i| poly_mesh.world_vertex(i as u16)));
+            for (polygon, indices) in poly_mesh.polygons().enumerate() {
+                let area = poly_mesh.areas.get(polygon).copied().unwrap_or_default();
+                mesh.polygons
+                    .push((indices.map(|i| first + i as u32).collect(), area));
+            }
+            mesh.cell_size = poly_mesh.cell_size;
+            mesh.cell_height = poly_mesh.cell_height;
+        }
+        mesh
+    }
+}
+
+fn write_godot_tres(mesh: &EngineMesh) -> String {
+    let mut tres = String::from("[gd_resource type=\"NavigationMesh\" format=3]\n\n[resource]\n");
+    let vertices: Vec<String> = mesh
+        .vertices
+        .iter()
+        .map(|v| format!("{}, {}, {}", v.x, v.y, v.z))
+        .collect();
+    _ = writeln!(
+        tres,
+        "vertices = PackedVector3Array({})",
+        vertices.join(", ")
+    );
+    let polygons: Vec<String> = mesh
+        .polygons
+        .iter()
+        .map(|(indices, _)| {
+            let indices: Vec<String> = indices.iter().rev().map(u32::to_string).collect();
+            format!("PackedInt32Array({})", indices.join(", "))
+        })
+        .collect();
+    _ = writeln!(tres, "polygons = [{}]", polygons.join(", "));
+    _ = writeln!(tres, "cell_size = {}", mesh.cell_size);
+    _ = writeln!(tres, "cell_height = {}", mesh.cell_height);
+    tres
+}
+
+fn write_unity_json(mesh: &EngineMesh) -> Result<String, UnityExportError> {
+    let vertices: Vec<String> = mesh
+        .vertices
+        .iter()
+        .map(|v| format!(r#"{{"x":{},"y":{},"z":{}}}"#, v.x, v.y, v.z))
+        .collect();
+    let mut indices = Vec::new();
+    let mut areas = Vec::new();
+    for (polygon, area) in &mesh.polygons {
+        for j in 2..polygon.len() {
+            indices.extend([polygon[0], polygon[j - 1], polygon[j]].map(|i| i.to_string()));
+            areas.push(area_to_unity(*area)?.to_string());
+        }
+    }
+    Ok(format!(
+        r#"{{"vertices":[{}],"indices":[{}],"areas":[{}]}}"#,
+        vertices.join(","),
+        indices.join(","),
+        areas.join(",")
+    ))
+}
+
+fn area_to_unity(area: AreaType) -> Result<u8, UnityExportError> {
+    match area {
+        AreaType::DEFAULT_WALKABLE => Ok(0),
+        AreaType(area) if area < UNITY_AREA_COUNT => Ok(area),
+        area => Err(UnityExportError::UnsupportedArea(area)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Aabb3d;
+    use glam::U16Vec3;
+
+    /// A quad and a triangle sharing an edge.
+    fn poly_mesh() -> PolygonNavmesh {
+        let no_index = PolygonNavmesh::NO_INDEX;
+        PolygonNavmesh {
+            vertices: vec![
+                U16Vec3::new(0, 0, 0),
+                U16Vec3::new(0, 0, 2),
+                U16Vec3::new(2, 0, 2),
+                U16Vec3::new(2, 0, 0),
+                U16Vec3::new(4, 5, 0),
+            ],
+            polygons: vec![0, 1, 2, 3, 3, 2, 4, no_index],
+            areas: vec![AreaType::DEFAULT_WALKABLE, AreaType(3)],
+            max_vertices_per_polygon: 4,
+            aabb: Aabb3d {
+                min: Vec3::new(10.0, 0.0, 0.0),
+                max: Vec3::new(12.0, 1.0, 2.0),
+            },
+            cell_size: 0.5,
+            cell_height: 0.2,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn writes_godot_navigation_mesh() {
+        let tres = poly_mesh().to_godot_tres();
+        assert_eq!(
+            tres,
+            "[gd_resource type=\"NavigationMesh\" format=3]\n\n[resource]\n\
+             vertices = PackedVector3Array(10, 0, 0, 10, 0, 1, 11, 0, 1, 11, 0, 0, 12, 1, 0)\n\
+             polygons = [PackedInt32Array(3, 2, 1, 0), PackedInt32Array(4, 2, 3)]\n\
+             cell_size = 0.5\n\
+             cell_height = 0.2\n"
+        );
+    }
+
+    #[test]
+    fn writes_unity_triangulation() {
+        let json: serde_json::Value =
+            serde_json::from_str(&poly_mesh().to_unity_json().unwrap()).unwrap();
+        assert_eq!(json["vertices"].as_array().unwrap().len(), 5);
+        assert_eq!(
+            json["vertices"][4],
+            serde_json::json!({"x": 12, "y": 1, "z": 0})
+        );
+        assert_eq!(
+            json["indices"],
+            serde_json::json!([0, 1, 2, 0, 2, 3, 3, 2, 4])
+        );
+        assert_eq!(json["areas"], serde_json::json!([0, 0, 3]));
+
+        let mut mesh = poly_mesh();
+        mesh.areas[1] = AreaType(32);
+        assert_eq!(
+            mesh.to_unity_json(),
+            Err(UnityExportError::UnsupportedArea(AreaType(32)))
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! Export of navmeshes as binary glTF (`.glb`) files, for inspecting them in web viewers and engine editors.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write as _;
+use glam::Vec3;
+
+use crate::{NavmeshTile, TiledNavmesh};
+
+impl TiledNavmesh {
+    /// Writes all tiles as a binary glTF file. See [`NavmeshTile::to_glb`] for the layout of each tile.
+    pub fn to_glb(&self) -> Vec<u8> {
+        write_glb(self.tiles().map(|(_, tile)| tile))
+    }
+}
+
+impl NavmeshTile {
+    /// Writes the tile as a binary glTF file.
+    ///
+    /// The tile becomes a node named `tile <x> <z>`, whose mesh has two primitives in world space:
+    /// - The triangles of the [`DetailNavmesh`](crate::DetailNavmesh), with the area type of their polygon
+    ///   stored as a color in `COLOR_0` and as its raw value in the custom `_AREA` attribute.
+    /// - The outlines of the polygons of the [`PolygonNavmesh`](crate::PolygonNavmesh) as lines.
+    pub fn to_glb(&self) -> Vec<u8> {
+        write_glb([self])
+    }
+}
+
+fn write_glb<'a>(tiles: impl IntoIterator<Item = &'a NavmeshTile>) -> Vec<u8> {
+    let mut gltf = GltfBuilder::default();
+    for tile in tiles {
+        let mut primitives = Vec::new();
+        if let Some(primitive) = gltf.detail_triangles(tile) {
+            primitives.push(primitive);
+        }
+        if let Some(primitive) = gltf.polygon_outlines(tile) {
+            primitives.push(primitive);
+        }
+        let mut node = format!(
+            r#"{{"name":"tile {x} {z}","extras":{{"tile":[{x},{z}]}}"#,
+            x = tile.coord.x,
+            z = tile.coord.z
+        );
+        if !primitives.is_empty() {
+            _ = write!(node, r#","mesh":{}"#, gltf.meshes.len());
+            gltf.meshes
+                .push(format!(r#"{{"primitives":[{}]}}"#, primitives.join(",")));
+        }
+        node.push('}');
+        gltf.nodes.push(node);
+    }
+    gltf.finish()
+}
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_BYTE: u32 = 5121;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const MODE_LINES: u32 = 1;
+const MODE_TRIANGLES: u32 = 4;
+const MATERIAL_AREAS: usize = 0;
+const MATERIAL_OUTLINES: usize = 1;
+
+/// Collects the JSON objects and binary data of a glTF asset.
+#[derive(Default)]
+struct GltfBuilder {
+    buffer: Vec<u8>,
+    buffer_views: Vec<String>,
+    accessors: Vec<String>,
+    meshes: Vec<String>,
+    nodes: Vec<String>,
+}
+
+impl GltfBuilder {
+    fn detail_triangles(&mut self, tile: &NavmeshTile) -> Option<String> {
+        let detail_mesh = &tile.detail_mesh;
+        let mut colors = Vec::with_capacity(detail_mesh.vertices.len() * 4);
+        let mut areas = Vec::with_capacity(detail_mesh.vertices.len());
+        let mut indices = Vec::with_capacity(detail_mesh.triangles.len() * 3);
+        for (polygon, submesh) in detail_mesh.meshes.iter().enumerate() {
+            let area = tile
+                .poly_mesh
+                .areas
+                .get(polygon)
+                .copied()
+                .unwrap_or_default();
+            for _ in 0..submesh.vertex_count {
+                colors.extend(area.debug_color());
+                areas.push(area.0 as f32);
+            }
+            let triangles = &detail_mesh.triangles[submesh.base_triangle_index as usize..]
+                [..submesh.triangle_count as usize];
+            for triangle in triangles {
+                indices.extend(triangle.map(|i| submesh.base_vertex_index + i as u32));
+            }
+        }
+        if indices.is_empty() || colors.len() != detail_mesh.vertices.len() * 4 {
+            return None;
+        }
+
+        let position = self.positions(&detail_mesh.vertices);
+        let view = self.buffer_view(&colors, ARRAY_BUFFER);
+        let color = self.accessor(
+            view,
+            UNSIGNED_BYTE,
+            r#""normalized":true,"#,
+            areas.len(),
+            "VEC4",
+        );
+        let view = self.buffer_view(&floats(&areas), ARRAY_BUFFER);
+        let area = self.accessor(view, FLOAT, "", areas.len(), "SCALAR");
+        let indices = self.indices(&indices);
+        Some(format!(
+            r#"{{"attributes":{{"POSITION":{position},"COLOR_0":{color},"_AREA":{area}}},"indices":{indices},"material":{MATERIAL_AREAS},"mode":{MODE_TRIANGLES}}}"#
+        ))
+    }
+
+    fn polygon_outlines(&mut self, tile: &NavmeshTile) -> Option<String> {
+        let poly_mesh = &tile.poly_mesh;
+        let mut indices = Vec::new();
+        for polygon in poly_mesh.polygons() {
+            let polygon: Vec<u32> = polygon.map(u32::from).collect();
+            for (i, a) in polygon.iter().enumerate() {
+                indices.extend([*a, polygon[(i + 1) % polygon.len()]]);
+            }
+        }
+        if indices.is_empty() {
+            return None;
+        }
+        let vertices: Vec<Vec3> = (0..poly_mesh.vertices.len())
+            .map(|i| poly_mesh.world_vertex(i as u16))
+            .collect();
+        let position = self.positions(&vertices);
+        let indices = self.indices(&indices);
+        Some(format!(
+            r#"{{"attributes":{{"POSITION":{position}}},"indices":{indices},"material":{MATERIAL_OUTLINES},"mode":{MODE_LINES}}}"#
+        ))
+    }
+
+    fn positions(&mut self, vertices: &[Vec3]) -> usize {
+        let min = vertices.iter().copied().fold(Vec3::INFINITY, Vec3::min);
+        let max = vertices.iter().copied().fold(Vec3::NEG_INFINITY, Vec3::max);
+        let data: Vec<f32> = vertices.iter().flat_map(|v| v.to_array()).collect();
+        let view = self.buffer_view(&floats(&data), ARRAY_BUFFER);
+        // glTF requires the bounds of positions.
+        let bounds = format!(
+            r#""min":[{},{},{}],"max":[{},{},{}],"#,
+            min.x, min.y, min.z, max.x, max.y, max.z
+        );
+        self.accessor(view, FLOAT, &bounds, vertices.len(), "VEC3")
+    }
+
+    fn indices(&mut self, indices: &[u32]) -> usize {
+        let data: Vec<u8> = indices.iter().flat_map(|i| i.to_le_bytes()).collect();
+        let view = self.buffer_view(&data, ELEMENT_ARRAY_BUFFER);
+        self.accessor(view, UNSIGNED_INT, "", indices.len(), "SCALAR")
+    }
+
+    /// Appends `data` to the buffer, aligned to 4 bytes as glTF requires for vertex attributes.
+    fn buffer_view(&mut self, data: &[u8], target: u32) -> usize {
+        let offset = self.buffer.len();
+        self.buffer.extend_from_slice(data);
+        self.buffer.resize(self.buffer.len().next_multiple_of(4), 0);
+        self.buffer_views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{offset},"byteLength":{},"target":{target}}}"#,
+            data.len()
+        ));
+        self.buffer_views.len() - 1
+    }
+
+    /// Adds an accessor. `extra` holds further JSON members, each followed by a comma.
+    fn accessor(
+        &mut self,
+        view: usize,
+        component_type: u32,
+        extra: &str,
+        count: usize,
+        kind: &str,
+    ) -> usize {
+        self.accessors.push(format!(
+            r#"{{"bufferView":{view},"componentType":{component_type},{extra}"count":{count},"type":"{kind}"}}"#
+        ));
+        self.accessors.len() - 1
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let scene_nodes: Vec<String> = (0..self.nodes.len()).map(|i| i.to_string()).collect();
+        let mut json = String::from(concat!(
+            r#"{"asset":{"version":"2.0","generator":"rerecast"},"scene":0,"#,
+            r#""materials":[{"name":"areas","pbrMetallicRoughness":{"baseColorFactor":[1,1,1,1],"metallicFactor":0},"doubleSided":true},"#,
+            r#"{"name":"outlines","pbrMetallicRoughness":{"baseColorFactor":[0,0,0,1],"metallicFactor":0}}]"#
+        ));
+        if scene_nodes.is_empty() {
+            json.push_str(r#","scenes":[{}]"#);
+        } else {
+            _ = write!(
+                json,
+                r#","scenes":[{{"nodes":[{}]}}]"#,
+                scene_nodes.join(",")
+            );
+        }
+        // glTF does not allow empty arrays at the top level.
+        for (name, objects) in [
+            ("nodes", &self.nodes),
+            ("meshes", &self.meshes),
+            ("accessors", &self.accessors),
+            ("bufferViews", &self.buffer_views),
+        ] {
+            if !objects.is_empty() {
+                _ = write!(json, r#","{name}":[{}]"#, objects.join(","));
+            }
+        }
+        if !self.buffer.is_empty() {
+            _ = write!(
+                json,
+                r#","buffers":[{{"byteLength":{}}}]"#,
+                self.buffer.len()
+            );
+        }
+        json.push('}');
+        // Chunks are padded to 4 bytes, the JSON chunk with spaces.
+        json.extend(core::iter::repeat_n(
+            ' ',
+            json.len().next_multiple_of(4) - json.len(),
+        ));
+
+        let mut glb = Vec::with_capacity(28 + json.len() + self.buffer.len());
+        let bin_chunk_len = if self.buffer.is_empty() {
+            0
+        } else {
+            8 + self.buffer.len()
+        };
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2_u32.to_le_bytes());
+        glb.extend_from_slice(&((12 + 8 + json.len() + bin_chunk_len) as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(json.as_bytes());
+        if !self.buffer.is_empty() {
+            glb.extend_from_slice(&(self.buffer.len() as u32).to_le_bytes());
+            glb.extend_from_slice(b"BIN\0");
+            glb.extend_from_slice(&self.buffer);
+        }
+        glb
+    }
+}
+
+fn floats(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AreaType, DetailNavmesh, PolygonNavmesh, SubMesh, TileCoord};
+    use glam::U16Vec3;
+
+    fn tile(x: u16) -> NavmeshTile {
+        NavmeshTile {
+            coord: TileCoord { x, z: 0 },
+            poly_mesh: PolygonNavmesh {
+                vertices: vec![
+                    U16Vec3::new(0, 0, 0),
+                    U16Vec3::new(0, 0, 1),
+                    U16Vec3::new(1, 0, 1),
+                    U16Vec3::new(1, 0, 0),
+                ],
+                polygons: vec![0, 1, 2, 3],
+                areas: vec![AreaType(3)],
+                max_vertices_per_polygon: 4,
+                cell_size: 1.0,
+                cell_height: 1.0,
+                ..Default::default()
+            },
+            detail_mesh: DetailNavmesh {
+                meshes: vec![SubMesh {
+                    base_vertex_index: 0,
+                    vertex_count: 4,
+                    base_triangle_index: 0,
+                    triangle_count: 2,
+                }],
+                vertices: vec![
+                    Vec3::new(0.0, 0.0, 0.0),
+                    Vec3::new(0.0, 0.0, 1.0),
+                    Vec3::new(1.0, 0.0, 1.0),
+                    Vec3::new(1.0, 0.0, 0.0),
+                ],
+                triangles: vec![[0, 1, 2], [0, 2, 3]],
+                triangle_flags: vec![0, 0],
+            },
+            off_mesh_connections: Vec::new(),
+        }
+    }
+
+    /// Splits a `.glb` file into its JSON and binary chunk.
+    fn chunks(glb: &[u8]) -> (serde_json::Value, &[u8]) {
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(glb[offset..offset + 4].try_into().unwrap());
+        assert_eq!(&glb[..4], b"glTF");
+        assert_eq!(u32_at(4), 2);
+        assert_eq!(u32_at(8) as usize, glb.len());
+        let json_len = u32_at(12) as usize;
+        assert_eq!(&glb[16..20], b"JSON");
+        let json = serde_json::from_slice(&glb[20..20 + json_len]).unwrap();
+        let bin = &glb[20 + json_len..];
+        if bin.is_empty() {
+            return (json, bin);
+        }
+        assert_eq!(&bin[4..8], b"BIN\0");
+        (json, &bin[8..])
+    }
+
+    #[test]
+    fn writes_each_tile_as_a_node() {
+        let navmesh = TiledNavmesh::from_tiles([tile(0), tile(1)]);
+        let glb = navmesh.to_glb();
+        assert_eq!(glb.len() % 4, 0);
+        let (json, bin) = chunks(&glb);
+
+        let nodes = json["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0]["name"], "tile 0 0");
+        assert_eq!(nodes[1]["name"], "tile 1 0");
+        assert_eq!(json["buffers"][0]["byteLength"], bin.len());
+
+        let primitives = json["meshes"][0]["primitives"].as_array().unwrap();
+        assert_eq!(primitives[0]["mode"], MODE_TRIANGLES);
+        assert_eq!(primitives[1]["mode"], MODE_LINES);
+        let outline_indices =
+            &json["accessors"][primitives[1]["indices"].as_u64().unwrap() as usize];
+        assert_eq!(outline_indices["count"], 8);
+    }
+
+    #[test]
+    fn stores_area_types_per_vertex() {
+        let glb = tile(0).to_glb();
+        let (json, bin) = chunks(&glb);
+        let attributes = &json["meshes"][0]["primitives"][0]["attributes"];
+
+        let read = |accessor: &serde_json::Value| {
+            let accessor = &json["accessors"][accessor.as_u64().unwrap() as usize];
+            let view = &json["bufferViews"][accessor["bufferView"].as_u64().unwrap() as usize];
+            let offset = view["byteOffset"].as_u64().unwrap() as usize;
+            let len = view["byteLength"].as_u64().unwrap() as usize;
+            &bin[offset..offset + len]
+        };
+        assert_eq!(
+            read(&attributes["COLOR_0"]),
+            AreaType(3).debug_color().repeat(4)
+        );
+        assert_eq!(read(&attributes["_AREA"]), floats(&[3.0; 4]));
+        assert_eq!(
+            json["accessors"][attributes["POSITION"].as_u64().unwrap() as usize]["max"],
+            serde_json::json!([1, 0, 1])
+        );
+    }
+
+    #[test]
+    fn omits_empty_arrays() {
+        let mut empty_tile = tile(0);
+        empty_tile.poly_mesh.polygons.clear();
+        empty_tile.detail_mesh = DetailNavmesh::default();
+        let glb = empty_tile.to_glb();
+        let (json, bin) = chunks(&glb);
+        assert!(bin.is_empty());
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 1);
+        for name in ["meshes", "accessors", "bufferViews", "buffers"] {
+            assert!(json.get(name).is_none(), "{name}");
+        }
+
+        let (json, _) = chunks(&TiledNavmesh::default().to_glb());
+        assert!(json.get("nodes").is_none());
+        assert_eq!(json["scenes"], serde_json::json!([{}]));
+    }
+}
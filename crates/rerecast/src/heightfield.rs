@@ -54,7 +54,11 @@ use crate::{
 /// }
 /// ```
 #[derive(Debug, Clone)]
-#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "HeightfieldData")
+)]
 pub struct Heightfield {
     /// The width of the heightfield along the x-axis in cell units
     pub width: u16,
@@ -95,9 +99,41 @@ impl Default for Heightfield {
     }
 }
 
+/// The serialized fields of a [`Heightfield`], from which the cached fields are recomputed on deserialization.
+#[cfg(feature = "serialize")]
+#[derive(serde::Deserialize)]
+struct HeightfieldData {
+    width: u16,
+    height: u16,
+    aabb: Aabb3d,
+    cell_size: f32,
+    cell_height: f32,
+    spans: Vec<Option<SpanKey>>,
+    allocated_spans: Spans,
+}
+
+#[cfg(feature = "serialize")]
+impl From<HeightfieldData> for Heightfield {
+    fn from(data: HeightfieldData) -> Self {
+        let mut heightfield = Self {
+            width: data.width,
+            height: data.height,
+            aabb: data.aabb,
+            cell_size: data.cell_size,
+            cell_height: data.cell_height,
+            inverse_cell_size: 1.0,
+            inverse_cell_height: 1.0,
+            spans: data.spans,
+            allocated_spans: data.allocated_spans,
+        };
+        heightfield.ensure_cache_initialized();
+        heightfield
+    }
+}
+
 impl Heightfield {
     /// Ensures the cached inverse values are initialized correctly.
-    /// Call this after deserialization to recompute cached fields.
+    /// Call this after changing [`Self::cell_size`] or [`Self::cell_height`]. Deserialization already does this.
     #[inline]
     pub fn ensure_cache_initialized(&mut self) {
         self.inverse_cell_size = 1.0 / self.cell_size;
@@ -0,0 +1,386 @@
+//! Automatic placement of off-mesh connections where agents can drop down ledges or jump across gaps.
+
+use alloc::vec::Vec;
+use glam::{Vec3, Vec3Swizzles as _};
+
+use crate::{
+    AreaType, Heightfield, OffMeshConnection, PolygonNavmesh,
+    ops::{abs, floor},
+};
+
+/// The movement capabilities of an agent, used by [`PolygonNavmesh::generate_jump_links`] to place off-mesh connections.
+///
+/// All values are in world units.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct JumpLinkConfig {
+    /// The radius of the agent. The navmesh border is eroded by this radius, so links search for ledges this far beyond
+    /// the border and land this far beyond them. `[Limit: > 0] [Units: wu]`
+    pub agent_radius: f32,
+    /// The height of the agent, which must fit above the whole trajectory of a link. `[Limit: > 0] [Units: wu]`
+    pub agent_height: f32,
+    /// The height difference the agent can walk up or down without a link. `[Limit: >= 0] [Units: wu]`
+    pub walkable_climb: f32,
+    /// The maximum height the agent can drop down a ledge. Zero disables drop-down links. `[Limit: >= 0] [Units: wu]`
+    pub max_drop_height: f32,
+    /// The maximum horizontal distance the agent can jump across a gap, measured from ledge to landing.
+    /// Zero disables jump links. `[Limit: >= 0] [Units: wu]`
+    pub max_jump_distance: f32,
+    /// The maximum height difference between take-off and landing of a jump across a gap. `[Limit: >= 0] [Units: wu]`
+    pub max_jump_height: f32,
+    /// The distance between neighboring links along the same navmesh border. `[Limit: > 0] [Units: wu]`
+    pub spacing: f32,
+    /// The area type of the generated connections.
+    pub area: AreaType,
+    /// The flags of the generated connections.
+    pub flags: u16,
+    /// The [`OffMeshConnection::user_id`] of generated drop-down links.
+    pub drop_down_user_id: u32,
+    /// The [`OffMeshConnection::user_id`] of generated jump links.
+    pub jump_user_id: u32,
+}
+
+impl Default for JumpLinkConfig {
+    fn default() -> Self {
+        Self {
+            agent_radius: 0.6,
+            agent_height: 2.0,
+            walkable_climb: 0.9,
+            max_drop_height: 4.0,
+            max_jump_distance: 3.0,
+            max_jump_height: 0.5,
+            spacing: 2.0,
+            area: AreaType::DEFAULT_WALKABLE,
+            flags: 1,
+            drop_down_user_id: 1,
+            jump_user_id: 2,
+        }
+    }
+}
+
+impl PolygonNavmesh {
+    /// Generates one-way off-mesh connections along the solid borders of the mesh where an agent can drop down a ledge
+    /// or jump across a gap, according to its capabilities in `config`.
+    ///
+    /// The borders are probed against `heightfield`, which must be the heightfield the mesh was built from,
+    /// taken before [`Heightfield::into_compact`]. Unlike the [`CompactHeightfield`](crate::CompactHeightfield), it still contains
+    /// the solid geometry that is not walkable, which must not obstruct the trajectory of a link.
+    ///
+    /// The returned connections are not snapped yet. Pass them to [`Config::off_mesh_connections`](crate::Config::off_mesh_connections),
+    /// or set [`Config::jump_links`](crate::Config::jump_links) to generate them during tile generation.
+    pub fn generate_jump_links(
+        &self,
+        heightfield: &Heightfield,
+        config: &JumpLinkConfig,
+    ) -> Vec<OffMeshConnection> {
+        let nvp = self.max_vertices_per_polygon as usize;
+        let probe = Probe {
+            heightfield,
+            config,
+        };
+        let mut links = Vec::new();
+        for (polygon, indices) in self.polygons().enumerate() {
+            let vertices: Vec<_> = indices.map(|i| self.world_vertex(i)).collect();
+            let centroid = vertices.iter().sum::<Vec3>() / vertices.len() as f32;
+            for (edge, &a) in vertices.iter().enumerate() {
+                // Only solid borders can lead off the navmesh, portals to other tiles lead to more navmesh.
+                if self.polygon_neighbors[polygon * nvp + edge] != Self::NO_CONNECTION {
+                    continue;
+                }
+                let b = vertices[(edge + 1) % vertices.len()];
+                let length = a.xz().distance(b.xz());
+                if length < heightfield.cell_size {
+                    continue;
+                }
+                // The direction pointing away from the polygon.
+                let direction = (b - a).with_y(0.0) / length;
+                let mut normal = Vec3::new(-direction.z, 0.0, direction.x);
+                if normal.dot(centroid - a) > 0.0 {
+                    normal = -normal;
+                }
+
+                let count =
+                    (floor(length / config.spacing.max(heightfield.cell_size)) as usize).max(1);
+                for i in 0..count {
+                    let start = a.lerp(b, (i as f32 + 0.5) / count as f32);
+                    links.extend(probe.drop_down(start, normal));
+                    links.extend(probe.jump(start, normal));
+                }
+            }
+        }
+        links
+    }
+}
+
+/// Answers questions about the solid geometry of a heightfield in world space.
+struct Probe<'a> {
+    heightfield: &'a Heightfield,
+    config: &'a JumpLinkConfig,
+}
+
+impl Probe<'_> {
+    /// Finds a drop-down link from `start` on the navmesh border over the ledge in `normal` direction.
+    fn drop_down(&self, start: Vec3, normal: Vec3) -> Option<OffMeshConnection> {
+        let config = self.config;
+        if config.max_drop_height <= 0.0 {
+            return None;
+        }
+        let (ledge, _) = self.find_ledge(start, normal)?;
+        // The highest floor below the ledge that cannot be reached by walking.
+        let landing_height = self
+            .floors(ledge)?
+            .into_iter()
+            .filter(|floor| {
+                *floor < start.y - config.walkable_climb
+                    && *floor >= start.y - config.max_drop_height
+            })
+            .reduce(f32::max)?;
+        // The agent must fit through the column it falls down.
+        if !self.is_free(
+            ledge,
+            landing_height + config.walkable_climb,
+            start.y + config.agent_height,
+        ) {
+            return None;
+        }
+        Some(self.link(
+            start,
+            (ledge + normal * config.agent_radius).with_y(landing_height),
+            config.drop_down_user_id,
+        ))
+    }
+
+    /// Finds a jump link from `start` on the navmesh border across the gap in `normal` direction.
+    fn jump(&self, start: Vec3, normal: Vec3) -> Option<OffMeshConnection> {
+        let config = self.config;
+        if config.max_jump_distance <= 0.0 {
+            return None;
+        }
+        let step = self.heightfield.cell_size;
+        let (ledge, ledge_distance) = self.find_ledge(start, normal)?;
+        // A floor that is close enough to jump to right behind the ledge is a step, not a gap.
+        if self.jumpable_floor(ledge, start.y)?.is_some() {
+            return None;
+        }
+
+        let mut distance = ledge_distance + step;
+        let (landing, landing_distance, landing_height) = loop {
+            if distance - ledge_distance > config.max_jump_distance {
+                return None;
+            }
+            let position = start + normal * distance;
+            if let Some(landing_height) = self.jumpable_floor(position, start.y)? {
+                break (position, distance, landing_height);
+            }
+            distance += step;
+        };
+
+        // The agent must fit above the trajectory, which is approximated by a straight line.
+        let steps = floor(landing_distance / step) as usize;
+        for i in 1..=steps {
+            let t = i as f32 / steps as f32;
+            let height = start.y + (landing_height - start.y) * t;
+            if !self.is_free(
+                start + normal * (landing_distance * t),
+                height + config.walkable_climb,
+                height + config.agent_height,
+            ) {
+                return None;
+            }
+        }
+        Some(self.link(
+            start,
+            (landing + normal * config.agent_radius).with_y(landing_height),
+            config.jump_user_id,
+        ))
+    }
+
+    /// Walks from `start` in `normal` direction until the ground at the height of `start` ends.
+    ///
+    /// Returns the first position without such ground and its distance from `start`, or `None` if the ground does not end
+    /// within the strip the navmesh border was eroded by.
+    fn find_ledge(&self, start: Vec3, normal: Vec3) -> Option<(Vec3, f32)> {
+        let step = self.heightfield.cell_size;
+        let max_distance = self.config.agent_radius * 2.0 + step;
+        let mut distance = step;
+        while distance <= max_distance {
+            let position = start + normal * distance;
+            let is_ground = self
+                .floors(position)?
+                .into_iter()
+                .any(|floor| abs(floor - start.y) <= self.config.walkable_climb);
+            if !is_ground {
+                return Some((position, distance));
+            }
+            distance += step;
+        }
+        None
+    }
+
+    /// Returns the floor at `position` closest to `height` that can be jumped to from `height`.
+    ///
+    /// Returns `None` if `position` is outside of the heightfield, and `Some(None)` if there is no such floor.
+    fn jumpable_floor(&self, position: Vec3, height: f32) -> Option<Option<f32>> {
+        let floors = self.floors(position)?;
+        Some(
+            floors
+                .into_iter()
+                .filter(|floor| abs(floor - height) <= self.config.max_jump_height)
+                .min_by(|a, b| abs(a - height).total_cmp(&abs(b - height))),
+        )
+    }
+
+    fn link(&self, start: Vec3, end: Vec3, user_id: u32) -> OffMeshConnection {
+        OffMeshConnection {
+            start,
+            end,
+            radius: self.config.agent_radius,
+            bidirectional: false,
+            area: self.config.area,
+            flags: self.config.flags,
+            user_id,
+        }
+    }
+
+    /// Returns the heights of the surfaces in the column at `position` with enough space above them for the agent,
+    /// or `None` if `position` is outside of the heightfield.
+    fn floors(&self, position: Vec3) -> Option<Vec<f32>> {
+        let spans = self.solid_spans(position)?;
+        let floors = spans
+            .iter()
+            .enumerate()
+            .filter(|(i, (_, top))| {
+                let ceiling = spans.get(i + 1).map_or(f32::MAX, |(bottom, _)| *bottom);
+                ceiling - top >= self.config.agent_height
+            })
+            .map(|(_, (_, top))| *top)
+            .collect();
+        Some(floors)
+    }
+
+    /// Returns whether the column at `position` contains no solid geometry between `bottom` and `top`.
+    fn is_free(&self, position: Vec3, bottom: f32, top: f32) -> bool {
+        self.solid_spans(position).is_some_and(|spans| {
+            spans
+                .iter()
+                .all(|(span_bottom, span_top)| *span_top <= bottom || *span_bottom >= top)
+        })
+    }
+
+    /// Returns the world-space bottom and top heights of the solid spans in the column at `position`, from bottom to top.
+    fn solid_spans(&self, position: Vec3) -> Option<Vec<(f32, f32)>> {
+        let heightfield = self.heightfield;
+        let x = floor((position.x - heightfield.aabb.min.x) / heightfield.cell_size);
+        let z = floor((position.z - heightfield.aabb.min.z) / heightfield.cell_size);
+        if x < 0.0 || z < 0.0 || x >= heightfield.width as f32 || z >= heightfield.height as f32 {
+            return None;
+        }
+        let mut spans = Vec::new();
+        let mut key = heightfield.span_key_at(x as u16, z as u16);
+        while let Some(span_key) = key {
+            let span = heightfield.span(span_key);
+            spans.push((
+                heightfield.aabb.min.y + span.min as f32 * heightfield.cell_height,
+                heightfield.aabb.min.y + span.max as f32 * heightfield.cell_height,
+            ));
+            key = span.next;
+        }
+        Some(spans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Aabb3d, HeightfieldBuilder, NavmeshQuery, TriMesh, test_meshes};
+    use glam::U16Vec3;
+
+    /// Rasterizes each platform `(min_x, max_x, height)` as a quad spanning z in `[0, 10]`
+    /// and builds a navmesh with one polygon per platform, eroded by 1 along x.
+    fn scene(platforms: &[(u16, u16, u16)]) -> (PolygonNavmesh, Heightfield) {
+        let aabb = Aabb3d {
+            min: Vec3::new(0.0, -1.0, 0.0),
+            max: Vec3::new(20.0, 6.0, 10.0),
+        };
+        let mut trimesh = TriMesh::default();
+        for &(x0, x1, y) in platforms {
+            trimesh.extend(test_meshes::flat_ground(Aabb3d {
+                min: Vec3::new(x0.into(), y.into(), 0.0),
+                max: Vec3::new(x1.into(), y.into(), 10.0),
+            }));
+        }
+        let mut heightfield = HeightfieldBuilder {
+            aabb,
+            cell_size: 0.25,
+            cell_height: 0.1,
+        }
+        .build()
+        .unwrap();
+        heightfield.populate_from_trimesh(trimesh, 20, 9).unwrap();
+
+        let mut vertices = Vec::new();
+        let mut polygons = Vec::new();
+        for &(x0, x1, y) in platforms {
+            let first = vertices.len() as u16;
+            vertices.extend(
+                [(x0 + 1, 10), (x1 - 1, 10), (x1 - 1, 0), (x0 + 1, 0)]
+                    .map(|(x, z)| U16Vec3::new(x, y + 1, z)),
+            );
+            polygons.extend((first..first + 4).collect::<Vec<_>>());
+        }
+        let n = platforms.len();
+        let mesh = PolygonNavmesh {
+            vertices,
+            polygons,
+            polygon_neighbors: vec![PolygonNavmesh::NO_CONNECTION; n * 4],
+            flags: vec![1; n],
+            regions: vec![Default::default(); n],
+            areas: vec![AreaType::DEFAULT_WALKABLE; n],
+            max_vertices_per_polygon: 4,
+            aabb,
+            cell_size: 1.0,
+            cell_height: 1.0,
+            border_size: 0,
+            max_edge_error: 0.0,
+        };
+        (mesh, heightfield)
+    }
+
+    #[test]
+    fn drops_down_ledges() {
+        let (mesh, heightfield) = scene(&[(0, 10, 3), (10, 20, 0)]);
+        let links = mesh.generate_jump_links(&heightfield, &JumpLinkConfig::default());
+        assert_eq!(links.len(), 5);
+
+        let query = NavmeshQuery::new(&mesh);
+        for link in &links {
+            assert_eq!(link.user_id, JumpLinkConfig::default().drop_down_user_id);
+            assert!(!link.bidirectional);
+            let snapped = link.snap(&query, 0.9).unwrap();
+            assert_eq!((snapped.start_polygon, snapped.end_polygon), (0, 1));
+        }
+    }
+
+    #[test]
+    fn jumps_across_gaps_within_reach() {
+        let (mesh, heightfield) = scene(&[(0, 8, 0), (12, 20, 0)]);
+        assert!(
+            mesh.generate_jump_links(&heightfield, &JumpLinkConfig::default())
+                .is_empty()
+        );
+
+        let config = JumpLinkConfig {
+            max_jump_distance: 5.0,
+            ..Default::default()
+        };
+        let links = mesh.generate_jump_links(&heightfield, &config);
+        // One set of links per direction.
+        assert_eq!(links.len(), 10);
+        let query = NavmeshQuery::new(&mesh);
+        for link in &links {
+            assert_eq!(link.user_id, config.jump_user_id);
+            let snapped = link.snap(&query, 0.9).unwrap();
+            assert_ne!(snapped.start_polygon, snapped.end_polygon);
+        }
+    }
+}
@@ -10,38 +10,95 @@ extern crate alloc;
 #[cfg(feature = "std")]
 extern crate std;
 
+#[cfg(feature = "nav_file")]
+mod checkpoint;
 mod compact_cell;
 mod compact_heightfield;
 mod compact_span;
 mod config;
 mod contours;
+mod crowd;
+mod debug_draw;
 mod detail_mesh;
+mod detour_tile;
+mod engine_export;
 mod erosion;
+mod glb;
 mod heightfield;
+mod jump_links;
 mod mark_convex_poly_area;
 pub(crate) mod math;
+#[cfg(feature = "nav_file")]
+mod nav_file;
+mod navmesh;
+mod navmesh_query;
+mod node_pool;
+mod obj;
+mod obstacle_avoidance;
+mod off_mesh_connection;
 pub(crate) mod ops;
+mod path_corridor;
+mod pathfinding;
 mod poly_mesh;
+mod poly_ref;
 mod pre_filter;
+mod proximity_grid;
+mod query_filter;
+mod raster;
 mod rasterize;
 mod region;
 mod span;
+mod straight_path;
+mod svg;
+#[cfg(test)]
+mod test_meshes;
+mod tile_cache;
 mod tiled_navmesh;
 mod trimesh;
 mod watershed_build_regions;
 mod watershed_distance_field;
 
+#[cfg(feature = "nav_file")]
+pub use checkpoint::Checkpoint;
 pub use compact_cell::CompactCell;
 pub use compact_heightfield::CompactHeightfield;
 pub use compact_span::CompactSpan;
 pub use config::{Config, ConfigBuilder};
 pub use contours::{BuildContoursFlags, Contour, ContourSet, RegionVertexId};
+pub use crowd::{
+    Crowd, CrowdAgent, CrowdAgentId, CrowdAgentParams, CrowdAgentState, CrowdError, CrowdNeighbor,
+    CrowdUpdateFlags, MoveRequestState,
+};
+pub use debug_draw::{DebugColor, DebugDraw, DebugDrawPrimitive};
 pub use detail_mesh::{DetailNavmesh, SubMesh};
+pub use detour_tile::DetourTileDataError;
+pub use engine_export::UnityExportError;
 pub use heightfield::{Heightfield, HeightfieldBuilder, HeightfieldBuilderError};
+pub use jump_links::JumpLinkConfig;
 pub use mark_convex_poly_area::ConvexVolume;
 pub use math::{Aabb2d, Aabb3d};
+#[cfg(feature = "nav_file")]
+pub use nav_file::{BakedNavmesh, NavmeshFile, NavmeshFileCompression, NavmeshFileError};
+pub use navmesh::{NavmeshError, TiledNavmesh};
+pub use navmesh_query::{
+    MoveAlongSurfaceResult, NavmeshQuery, NavmeshQueryError, PolygonSearchResult, RaycastHit,
+    WallHit,
+};
+pub use obj::ObjError;
+pub use obstacle_avoidance::{
+    ObstacleAvoidanceMode, ObstacleAvoidanceParams, ObstacleAvoidanceQuery,
+};
+pub use off_mesh_connection::{OffMeshConnection, SnappedOffMeshConnection};
+pub use path_corridor::PathCorridor;
+pub use pathfinding::{FindPathResult, SlicedFindPath, SlicedFindPathStatus};
 pub use poly_mesh::PolygonNavmesh;
+pub use poly_ref::PolyRef;
+pub use query_filter::{PolygonFilter, QueryFilter};
+pub use raster::RasterImage;
 pub use region::RegionId;
 pub use span::{AreaType, Span, SpanKey, Spans};
+pub use straight_path::{StraightPathFlags, StraightPathVertex};
+pub use svg::SvgOptions;
+pub use tile_cache::{Obstacle, ObstacleKey, TileCache};
 pub use tiled_navmesh::{NavmeshTile, TileCoord, TiledNavmeshConfig, TiledNavmeshError};
 pub use trimesh::TriMesh;
@@ -1,7 +1,7 @@
 use alloc::vec::Vec;
-use glam::{IVec3, Vec2};
+use glam::{IVec3, Vec2, Vec3};
 
-use crate::{Aabb2d, AreaType, CompactHeightfield};
+use crate::{Aabb2d, AreaType, CompactHeightfield, ops::FloatPow as _};
 
 impl CompactHeightfield {
     /// Sets the [`AreaType`] of the spans within the given convex volume.
@@ -90,6 +90,68 @@ impl CompactHeightfield {
             }
         }
     }
+
+    /// Sets the [`AreaType`] of the spans within the given vertical cylinder.
+    ///
+    /// `position` is the center of the bottom of the cylinder.
+    pub fn mark_cylinder_area(&mut self, position: Vec3, radius: f32, height: f32, area: AreaType) {
+        let inverse_cell_size = 1.0 / self.cell_size;
+        let inverse_cell_height = 1.0 / self.cell_height;
+
+        // Compute the grid footprint of the cylinder
+        let min = position - Vec3::new(radius, 0.0, radius) - self.aabb.min;
+        let max = position + Vec3::new(radius, height, radius) - self.aabb.min;
+        let mut min = IVec3::new(
+            (min.x * inverse_cell_size) as i32,
+            (min.y * inverse_cell_height) as i32,
+            (min.z * inverse_cell_size) as i32,
+        );
+        let mut max = IVec3::new(
+            (max.x * inverse_cell_size) as i32,
+            (max.y * inverse_cell_height) as i32,
+            (max.z * inverse_cell_size) as i32,
+        );
+
+        // Early-out if the cylinder lies entirely outside the grid.
+        if max.x < 0 || min.x >= self.width as i32 || max.z < 0 || min.z >= self.height as i32 {
+            return;
+        }
+
+        // Clamp the cylinder footprint to the grid
+        min.x = min.x.max(0);
+        max.x = max.x.min(self.width as i32 - 1);
+        min.z = min.z.max(0);
+        max.z = max.z.min(self.height as i32 - 1);
+
+        let radius_squared = radius.squared();
+        for z in min.z..=max.z {
+            for x in min.x..=max.x {
+                let point = Vec2::new(
+                    self.aabb.min.x + (x as f32 + 0.5) * self.cell_size,
+                    self.aabb.min.z + (z as f32 + 0.5) * self.cell_size,
+                );
+                if point.distance_squared(Vec2::new(position.x, position.z)) >= radius_squared {
+                    continue;
+                }
+
+                let cell = &self.cells[(x + z * self.width as i32) as usize];
+                for i in cell.index_range() {
+                    // Skip if span is removed
+                    if !self.areas[i].is_walkable() {
+                        continue;
+                    }
+
+                    // Skip if y extents don't overlap
+                    let span_y = self.spans[i].y as i32;
+                    if span_y < min.y || span_y > max.y {
+                        continue;
+                    }
+
+                    self.areas[i] = area;
+                }
+            }
+        }
+    }
 }
 
 // Optimized point-in-polygon test with inlining
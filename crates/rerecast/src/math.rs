@@ -1,4 +1,5 @@
-use glam::{U16Vec2, UVec3, Vec2, Vec3, Vec3A};
+use crate::ops::{abs, sqrt};
+use glam::{U16Vec2, UVec3, Vec2, Vec3, Vec3A, Vec3Swizzles as _};
 
 /// A 3D axis-aligned bounding box
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
@@ -214,6 +215,222 @@ pub(crate) fn distance_squared_between_point_and_line_vec3(
     dt.length_squared()
 }
 
+/// Returns the perp dot product of `u` and `v` on the xz-plane.
+#[inline]
+pub(crate) fn perp_xz(u: Vec3, v: Vec3) -> f32 {
+    u.z * v.x - u.x * v.z
+}
+
+/// Returns the squared distance between `pt` and the segment `(p, q)` on the xz-plane,
+/// together with the parametric position of the closest point on the segment.
+#[inline]
+pub(crate) fn distance_squared_between_point_and_segment_xz(
+    pt: Vec3,
+    (p, q): (Vec3, Vec3),
+) -> (f32, f32) {
+    let pq = (q - p).xz();
+    let d = pq.length_squared();
+    let mut t = pq.dot((pt - p).xz());
+    if d > 0.0 {
+        t /= d;
+    }
+    t = t.clamp(0.0, 1.0);
+    let dt = p.xz() + t * pq - pt.xz();
+    (dt.length_squared(), t)
+}
+
+/// Returns the height of the triangle `(a, b, c)` at `p` on the xz-plane,
+/// or `None` if `p` lies outside of the triangle.
+#[inline]
+pub(crate) fn closest_height_point_triangle(p: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+    const EPS: f32 = 1e-6;
+    let v0 = c - a;
+    let v1 = b - a;
+    let v2 = p - a;
+
+    let mut denom = v0.x * v1.z - v0.z * v1.x;
+    if abs(denom) < EPS {
+        return None;
+    }
+    let mut u = v1.z * v2.x - v1.x * v2.z;
+    let mut v = v0.x * v2.z - v0.z * v2.x;
+    if denom < 0.0 {
+        denom = -denom;
+        u = -u;
+        v = -v;
+    }
+
+    // If point lies inside the triangle, return interpolated y-coord.
+    if u >= 0.0 && v >= 0.0 && (u + v) <= denom {
+        Some(a.y + (v0.y * u + v1.y * v) / denom)
+    } else {
+        None
+    }
+}
+
+/// Returns whether `pt` lies inside the polygon `vertices` on the xz-plane.
+#[inline]
+pub(crate) fn point_in_polygon_xz(pt: Vec3, vertices: &[Vec3]) -> bool {
+    let mut inside = false;
+    let n = vertices.len();
+    let mut j = n.wrapping_sub(1);
+    for i in 0..n {
+        let vi = vertices[i];
+        let vj = vertices[j];
+        if ((vi.z > pt.z) != (vj.z > pt.z))
+            && (pt.x < (vj.x - vi.x) * (pt.z - vi.z) / (vj.z - vi.z) + vi.x)
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Returns whether the convex polygons `a` and `b` overlap on the xz-plane.
+///
+/// Polygons that merely touch are not considered overlapping.
+pub(crate) fn overlap_polygon_polygon_xz(a: &[Vec3], b: &[Vec3]) -> bool {
+    const EPS: f32 = 1e-4;
+
+    fn project(polygon: &[Vec3], axis: Vec2) -> (f32, f32) {
+        polygon.iter().fold((f32::MAX, f32::MIN), |(min, max), v| {
+            let d = axis.dot(v.xz());
+            (min.min(d), max.max(d))
+        })
+    }
+
+    // Separating axis test with the edge normals of both polygons.
+    for polygon in [a, b] {
+        let n = polygon.len();
+        let mut j = n.wrapping_sub(1);
+        for i in 0..n {
+            let edge = polygon[i] - polygon[j];
+            let axis = Vec2::new(edge.z, -edge.x);
+            let (a_min, a_max) = project(a, axis);
+            let (b_min, b_max) = project(b, axis);
+            if a_min + EPS > b_max || a_max - EPS < b_min {
+                // Found separating axis
+                return false;
+            }
+            j = i;
+        }
+    }
+    true
+}
+
+/// Returns twice the signed area of the triangle `(a, b, c)` on the xz-plane.
+#[inline]
+pub(crate) fn triangle_area_xz(a: Vec3, b: Vec3, c: Vec3) -> f32 {
+    let ab = b - a;
+    let ac = c - a;
+    ac.x * ab.z - ab.x * ac.z
+}
+
+/// Returns a point inside the convex polygon `vertices`, uniformly distributed over its area on the xz-plane.
+///
+/// `s` and `t` are random numbers in the range `[0, 1)`.
+pub(crate) fn random_point_in_convex_polygon(vertices: &[Vec3], s: f32, t: f32) -> Vec3 {
+    // Calc triangle areas
+    let area_sum: f32 = (2..vertices.len())
+        .map(|i| abs(triangle_area_xz(vertices[0], vertices[i - 1], vertices[i])).max(0.001))
+        .sum();
+
+    // Find sub triangle weighted by area.
+    let threshold = s * area_sum;
+    let mut accumulated = 0.0;
+    let mut u = 1.0;
+    let mut triangle = vertices.len() - 1;
+    for i in 2..vertices.len() {
+        let area = abs(triangle_area_xz(vertices[0], vertices[i - 1], vertices[i])).max(0.001);
+        if threshold >= accumulated && threshold < accumulated + area {
+            u = (threshold - accumulated) / area;
+            triangle = i;
+            break;
+        }
+        accumulated += area;
+    }
+
+    let v = sqrt(t);
+    let a = 1.0 - v;
+    let b = (1.0 - u) * v;
+    let c = u * v;
+    a * vertices[0] + b * vertices[triangle - 1] + c * vertices[triangle]
+}
+
+/// The result of [`intersect_segment_polygon_xz`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SegmentPolygonIntersection {
+    /// The parametric position along the segment where it enters the polygon.
+    pub(crate) t_min: f32,
+    /// The parametric position along the segment where it leaves the polygon.
+    pub(crate) t_max: f32,
+    /// The polygon edge through which the segment enters the polygon, if it starts outside of it.
+    pub(crate) edge_min: Option<usize>,
+    /// The polygon edge through which the segment leaves the polygon, if it ends outside of it.
+    pub(crate) edge_max: Option<usize>,
+}
+
+/// Intersects the segment `(p0, p1)` with the convex polygon `vertices` on the xz-plane.
+///
+/// Edge `i` of the polygon runs from `vertices[i]` to `vertices[i + 1]`.
+/// Returns `None` if the segment does not overlap the polygon.
+pub(crate) fn intersect_segment_polygon_xz(
+    p0: Vec3,
+    p1: Vec3,
+    vertices: &[Vec3],
+) -> Option<SegmentPolygonIntersection> {
+    const EPS: f32 = 1e-8;
+    let mut hit = SegmentPolygonIntersection {
+        t_min: 0.0,
+        t_max: 1.0,
+        edge_min: None,
+        edge_max: None,
+    };
+    let dir = p1 - p0;
+
+    let n = vertices.len();
+    let mut j = n.wrapping_sub(1);
+    for i in 0..n {
+        let edge = vertices[i] - vertices[j];
+        let diff = p0 - vertices[j];
+        let num = perp_xz(edge, diff);
+        let den = perp_xz(dir, edge);
+        if abs(den) < EPS {
+            // S is nearly parallel to this edge
+            if num < 0.0 {
+                return None;
+            }
+            j = i;
+            continue;
+        }
+        let t = num / den;
+        if den < 0.0 {
+            // segment S is entering across this edge
+            if t > hit.t_min {
+                hit.t_min = t;
+                hit.edge_min = Some(j);
+                // S enters after leaving polygon
+                if hit.t_min > hit.t_max {
+                    return None;
+                }
+            }
+        } else {
+            // segment S is leaving across this edge
+            if t < hit.t_max {
+                hit.t_max = t;
+                hit.edge_max = Some(j);
+                // S leaves before entering polygon
+                if hit.t_max < hit.t_min {
+                    return None;
+                }
+            }
+        }
+        j = i;
+    }
+    Some(hit)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
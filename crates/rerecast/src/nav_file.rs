@@ -0,0 +1,363 @@
+//! A versioned binary container for baked navmeshes.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use std::io::{Read as _, Write as _};
+use thiserror::Error;
+
+use crate::{Config, DetailNavmesh, NavmeshTile, PolygonNavmesh, TileCoord, TiledNavmesh};
+
+/// A baked navmesh together with the [`Config`] it was generated with, stored in a versioned binary format.
+///
+/// A file consists of a 20 byte header followed by the payload. All integers are little-endian.
+///
+/// | Offset | Size | Content                                                              |
+/// |--------|------|----------------------------------------------------------------------|
+/// | 0      | 4    | The magic bytes `RRNM`                                               |
+/// | 4      | 2    | The format version, see [`NavmeshFile::VERSION`]                      |
+/// | 6      | 1    | The compression of the payload, see [`NavmeshFileCompression`]        |
+/// | 7      | 1    | Reserved, always `0`                                                 |
+/// | 8      | 4    | The CRC-32 checksum of the uncompressed payload                      |
+/// | 12     | 4    | The length of the payload as stored in the file                      |
+/// | 16     | 4    | The length of the uncompressed payload                               |
+/// | 20     | ...  | This struct, encoded with bincode's standard configuration    |
+///
+/// The version is incremented whenever the payload or the header changes in an incompatible way.
+/// Files of other versions are rejected with [`NavmeshFileError::UnsupportedVersion`] and must be baked again.
+/// Compressed payloads are never decompressed past their uncompressed length, so corrupt files cannot exhaust memory.
+///
+/// ```
+/// # use rerecast::*;
+/// # fn example(file: NavmeshFile) -> Result<(), NavmeshFileError> {
+/// let bytes = file.to_bytes(NavmeshFileCompression::Deflate)?;
+/// let loaded = NavmeshFile::from_bytes(&bytes)?;
+/// assert_eq!(loaded, file);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct NavmeshFile {
+    /// The configuration the navmesh was generated with.
+    pub config: Config,
+    /// The navmesh itself.
+    pub navmesh: BakedNavmesh,
+}
+
+/// The navmesh stored in a [`NavmeshFile`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[expect(
+    clippy::large_enum_variant,
+    reason = "A baked navmesh is loaded once, so boxing the single mesh would not save anything."
+)]
+pub enum BakedNavmesh {
+    /// A navmesh generated in one piece.
+    Single {
+        /// The polygon mesh.
+        poly_mesh: PolygonNavmesh,
+        /// The detail mesh of [`Self::Single::poly_mesh`].
+        detail_mesh: DetailNavmesh,
+    },
+    /// A navmesh generated as tiles, e.g. by [`TiledNavmeshConfig::generate_tiles`](crate::TiledNavmeshConfig::generate_tiles).
+    Tiled(Vec<NavmeshTile>),
+}
+
+impl BakedNavmesh {
+    /// Collects the tiles into a [`TiledNavmesh`]. A single navmesh becomes the only tile, at the origin.
+    pub fn into_tiled_navmesh(self) -> TiledNavmesh {
+        match self {
+            BakedNavmesh::Single {
+                poly_mesh,
+                detail_mesh,
+            } => TiledNavmesh::from_tiles([NavmeshTile {
+                coord: TileCoord { x: 0, z: 0 },
+                poly_mesh,
+                detail_mesh,
+                off_mesh_connections: Vec::new(),
+            }]),
+            BakedNavmesh::Tiled(tiles) => TiledNavmesh::from_tiles(tiles),
+        }
+    }
+}
+
+/// The compression of the payload of a [`NavmeshFile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum NavmeshFileCompression {
+    /// The payload is stored as is.
+    None = 0,
+    /// The payload is compressed with DEFLATE.
+    #[default]
+    Deflate = 1,
+}
+
+impl NavmeshFileCompression {
+    fn from_u8(value: u8) -> Result<Self, NavmeshFileError> {
+        match value {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Deflate),
+            _ => Err(NavmeshFileError::UnknownCompression(value)),
+        }
+    }
+}
+
+impl NavmeshFile {
+    /// The magic bytes at the start of every file.
+    pub const MAGIC: [u8; 4] = *b"RRNM";
+    /// The format version written by this version of the crate, and the only one it can read.
+    pub const VERSION: u16 = 1;
+
+    /// Encodes the file, compressing the payload with `compression`.
+    pub fn to_bytes(
+        &self,
+        compression: NavmeshFileCompression,
+    ) -> Result<Vec<u8>, NavmeshFileError> {
+        write_container(Self::MAGIC, self, compression)
+    }
+
+    /// Decodes a file written by [`Self::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, NavmeshFileError> {
+        read_container(Self::MAGIC, bytes)
+    }
+}
+
+const HEADER_LEN: usize = 20;
+
+/// Writes `value` with the header described in [`NavmeshFile`], starting with `magic`.
+pub(crate) fn write_container<T: serde::Serialize>(
+    magic: [u8; 4],
+    value: &T,
+    compression: NavmeshFileCompression,
+) -> Result<Vec<u8>, NavmeshFileError> {
+    let payload = bincode::serde::encode_to_vec(value, bincode::config::standard())
+        .map_err(|e| NavmeshFileError::Encode(e.to_string()))?;
+    let checksum = crc32(&payload);
+    let uncompressed_len = u32::try_from(payload.len())
+        .map_err(|_| NavmeshFileError::Encode("payload exceeds 4 GiB".to_string()))?;
+    let payload = match compression {
+        NavmeshFileCompression::None => payload,
+        NavmeshFileCompression::Deflate => {
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder
+                .write_all(&payload)
+                .and_then(|()| encoder.finish())
+                .map_err(|e| NavmeshFileError::Compression(e.to_string()))?
+        }
+    };
+    let payload_len = u32::try_from(payload.len())
+        .map_err(|_| NavmeshFileError::Encode("payload exceeds 4 GiB".to_string()))?;
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(&magic);
+    bytes.extend_from_slice(&NavmeshFile::VERSION.to_le_bytes());
+    bytes.push(compression as u8);
+    bytes.push(0);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    bytes.extend_from_slice(&payload_len.to_le_bytes());
+    bytes.extend_from_slice(&uncompressed_len.to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// Reads a value written by [`write_container`] with the same `magic`.
+pub(crate) fn read_container<T: serde::de::DeserializeOwned>(
+    magic: [u8; 4],
+    bytes: &[u8],
+) -> Result<T, NavmeshFileError> {
+    if bytes.get(..4) != Some(magic.as_slice()) {
+        return Err(NavmeshFileError::InvalidMagic);
+    }
+    let header = bytes.get(..HEADER_LEN).ok_or(NavmeshFileError::Truncated)?;
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version != NavmeshFile::VERSION {
+        return Err(NavmeshFileError::UnsupportedVersion {
+            found: version,
+            supported: NavmeshFile::VERSION,
+        });
+    }
+    let compression = NavmeshFileCompression::from_u8(header[6])?;
+    let checksum = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+    let payload_len = u32::from_le_bytes([header[12], header[13], header[14], header[15]]);
+    let uncompressed_len = u32::from_le_bytes([header[16], header[17], header[18], header[19]]);
+    let payload = bytes[HEADER_LEN..]
+        .get(..payload_len as usize)
+        .ok_or(NavmeshFileError::Truncated)?;
+
+    let payload = match compression {
+        NavmeshFileCompression::None => payload.to_vec(),
+        NavmeshFileCompression::Deflate => {
+            // Reading one byte past the expected length detects payloads that decompress to more.
+            let mut decompressed = Vec::new();
+            flate2::read::DeflateDecoder::new(payload)
+                .take(u64::from(uncompressed_len) + 1)
+                .read_to_end(&mut decompressed)
+                .map_err(|e| NavmeshFileError::Compression(e.to_string()))?;
+            decompressed
+        }
+    };
+    if payload.len() != uncompressed_len as usize {
+        return Err(NavmeshFileError::PayloadLengthMismatch {
+            expected: uncompressed_len,
+        });
+    }
+    let found = crc32(&payload);
+    if found != checksum {
+        return Err(NavmeshFileError::ChecksumMismatch {
+            expected: checksum,
+            found,
+        });
+    }
+
+    let (value, _) = bincode::serde::decode_from_slice(&payload, bincode::config::standard())
+        .map_err(|e| NavmeshFileError::Decode(e.to_string()))?;
+    Ok(value)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+/// Errors that can occur when reading or writing a [`NavmeshFile`].
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum NavmeshFileError {
+    /// The data does not start with [`NavmeshFile::MAGIC`].
+    #[error("Not a rerecast navmesh file")]
+    InvalidMagic,
+    /// The file was written with a different version of the format.
+    #[error(
+        "Navmesh file format version {found} is not supported, expected version {supported}. Bake the navmesh again"
+    )]
+    UnsupportedVersion {
+        /// The version of the file.
+        found: u16,
+        /// The version supported by this version of the crate.
+        supported: u16,
+    },
+    /// The data holds a different stage than the one requested from [`Checkpoint::from_checkpoint`](crate::Checkpoint::from_checkpoint).
+    #[error("Expected a {expected} checkpoint, found a {found}")]
+    StageMismatch {
+        /// The requested stage.
+        expected: &'static str,
+        /// The stage stored in the data.
+        found: &'static str,
+    },
+    /// The file uses a compression that is not supported.
+    #[error("Unknown navmesh file compression {0}")]
+    UnknownCompression(u8),
+    /// The file ends before its payload does.
+    #[error("Navmesh file is truncated")]
+    Truncated,
+    /// The payload does not match its checksum.
+    #[error("Navmesh file is corrupt: expected checksum {expected:#010x}, found {found:#010x}")]
+    ChecksumMismatch {
+        /// The checksum stored in the header.
+        expected: u32,
+        /// The checksum of the payload.
+        found: u32,
+    },
+    /// The uncompressed payload is longer or shorter than the length stored in the header.
+    #[error(
+        "Navmesh file is corrupt: the payload does not have the expected length of {expected} bytes"
+    )]
+    PayloadLengthMismatch {
+        /// The length stored in the header.
+        expected: u32,
+    },
+    /// Error compressing or decompressing the payload
+    #[error("Failed to compress or decompress navmesh file: {0}")]
+    Compression(String),
+    /// Error encoding the payload
+    #[error("Failed to encode navmesh file: {0}")]
+    Encode(String),
+    /// Error decoding the payload
+    #[error("Failed to decode navmesh file: {0}")]
+    Decode(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AreaType, ConfigBuilder};
+    use glam::U16Vec3;
+
+    fn file() -> NavmeshFile {
+        NavmeshFile {
+            config: ConfigBuilder::default().build(),
+            navmesh: BakedNavmesh::Single {
+                poly_mesh: PolygonNavmesh {
+                    vertices: vec![
+                        U16Vec3::new(0, 0, 0),
+                        U16Vec3::new(1, 0, 0),
+                        U16Vec3::new(0, 0, 1),
+                    ],
+                    polygons: vec![0, 2, 1],
+                    polygon_neighbors: vec![PolygonNavmesh::NO_CONNECTION; 3],
+                    flags: vec![1],
+                    regions: vec![Default::default()],
+                    areas: vec![AreaType::DEFAULT_WALKABLE],
+                    max_vertices_per_polygon: 3,
+                    ..Default::default()
+                },
+                detail_mesh: DetailNavmesh::default(),
+            },
+        }
+    }
+
+    #[test]
+    fn round_trips_with_and_without_compression() {
+        let file = file();
+        for compression in [
+            NavmeshFileCompression::None,
+            NavmeshFileCompression::Deflate,
+        ] {
+            let bytes = file.to_bytes(compression).unwrap();
+            assert_eq!(NavmeshFile::from_bytes(&bytes).unwrap(), file);
+        }
+    }
+
+    #[test]
+    fn rejects_incompatible_or_corrupt_files() {
+        let bytes = file().to_bytes(NavmeshFileCompression::None).unwrap();
+
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(NavmeshFile::VERSION + 1).to_le_bytes());
+        assert_eq!(
+            NavmeshFile::from_bytes(&newer),
+            Err(NavmeshFileError::UnsupportedVersion {
+                found: NavmeshFile::VERSION + 1,
+                supported: NavmeshFile::VERSION
+            })
+        );
+
+        let mut corrupt = bytes.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            NavmeshFile::from_bytes(&corrupt),
+            Err(NavmeshFileError::ChecksumMismatch { .. })
+        ));
+        assert_eq!(
+            NavmeshFile::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(NavmeshFileError::Truncated)
+        );
+
+        // A payload that decompresses to more than the stated length is not read past it.
+        let compressed = file().to_bytes(NavmeshFileCompression::Deflate).unwrap();
+        let mut understated = compressed.clone();
+        understated[16..20].copy_from_slice(&1_u32.to_le_bytes());
+        assert_eq!(
+            NavmeshFile::from_bytes(&understated),
+            Err(NavmeshFileError::PayloadLengthMismatch { expected: 1 })
+        );
+
+        // Files written before the format was versioned have no header.
+        let legacy = include_bytes!("../../../assets/test/primitives/navmesh_1.nav");
+        assert_eq!(
+            NavmeshFile::from_bytes(legacy),
+            Err(NavmeshFileError::InvalidMagic)
+        );
+    }
+}
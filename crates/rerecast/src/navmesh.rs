@@ -0,0 +1,858 @@
+//! A runtime navmesh made of independently replaceable tiles.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{
+    Aabb3d, AreaType, NavmeshQuery, NavmeshTile, PolyRef, TileCoord,
+    ops::{FloatPow as _, abs},
+};
+use glam::{Vec2, Vec3};
+use thiserror::Error;
+
+/// A navmesh assembled from [`NavmeshTile`]s at runtime, modelled after Detour's `dtNavMesh`.
+///
+/// Each tile lives in a slot with a salt. Polygons are referenced across tile changes with [`PolyRef`]s,
+/// which stop being valid once the tile they were created for is replaced or removed.
+///
+/// Like Detour's `dtNavMesh::connectExtLinks`, adding a tile links the portals on its border to the overlapping portals
+/// of the neighboring tiles, and removing it drops these links again. Off-mesh connections that lead into another tile
+/// are linked to the polygons of that tile the same way. Queries created with [`TiledNavmesh::query`]
+/// follow the links, so paths, [`PathCorridor`](crate::PathCorridor)s and [`Crowd`](crate::Crowd)s cross tile borders.
+#[derive(Debug, Clone)]
+pub struct TiledNavmesh {
+    slots: Vec<TileSlot>,
+    /// Indices of empty slots, reused before new slots are allocated.
+    free_slots: Vec<u32>,
+    /// The slot of the tile at each coordinate.
+    lookup: BTreeMap<TileCoord, u32>,
+    /// The maximum height difference between two portals that are linked. `[Units: wu]`
+    walkable_climb: f32,
+}
+
+#[derive(Debug, Clone)]
+struct TileSlot {
+    /// Incremented every time the tile of the slot is replaced or removed. Never `0`.
+    salt: u16,
+    tile: Option<NavmeshTile>,
+    /// The links from the portals of the tile to the polygons of its neighbors.
+    links: Vec<TileLink>,
+    /// The ends of the off-mesh connections of the tile that lead into other tiles.
+    connection_links: Vec<OffMeshConnectionLink>,
+}
+
+/// A link from a portal edge on the border of a tile to a polygon of the neighboring tile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TileLink {
+    /// The polygon of the tile the link starts from.
+    pub(crate) polygon: u16,
+    /// The edge of [`Self::polygon`] the link crosses.
+    pub(crate) edge: u8,
+    /// The polygon of the neighboring tile the link leads to.
+    pub(crate) neighbor: PolyRef,
+    /// The part of the edge shared with the neighbor, as parameters from the start to the end vertex of the edge.
+    pub(crate) range: (f32, f32),
+}
+
+/// The end of an off-mesh connection that leads into another tile, see [`SnappedOffMeshConnection::end_tile`](crate::SnappedOffMeshConnection::end_tile).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct OffMeshConnectionLink {
+    /// The index of the connection in the [`NavmeshTile::off_mesh_connections`] of its tile.
+    pub(crate) connection: u16,
+    /// The polygon of the other tile the connection ends on.
+    pub(crate) end_polygon: PolyRef,
+    /// The end position, moved onto [`Self::end_polygon`].
+    pub(crate) end: Vec3,
+}
+
+impl Default for TiledNavmesh {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            lookup: BTreeMap::new(),
+            walkable_climb: 0.9,
+        }
+    }
+}
+
+impl TiledNavmesh {
+    /// Creates an empty navmesh.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum height difference between the portals of two neighboring tiles for them to be linked,
+    /// and between the end of an off-mesh connection and the polygon it is linked to in another tile, usually the [`ConfigBuilder::walkable_climb`](crate::ConfigBuilder::walkable_climb) the tiles were built with.
+    ///
+    /// Defaults to `0.9`, the default of [`ConfigBuilder`](crate::ConfigBuilder). Only affects tiles added afterwards.
+    pub fn with_walkable_climb(mut self, walkable_climb: f32) -> Self {
+        self.walkable_climb = walkable_climb;
+        self
+    }
+
+    /// Creates a navmesh from the given tiles, e.g. the output of [`TiledNavmeshConfig::generate_tiles`](crate::TiledNavmeshConfig::generate_tiles).
+    pub fn from_tiles(tiles: impl IntoIterator<Item = NavmeshTile>) -> Self {
+        let mut navmesh = Self::new();
+        for tile in tiles {
+            navmesh.add_tile(tile);
+        }
+        navmesh
+    }
+
+    /// Adds a tile and returns the reference to its first polygon,
+    /// which can be turned into references to its other polygons with [`PolyRef::with_polygon`].
+    ///
+    /// A tile already present at the same [`TileCoord`] is replaced, which invalidates all references to its polygons.
+    /// The portals of the tile are linked to the portals of the neighboring tiles,
+    /// and off-mesh connections are linked between the tile and the tiles they lead into.
+    pub fn add_tile(&mut self, tile: NavmeshTile) -> PolyRef {
+        let index = if let Some(&index) = self.lookup.get(&tile.coord) {
+            self.unlink_tile(index);
+            let slot = &mut self.slots[index as usize];
+            slot.salt = next_salt(slot.salt);
+            index
+        } else if let Some(index) = self.free_slots.pop() {
+            index
+        } else {
+            self.slots.push(TileSlot {
+                salt: 1,
+                tile: None,
+                links: Vec::new(),
+                connection_links: Vec::new(),
+            });
+            (self.slots.len() - 1) as u32
+        };
+        self.lookup.insert(tile.coord, index);
+        self.slots[index as usize].tile = Some(tile);
+        self.link_tile(index);
+        self.slot_ref(index)
+    }
+
+    /// Removes the tile at the given coordinate, invalidating all references to its polygons
+    /// and unlinking it from its neighbors.
+    pub fn remove_tile(&mut self, coord: TileCoord) -> Option<NavmeshTile> {
+        let index = *self.lookup.get(&coord)?;
+        self.unlink_tile(index);
+        self.lookup.remove(&coord);
+        let slot = &mut self.slots[index as usize];
+        slot.salt = next_salt(slot.salt);
+        self.free_slots.push(index);
+        slot.tile.take()
+    }
+
+    /// Links the portals of the tile in the given slot and the portals of its neighbors facing it.
+    fn link_tile(&mut self, index: u32) {
+        let tile_ref = self.slot_ref(index);
+        for (side, neighbor_index) in self.neighbor_slots(index) {
+            let neighbor_ref = self.slot_ref(neighbor_index);
+            let (Some(tile), Some(neighbor)) = (
+                self.slots[index as usize].tile.as_ref(),
+                self.slots[neighbor_index as usize].tile.as_ref(),
+            ) else {
+                continue;
+            };
+            let links = find_tile_links(tile, side, neighbor, neighbor_ref, self.walkable_climb);
+            let back_links = find_tile_links(
+                neighbor,
+                opposite_side(side),
+                tile,
+                tile_ref,
+                self.walkable_climb,
+            );
+            self.slots[index as usize].links.extend(links);
+            self.slots[neighbor_index as usize].links.extend(back_links);
+        }
+        self.link_off_mesh_connections(index);
+    }
+
+    /// Links the off-mesh connections of the tile in the given slot that lead into other tiles,
+    /// and the off-mesh connections of other tiles that lead into it.
+    fn link_off_mesh_connections(&mut self, index: u32) {
+        let Some(tile) = &self.slots[index as usize].tile else {
+            return;
+        };
+        // The connections to link as `(slot of the connection, connection, slot of its end)`.
+        let mut pending = Vec::new();
+        for (connection, snapped) in tile.off_mesh_connections.iter().enumerate() {
+            if let Some(&end_index) = snapped.end_tile.and_then(|coord| self.lookup.get(&coord)) {
+                pending.push((index, connection, end_index));
+            }
+        }
+        for (from_index, slot) in self.slots.iter().enumerate() {
+            let Some(from) = slot.tile.as_ref().filter(|_| from_index as u32 != index) else {
+                continue;
+            };
+            for (connection, snapped) in from.off_mesh_connections.iter().enumerate() {
+                if snapped.end_tile == Some(tile.coord) {
+                    pending.push((from_index as u32, connection, index));
+                }
+            }
+        }
+
+        let mut queries = BTreeMap::new();
+        let mut links = Vec::new();
+        for (from_index, connection, end_index) in pending {
+            let (Some(from), Some(end_tile)) = (
+                &self.slots[from_index as usize].tile,
+                &self.slots[end_index as usize].tile,
+            ) else {
+                continue;
+            };
+            let query = queries.entry(end_index).or_insert_with(|| {
+                NavmeshQuery::new(&end_tile.poly_mesh).with_detail_mesh(&end_tile.detail_mesh)
+            });
+            let snapped = &from.off_mesh_connections[connection];
+            let Some((end_polygon, end)) =
+                snapped
+                    .connection
+                    .snap_point(snapped.connection.end, query, self.walkable_climb)
+            else {
+                continue;
+            };
+            links.push((
+                from_index,
+                OffMeshConnectionLink {
+                    connection: connection as u16,
+                    end_polygon: self.slot_ref(end_index).with_polygon(end_polygon),
+                    end,
+                },
+            ));
+        }
+        for (from_index, link) in links {
+            self.slots[from_index as usize].connection_links.push(link);
+        }
+    }
+
+    /// Drops the links of the tile in the given slot and the links of other tiles leading into it.
+    fn unlink_tile(&mut self, index: u32) {
+        self.slots[index as usize].links.clear();
+        for (_, neighbor_index) in self.neighbor_slots(index) {
+            self.slots[neighbor_index as usize]
+                .links
+                .retain(|link| link.neighbor.tile() != index);
+        }
+        self.slots[index as usize].connection_links.clear();
+        for slot in &mut self.slots {
+            slot.connection_links
+                .retain(|link| link.end_polygon.tile() != index);
+        }
+    }
+
+    /// Returns the slots of the tiles next to the tile in the given slot, together with the side of the tile they lie on.
+    fn neighbor_slots(&self, index: u32) -> Vec<(u16, u32)> {
+        let Some(tile) = &self.slots[index as usize].tile else {
+            return Vec::new();
+        };
+        let TileCoord { x, z } = tile.coord;
+        // The sides are numbered like the portals of `PolygonNavmesh::polygon_neighbors`.
+        [
+            (0, x.checked_sub(1).map(|x| TileCoord { x, z })),
+            (1, z.checked_add(1).map(|z| TileCoord { x, z })),
+            (2, x.checked_add(1).map(|x| TileCoord { x, z })),
+            (3, z.checked_sub(1).map(|z| TileCoord { x, z })),
+        ]
+        .into_iter()
+        .filter_map(|(side, coord)| Some((side, *self.lookup.get(&coord?)?)))
+        .collect()
+    }
+
+    fn slot_ref(&self, index: u32) -> PolyRef {
+        PolyRef::new(self.slots[index as usize].salt, index, 0)
+    }
+
+    /// Returns the number of tiles.
+    #[inline]
+    pub fn tile_count(&self) -> usize {
+        self.lookup.len()
+    }
+
+    /// Returns the tiles together with the references to their first polygons.
+    pub fn tiles(&self) -> impl Iterator<Item = (PolyRef, &NavmeshTile)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let tile = slot.tile.as_ref()?;
+            Some((PolyRef::new(slot.salt, index as u32, 0), tile))
+        })
+    }
+
+    /// Returns the links from the edges of the referenced polygon to the polygons of neighboring tiles, as `(edge, neighbor)`.
+    pub fn tile_links(&self, poly_ref: PolyRef) -> Result<Vec<(usize, PolyRef)>, NavmeshError> {
+        self.tile_and_polygon_by_ref(poly_ref)
+            .ok_or(NavmeshError::InvalidPolyRef(poly_ref))?;
+        Ok(self.slots[poly_ref.tile() as usize]
+            .links
+            .iter()
+            .filter(|link| link.polygon == poly_ref.polygon())
+            .map(|link| (link.edge as usize, link.neighbor))
+            .collect())
+    }
+
+    /// Returns the reference to the first polygon of the tile at the given coordinate.
+    pub fn tile_ref_at(&self, coord: TileCoord) -> Option<PolyRef> {
+        let index = *self.lookup.get(&coord)?;
+        Some(self.slot_ref(index))
+    }
+
+    /// Returns the tile at the given coordinate.
+    pub fn tile_at(&self, coord: TileCoord) -> Option<&NavmeshTile> {
+        let index = *self.lookup.get(&coord)?;
+        self.slots[index as usize].tile.as_ref()
+    }
+
+    /// Returns the tile referenced by the given reference, ignoring its polygon index.
+    ///
+    /// Returns `None` if the tile has been replaced or removed since the reference was created.
+    pub fn tile_by_ref(&self, poly_ref: PolyRef) -> Option<&NavmeshTile> {
+        let slot = self.slots.get(poly_ref.tile() as usize)?;
+        if slot.salt != poly_ref.salt() {
+            return None;
+        }
+        slot.tile.as_ref()
+    }
+
+    /// Returns the referenced tile and the index of the referenced polygon in the tile's [`PolygonNavmesh`](crate::PolygonNavmesh).
+    /// Indices past the polygons of the mesh reference the tile's [`NavmeshTile::off_mesh_connections`].
+    ///
+    /// Returns `None` if the reference is not valid.
+    pub fn tile_and_polygon_by_ref(&self, poly_ref: PolyRef) -> Option<(&NavmeshTile, u16)> {
+        let tile = self.tile_by_ref(poly_ref)?;
+        let polygon = poly_ref.polygon();
+        let polygon_count = tile.poly_mesh.polygon_count() + tile.off_mesh_connections.len();
+        ((polygon as usize) < polygon_count).then_some((tile, polygon))
+    }
+
+    /// Returns whether the reference points to an existing polygon of a tile that has not been replaced or removed since.
+    #[inline]
+    pub fn is_valid_poly_ref(&self, poly_ref: PolyRef) -> bool {
+        self.tile_and_polygon_by_ref(poly_ref).is_some()
+    }
+
+    /// Returns the flags of the referenced polygon or off-mesh connection.
+    pub fn poly_flags(&self, poly_ref: PolyRef) -> Result<u16, NavmeshError> {
+        let (tile, polygon) = self
+            .tile_and_polygon_by_ref(poly_ref)
+            .ok_or(NavmeshError::InvalidPolyRef(poly_ref))?;
+        Ok(match off_mesh_index(tile, polygon) {
+            Some(index) => tile.off_mesh_connections[index].connection.flags,
+            None => tile.poly_mesh.flags[polygon as usize],
+        })
+    }
+
+    /// Sets the flags of the referenced polygon or off-mesh connection, e.g. to close a door.
+    ///
+    /// Takes effect for all queries created afterwards, without regenerating the tile.
+    pub fn set_poly_flags(&mut self, poly_ref: PolyRef, flags: u16) -> Result<(), NavmeshError> {
+        let (tile, polygon) = self.tile_and_polygon_by_ref_mut(poly_ref)?;
+        match off_mesh_index(tile, polygon) {
+            Some(index) => tile.off_mesh_connections[index].connection.flags = flags,
+            None => tile.poly_mesh.flags[polygon as usize] = flags,
+        }
+        Ok(())
+    }
+
+    /// Returns the area type of the referenced polygon or off-mesh connection.
+    pub fn poly_area(&self, poly_ref: PolyRef) -> Result<AreaType, NavmeshError> {
+        let (tile, polygon) = self
+            .tile_and_polygon_by_ref(poly_ref)
+            .ok_or(NavmeshError::InvalidPolyRef(poly_ref))?;
+        Ok(match off_mesh_index(tile, polygon) {
+            Some(index) => tile.off_mesh_connections[index].connection.area,
+            None => tile.poly_mesh.areas[polygon as usize],
+        })
+    }
+
+    /// Sets the area type of the referenced polygon or off-mesh connection, e.g. to make a burning floor expensive to cross.
+    ///
+    /// Takes effect for all queries created afterwards, without regenerating the tile.
+    pub fn set_poly_area(&mut self, poly_ref: PolyRef, area: AreaType) -> Result<(), NavmeshError> {
+        let (tile, polygon) = self.tile_and_polygon_by_ref_mut(poly_ref)?;
+        match off_mesh_index(tile, polygon) {
+            Some(index) => tile.off_mesh_connections[index].connection.area = area,
+            None => tile.poly_mesh.areas[polygon as usize] = area,
+        }
+        Ok(())
+    }
+
+    /// Returns the references to all polygons of all tiles whose bounds intersect `volume`.
+    ///
+    /// Off-mesh connections are not included.
+    pub fn polygons_in_volume(&self, volume: &Aabb3d) -> Vec<PolyRef> {
+        self.tiles()
+            .flat_map(|(tile_ref, tile)| {
+                let mesh = &tile.poly_mesh;
+                mesh.polygons()
+                    .enumerate()
+                    .filter_map(move |(polygon, indices)| {
+                        let (min, max) = indices
+                            .map(|i| mesh.world_vertex(i))
+                            .fold((Vec3::MAX, Vec3::MIN), |(min, max), v| {
+                                (min.min(v), max.max(v))
+                            });
+                        Aabb3d { min, max }
+                            .intersects(volume)
+                            .then(|| tile_ref.with_polygon(polygon as u16))
+                    })
+            })
+            .collect()
+    }
+
+    /// Sets the flags of all polygons returned by [`Self::polygons_in_volume`] and returns how many were changed,
+    /// e.g. to toggle all polygons of a door or a collapsing bridge together.
+    pub fn set_flags_in_volume(&mut self, volume: &Aabb3d, flags: u16) -> usize {
+        let polygons = self.polygons_in_volume(volume);
+        for &poly_ref in &polygons {
+            // The references were just created, so they are valid.
+            let _ = self.set_poly_flags(poly_ref, flags);
+        }
+        polygons.len()
+    }
+
+    /// Sets the area type of all polygons returned by [`Self::polygons_in_volume`] and returns how many were changed.
+    pub fn set_area_in_volume(&mut self, volume: &Aabb3d, area: AreaType) -> usize {
+        let polygons = self.polygons_in_volume(volume);
+        for &poly_ref in &polygons {
+            // The references were just created, so they are valid.
+            let _ = self.set_poly_area(poly_ref, area);
+        }
+        polygons.len()
+    }
+
+    fn tile_and_polygon_by_ref_mut(
+        &mut self,
+        poly_ref: PolyRef,
+    ) -> Result<(&mut NavmeshTile, u16), NavmeshError> {
+        let polygon = poly_ref.polygon();
+        self.slots
+            .get_mut(poly_ref.tile() as usize)
+            .filter(|slot| slot.salt == poly_ref.salt())
+            .and_then(|slot| slot.tile.as_mut())
+            .filter(|tile| {
+                (polygon as usize)
+                    < tile.poly_mesh.polygon_count() + tile.off_mesh_connections.len()
+            })
+            .map(|tile| (tile, polygon))
+            .ok_or(NavmeshError::InvalidPolyRef(poly_ref))
+    }
+
+    /// Creates a query against all tiles, including their detail meshes and off-mesh connections.
+    ///
+    /// The query numbers the polygons of all tiles consecutively and follows the links between the tiles,
+    /// so searches cross tile borders. It converts between its polygon indices and [`PolyRef`]s with
+    /// [`NavmeshQuery::poly_ref`] and [`NavmeshQuery::polygon_by_ref`], so references stay valid across queries
+    /// as long as their tile is not replaced or removed.
+    ///
+    /// Polygon indices are `u16`s, so tiles whose polygons and off-mesh connections do not fit into the
+    /// indices left by the tiles before them are left out. Use [`Self::query_in`] to query a part of larger navmeshes.
+    pub fn query(&self) -> NavmeshQuery<'_> {
+        self.query_where(|_| true)
+    }
+
+    /// Like [`Self::query`], but only includes the tiles between `min` and `max`, inclusive.
+    ///
+    /// Links to tiles outside of the range are treated as walls.
+    pub fn query_in(&self, min: TileCoord, max: TileCoord) -> NavmeshQuery<'_> {
+        self.query_where(|coord| {
+            (min.x..=max.x).contains(&coord.x) && (min.z..=max.z).contains(&coord.z)
+        })
+    }
+
+    fn query_where(&self, include: impl Fn(TileCoord) -> bool) -> NavmeshQuery<'_> {
+        NavmeshQuery::from_tiles(self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let tile = slot.tile.as_ref()?;
+            let tile_ref = PolyRef::new(slot.salt, index as u32, 0);
+            include(tile.coord).then_some((
+                tile_ref,
+                tile,
+                slot.links.as_slice(),
+                slot.connection_links.as_slice(),
+            ))
+        }))
+    }
+}
+
+/// Returns the side of a tile facing the tile on the given side.
+fn opposite_side(side: u16) -> u16 {
+    (side + 2) % 4
+}
+
+/// Finds the links from the portals of `tile` on the given side to the portals of `neighbor`,
+/// following Detour's `dtNavMesh::findConnectingPolys`.
+fn find_tile_links(
+    tile: &NavmeshTile,
+    side: u16,
+    neighbor: &NavmeshTile,
+    neighbor_ref: PolyRef,
+    walkable_climb: f32,
+) -> Vec<TileLink> {
+    /// The distance within which portals are considered to lie on the same tile border.
+    const BORDER_TOLERANCE: f32 = 0.01;
+    /// The minimum overlap of two portals along the tile border for them to be linked.
+    const MIN_OVERLAP: f32 = 0.01;
+
+    let neighbor_portals = portal_edges(neighbor, opposite_side(side));
+    let mut links = Vec::new();
+    for (polygon, edge, a, b) in portal_edges(tile, side) {
+        // Portals on the sides along x lie on a line of constant x and extend along z, and vice versa.
+        let along_z = side.is_multiple_of(2);
+        let border = if along_z { a.x } else { a.z };
+        let slab = |v: Vec3| {
+            if along_z {
+                Vec2::new(v.z, v.y)
+            } else {
+                Vec2::new(v.x, v.y)
+            }
+        };
+        let (start, end) = (slab(a), slab(b));
+        if start.x == end.x {
+            continue;
+        }
+        for &(other, _, c, d) in &neighbor_portals {
+            let other_border = if along_z { c.x } else { c.z };
+            if abs(border - other_border) > BORDER_TOLERANCE {
+                continue;
+            }
+            if !overlap_slabs(
+                (start, end),
+                (slab(c), slab(d)),
+                MIN_OVERLAP,
+                walkable_climb,
+            ) {
+                continue;
+            }
+            // Store the overlap as parameters along the edge.
+            let (min, max) = (
+                start.x.min(end.x).max(slab(c).x.min(slab(d).x)),
+                start.x.max(end.x).min(slab(c).x.max(slab(d).x)),
+            );
+            let to_t = |u: f32| ((u - start.x) / (end.x - start.x)).clamp(0.0, 1.0);
+            let (t0, t1) = (to_t(min), to_t(max));
+            links.push(TileLink {
+                polygon,
+                edge,
+                neighbor: neighbor_ref.with_polygon(other),
+                range: (t0.min(t1), t0.max(t1)),
+            });
+        }
+    }
+    links
+}
+
+/// Returns the edges of the polygons of `tile` that are portals to the given side,
+/// as `(polygon, edge, start, end)` in world space.
+fn portal_edges(tile: &NavmeshTile, side: u16) -> Vec<(u16, u8, Vec3, Vec3)> {
+    let mesh = &tile.poly_mesh;
+    let nvp = mesh.max_vertices_per_polygon as usize;
+    let mut edges = Vec::new();
+    let neighbors = mesh.polygon_neighbors.chunks_exact(nvp);
+    for (polygon, (indices, neighbors)) in mesh.polygons().zip(neighbors).enumerate() {
+        let indices: Vec<u16> = indices.collect();
+        for edge in 0..indices.len() {
+            if neighbors[edge] == 0x8000 | side {
+                edges.push((
+                    polygon as u16,
+                    edge as u8,
+                    mesh.world_vertex(indices[edge]),
+                    mesh.world_vertex(indices[(edge + 1) % indices.len()]),
+                ));
+            }
+        }
+    }
+    edges
+}
+
+/// Returns whether two portal edges overlap, given as the position along the tile border in `x` and the height in `y`.
+///
+/// The edges must overlap by more than `2 * min_overlap` along the border, and either cross each other or have
+/// end points within twice `walkable_climb` vertically, like Detour's `overlapSlabs`.
+fn overlap_slabs(a: (Vec2, Vec2), b: (Vec2, Vec2), min_overlap: f32, walkable_climb: f32) -> bool {
+    let sorted = |(p, q): (Vec2, Vec2)| if p.x <= q.x { (p, q) } else { (q, p) };
+    let ((a_min, a_max), (b_min, b_max)) = (sorted(a), sorted(b));
+
+    // Check for horizontal overlap.
+    // The segments are shrunk a little so that slabs which touch at their end points are not considered overlapping.
+    let min_x = a_min.x.max(b_min.x) + min_overlap;
+    let max_x = a_max.x.min(b_max.x) - min_overlap;
+    if min_x > max_x {
+        return false;
+    }
+
+    // Check vertical overlap.
+    let height_at = |(p, q): (Vec2, Vec2), x: f32| {
+        if q.x == p.x {
+            p.y
+        } else {
+            p.y + (q.y - p.y) * (x - p.x) / (q.x - p.x)
+        }
+    };
+    let d_min = height_at((b_min, b_max), min_x) - height_at((a_min, a_max), min_x);
+    let d_max = height_at((b_min, b_max), max_x) - height_at((a_min, a_max), max_x);
+
+    // Crossing segments always overlap.
+    if d_min * d_max < 0.0 {
+        return true;
+    }
+    // Check for overlap at endpoints.
+    let threshold = (walkable_climb * 2.0).squared();
+    d_min.squared() <= threshold || d_max.squared() <= threshold
+}
+
+/// Returns the index into [`NavmeshTile::off_mesh_connections`] if `polygon` references an off-mesh connection.
+fn off_mesh_index(tile: &NavmeshTile, polygon: u16) -> Option<usize> {
+    (polygon as usize).checked_sub(tile.poly_mesh.polygon_count())
+}
+
+/// Errors that can occur when modifying a [`TiledNavmesh`].
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum NavmeshError {
+    /// The polygon reference does not point to an existing polygon, or its tile has been replaced or removed since it was created.
+    #[error("Polygon reference {0:?} is not valid")]
+    InvalidPolyRef(PolyRef),
+}
+
+fn next_salt(salt: u16) -> u16 {
+    match salt.wrapping_add(1) {
+        0 => 1,
+        salt => salt,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DetailNavmesh, OffMeshConnection, PolygonNavmesh, QueryFilter, test_meshes};
+    use glam::U16Vec3;
+
+    fn tile(x: u16, polygon_count: usize) -> NavmeshTile {
+        let poly_mesh = PolygonNavmesh {
+            vertices: vec![U16Vec3::ZERO],
+            max_vertices_per_polygon: 3,
+            polygons: vec![0; polygon_count * 3],
+            polygon_neighbors: vec![PolygonNavmesh::NO_CONNECTION; polygon_count * 3],
+            flags: vec![0; polygon_count],
+            areas: vec![AreaType::NOT_WALKABLE; polygon_count],
+            ..Default::default()
+        };
+        NavmeshTile {
+            coord: TileCoord { x, z: 0 },
+            poly_mesh,
+            detail_mesh: DetailNavmesh::default(),
+            off_mesh_connections: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn replacing_a_tile_invalidates_its_references() {
+        let mut navmesh = TiledNavmesh::from_tiles([tile(0, 2), tile(1, 1)]);
+        let tile_ref = navmesh.tile_ref_at(TileCoord { x: 0, z: 0 }).unwrap();
+        let poly_ref = tile_ref.with_polygon(1);
+        assert!(navmesh.is_valid_poly_ref(poly_ref));
+        assert!(!navmesh.is_valid_poly_ref(tile_ref.with_polygon(2)));
+        assert!(!navmesh.is_valid_poly_ref(PolyRef::NULL));
+
+        let new_ref = navmesh.add_tile(tile(0, 2));
+        assert_eq!(new_ref.tile(), tile_ref.tile());
+        assert!(!navmesh.is_valid_poly_ref(poly_ref));
+        assert!(navmesh.is_valid_poly_ref(new_ref.with_polygon(1)));
+        assert_eq!(navmesh.tile_count(), 2);
+
+        let query = navmesh.query();
+        assert_eq!(query.polygon_by_ref(new_ref.with_polygon(1)), Ok(1));
+        assert_eq!(query.poly_ref(1), new_ref.with_polygon(1));
+        assert!(query.polygon_by_ref(poly_ref).is_err());
+    }
+
+    #[test]
+    fn removed_slots_are_reused_with_new_salt() {
+        let mut navmesh = TiledNavmesh::new();
+        let old_ref = navmesh.add_tile(tile(0, 1));
+        assert!(navmesh.remove_tile(TileCoord { x: 0, z: 0 }).is_some());
+        assert!(navmesh.tile_by_ref(old_ref).is_none());
+
+        let new_ref = navmesh.add_tile(tile(3, 1));
+        assert_eq!(new_ref.tile(), old_ref.tile());
+        assert_ne!(new_ref.salt(), old_ref.salt());
+        assert!(!navmesh.is_valid_poly_ref(old_ref));
+        assert_eq!(
+            navmesh.tile_by_ref(new_ref).unwrap().coord,
+            TileCoord { x: 3, z: 0 }
+        );
+    }
+
+    #[test]
+    fn overrides_flags_and_areas_at_runtime() {
+        let mut navmesh = TiledNavmesh::from_tiles([tile(0, 2)]);
+        let tile_ref = navmesh.tile_ref_at(TileCoord { x: 0, z: 0 }).unwrap();
+        let poly_ref = tile_ref.with_polygon(1);
+        navmesh.set_poly_flags(poly_ref, 4).unwrap();
+        navmesh.set_poly_area(poly_ref, AreaType(3)).unwrap();
+        assert_eq!(navmesh.poly_flags(poly_ref), Ok(4));
+        assert_eq!(navmesh.poly_area(poly_ref), Ok(AreaType(3)));
+        assert_eq!(navmesh.poly_flags(tile_ref), Ok(0));
+
+        let stale = tile_ref.with_polygon(2);
+        assert_eq!(
+            navmesh.set_poly_flags(stale, 1),
+            Err(NavmeshError::InvalidPolyRef(stale))
+        );
+
+        let everywhere = Aabb3d::new(Vec3::ZERO, Vec3::ONE);
+        assert_eq!(
+            navmesh.polygons_in_volume(&everywhere),
+            [tile_ref, poly_ref]
+        );
+        assert_eq!(navmesh.set_flags_in_volume(&everywhere, 8), 2);
+        assert_eq!(navmesh.poly_flags(tile_ref), Ok(8));
+        let elsewhere = Aabb3d::new(Vec3::splat(10.0), Vec3::ONE);
+        assert_eq!(navmesh.set_area_in_volume(&elsewhere, AreaType(5)), 0);
+    }
+
+    /// A 20x20 square at the origin next to a tile of two 10x10 squares along z.
+    fn uneven_tiles() -> (NavmeshTile, NavmeshTile) {
+        let mut large = test_meshes::grid_tile(TileCoord { x: 0, z: 0 }, 1, 1);
+        large.poly_mesh.cell_size = 2.0;
+        let mut small = test_meshes::grid_tile(TileCoord { x: 1, z: 0 }, 1, 2);
+        small.poly_mesh.aabb.min.x = 20.0;
+        (large, small)
+    }
+
+    #[test]
+    fn links_portals_of_neighboring_tiles() {
+        let (large, small) = uneven_tiles();
+        let mut navmesh = TiledNavmesh::from_tiles([large.clone(), small.clone()]);
+        let large_ref = navmesh.tile_ref_at(large.coord).unwrap();
+        let small_ref = navmesh.tile_ref_at(small.coord).unwrap();
+        // The +x edge of the large square borders both small squares.
+        assert_eq!(
+            navmesh.tile_links(large_ref),
+            Ok(vec![(1, small_ref), (1, small_ref.with_polygon(1))])
+        );
+        assert_eq!(
+            navmesh.tile_links(small_ref.with_polygon(1)),
+            Ok(vec![(3, large_ref)])
+        );
+
+        let query = navmesh.query();
+        assert_eq!(query.polygon_count(), 3);
+        let small_polygon = query.polygon_by_ref(small_ref).unwrap();
+        assert_eq!(
+            query.edge_neighbors(0, 1).collect::<Vec<_>>(),
+            [(small_polygon, (0.5, 1.0)), (small_polygon + 1, (0.0, 0.5))]
+        );
+        assert_eq!(
+            query.portal_points(0, small_polygon),
+            Some((Vec3::new(20.0, 0.0, 10.0), Vec3::new(20.0, 0.0, 0.0)))
+        );
+
+        // Replacing a tile links the new tile, and removing it drops the links leading into it.
+        let new_small_ref = navmesh.add_tile(small);
+        assert_eq!(
+            navmesh.tile_links(large_ref),
+            Ok(vec![(1, new_small_ref), (1, new_small_ref.with_polygon(1))])
+        );
+        navmesh.remove_tile(TileCoord { x: 1, z: 0 });
+        assert_eq!(navmesh.tile_links(large_ref), Ok(vec![]));
+        assert_eq!(navmesh.query().edge_neighbors(0, 1).count(), 0);
+    }
+
+    #[test]
+    fn paths_cross_tile_borders() {
+        let (large, small) = uneven_tiles();
+        let navmesh = TiledNavmesh::from_tiles([large, small]);
+        let query = navmesh.query();
+        let filter = QueryFilter::default();
+        // The straight line crosses the border above the lower small square, so the path bends at the portal's end.
+        let (start, end) = (Vec3::new(5.0, 0.0, 19.0), Vec3::new(25.0, 0.0, 9.0));
+        let (start_polygon, _) = query
+            .find_nearest_polygon(start, Vec3::ONE, &filter)
+            .unwrap();
+        let (end_polygon, _) = query.find_nearest_polygon(end, Vec3::ONE, &filter).unwrap();
+        let result = query
+            .find_path(start_polygon, end_polygon, start, end, &filter)
+            .unwrap();
+        assert_eq!(result.path, [0, end_polygon]);
+        assert!(!result.is_partial);
+
+        let path = query
+            .find_straight_path(start, end, &result.path, 8)
+            .unwrap();
+        let positions: Vec<_> = path.iter().map(|vertex| vertex.position).collect();
+        assert_eq!(positions, [start, Vec3::new(20.0, 0.0, 10.0), end]);
+
+        // The ray passes through the part of the portal shared with the upper small square.
+        let hit = query
+            .raycast(0, start, Vec3::new(25.0, 0.0, 15.0), &filter)
+            .unwrap();
+        assert!(!hit.is_hit());
+        assert_eq!(hit.path, [0, end_polygon + 1]);
+
+        // The portal is not a wall, but the outer edges of the small squares are.
+        let walls = query.polygon_wall_segments(0, &filter).unwrap();
+        assert!(walls.iter().all(|(a, b)| a.x != 20.0 || b.x != 20.0));
+        let wall = query
+            .find_distance_to_wall(0, Vec3::new(18.0, 0.0, 10.0), 10.0, &filter)
+            .unwrap()
+            .unwrap();
+        assert_eq!(wall.distance, 10.0);
+    }
+
+    #[test]
+    fn links_off_mesh_connections_into_other_tiles() {
+        // The tiles are not neighbors, so the connection is the only way between them.
+        let mut start_tile = test_meshes::grid_tile(TileCoord { x: 0, z: 0 }, 1, 1);
+        let end_tile = test_meshes::grid_tile(TileCoord { x: 2, z: 0 }, 1, 1);
+        let connection = OffMeshConnection {
+            start: Vec3::new(9.0, 0.0, 5.0),
+            end: Vec3::new(21.0, 0.0, 5.0),
+            bidirectional: false,
+            ..Default::default()
+        };
+        let snapped = connection
+            .snap_start(
+                &NavmeshQuery::new(&start_tile.poly_mesh),
+                1.0,
+                end_tile.coord,
+            )
+            .unwrap();
+        start_tile.off_mesh_connections.push(snapped);
+        let (start, end) = (Vec3::new(2.0, 0.0, 5.0), Vec3::new(28.0, 0.0, 5.0));
+
+        // The connection is linked no matter which tile is added first.
+        for tiles in [
+            [start_tile.clone(), end_tile.clone()],
+            [end_tile.clone(), start_tile.clone()],
+        ] {
+            let navmesh = TiledNavmesh::from_tiles(tiles);
+            let query = navmesh.query();
+            let start_polygon = query
+                .polygon_by_ref(navmesh.tile_ref_at(start_tile.coord).unwrap())
+                .unwrap();
+            let end_polygon = query
+                .polygon_by_ref(navmesh.tile_ref_at(end_tile.coord).unwrap())
+                .unwrap();
+            let result = query
+                .find_path(
+                    start_polygon,
+                    end_polygon,
+                    start,
+                    end,
+                    &QueryFilter::default(),
+                )
+                .unwrap();
+            assert!(!result.is_partial);
+            assert_eq!(result.path.len(), 3);
+
+            let path = query
+                .find_straight_path(start, end, &result.path, 8)
+                .unwrap();
+            let positions: Vec<_> = path.iter().map(|vertex| vertex.position).collect();
+            assert_eq!(positions, [start, connection.start, connection.end, end]);
+        }
+
+        // Removing the tile the connection leads into unlinks it.
+        let mut navmesh = TiledNavmesh::from_tiles([start_tile, end_tile.clone()]);
+        navmesh.remove_tile(end_tile.coord);
+        let query = navmesh.query();
+        let connection = query.off_mesh_connection(1).unwrap();
+        assert_eq!(connection.end_polygon, PolygonNavmesh::NO_INDEX);
+        assert_eq!(query.graph_neighbors(0, None).count(), 0);
+    }
+}
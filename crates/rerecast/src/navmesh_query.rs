@@ -0,0 +1,1646 @@
+//! Runtime queries against a generated [`PolygonNavmesh`] or the tiles of a [`TiledNavmesh`](crate::TiledNavmesh).
+//!
+//! This is the runtime counterpart to the build pipeline and is modelled after Detour's `dtNavMeshQuery`.
+//! Polygons are referenced by their index in the [`PolygonNavmesh`], the same indices used by
+//! [`PolygonNavmesh::polygon_neighbors`]. Queries created by [`TiledNavmesh::query`](crate::TiledNavmesh::query) number the polygons of all tiles
+//! one after the other, follow the links between the tiles, and convert these indices to and from stable [`PolyRef`]s.
+//! Off-mesh connections are referenced by the indices following the polygons.
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    vec::Vec,
+};
+use glam::{Vec3, Vec3Swizzles as _};
+use thiserror::Error;
+
+use crate::{
+    Aabb3d, AreaType, DetailNavmesh, NavmeshTile, PolyRef, PolygonFilter, PolygonNavmesh,
+    SnappedOffMeshConnection,
+    math::{
+        closest_height_point_triangle, distance_squared_between_point_and_segment_xz,
+        intersect_segment_polygon_xz, overlap_polygon_polygon_xz, point_in_polygon_xz,
+        random_point_in_convex_polygon, triangle_area_xz,
+    },
+    navmesh::{OffMeshConnectionLink, TileLink},
+    node_pool::{NodePool, NodeState, OpenList},
+    ops::{FloatPow as _, abs, sqrt},
+};
+
+/// The result of [`NavmeshQuery::raycast`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RaycastHit {
+    /// The hit parameter along the ray, i.e. the hit position is `start + (end - start) * t`.
+    ///
+    /// [`f32::MAX`] if the ray reached its end without hitting a wall.
+    pub t: f32,
+    /// The normal of the wall that was hit, pointing back towards the navmesh.
+    /// Zero if nothing was hit.
+    pub hit_normal: Vec3,
+    /// The index of the edge of the last visited polygon that was hit, if any.
+    pub hit_edge_index: Option<usize>,
+    /// The polygons visited by the ray, in order, starting with the start polygon.
+    pub path: Vec<u16>,
+}
+
+/// The result of [`NavmeshQuery::move_along_surface`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MoveAlongSurfaceResult {
+    /// The reachable position closest to the requested end position.
+    pub position: Vec3,
+    /// The polygons visited on the way from the start polygon to [`Self::position`], in order.
+    /// The last polygon contains [`Self::position`].
+    pub visited: Vec<u16>,
+}
+
+/// The result of [`NavmeshQuery::find_distance_to_wall`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WallHit {
+    /// The distance on the xz-plane from the search center to the nearest wall.
+    pub distance: f32,
+    /// The nearest position on the wall.
+    pub position: Vec3,
+    /// The normal of the wall, pointing back towards the navmesh.
+    pub normal: Vec3,
+}
+
+/// The result of the polygon searches [`NavmeshQuery::find_polygons_around_circle`],
+/// [`NavmeshQuery::find_polygons_around_shape`] and [`NavmeshQuery::find_local_neighbourhood`].
+///
+/// All vectors have the same length, and entry `i` of each describes the same polygon.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PolygonSearchResult {
+    /// The polygons found by the search, starting with the start polygon.
+    pub polygons: Vec<u16>,
+    /// The polygon each polygon was reached from. `None` for the start polygon.
+    pub parents: Vec<Option<u16>>,
+    /// The cost of the path from the search center to each polygon, according to the filter.
+    pub costs: Vec<f32>,
+}
+
+impl PolygonSearchResult {
+    fn push(&mut self, polygon: u16, parent: Option<u16>, cost: f32) {
+        self.polygons.push(polygon);
+        self.parents.push(parent);
+        self.costs.push(cost);
+    }
+}
+
+impl RaycastHit {
+    /// Returns whether the ray hit a wall before reaching its end.
+    #[inline]
+    pub fn is_hit(&self) -> bool {
+        self.t != f32::MAX
+    }
+}
+
+/// Provides the ability to perform pathfinding related queries against a [`PolygonNavmesh`] or the tiles of a [`TiledNavmesh`](crate::TiledNavmesh).
+///
+/// The query converts the polygon meshes into world space once on construction,
+/// so it is cheap to run many queries against the same instance.
+/// If a [`DetailNavmesh`] is provided, heights are taken from it instead of the coarse polygon vertices.
+#[derive(Debug, Clone)]
+pub struct NavmeshQuery<'a> {
+    /// The queried meshes, in the order of their polygon indices.
+    tiles: Vec<QueryTile<'a>>,
+    /// The index in `tiles` of the tile with each reference.
+    tile_lookup: BTreeMap<PolyRef, usize>,
+    /// World-space vertices of all polygons, grouped by polygon.
+    polygon_vertices: Vec<Vec3>,
+    /// Start of each polygon's vertices in `polygon_vertices`. Has one more entry than there are polygons.
+    polygon_offsets: Vec<u32>,
+    /// World-space bounds of each polygon.
+    polygon_bounds: Vec<Aabb3d>,
+    /// The neighbor in the same tile across each polygon edge, grouped like `polygon_vertices`.
+    neighbors: Vec<u16>,
+    /// The links across polygon edges into other tiles, sorted by polygon and edge.
+    tile_links: Vec<QueryLink>,
+    /// The off-mesh connections, referenced by the indices following the polygons.
+    /// Their polygons are stored as indices of this query.
+    off_mesh_connections: Vec<SnappedOffMeshConnection>,
+    /// The off-mesh connections that can be entered from each polygon, as `(polygon, connection)` sorted by polygon.
+    off_mesh_links: Vec<(u16, u16)>,
+}
+
+/// A mesh covered by a [`NavmeshQuery`].
+#[derive(Debug, Clone)]
+struct QueryTile<'a> {
+    mesh: &'a PolygonNavmesh,
+    detail_mesh: Option<&'a DetailNavmesh>,
+    /// The reference to the first polygon of the tile.
+    tile_ref: PolyRef,
+    /// The index of the first polygon of the tile in the query.
+    first_polygon: u16,
+    /// The index of the first off-mesh connection of the tile in the query.
+    first_connection: u16,
+    connection_count: u16,
+}
+
+/// A link across a polygon edge into another tile, see [`TileLink`].
+#[derive(Debug, Clone, Copy)]
+struct QueryLink {
+    polygon: u16,
+    edge: u8,
+    neighbor: u16,
+    range: (f32, f32),
+}
+
+impl<'a> NavmeshQuery<'a> {
+    /// The maximum number of polygons a single graph search visits, unless specified otherwise.
+    pub const DEFAULT_MAX_NODES: usize = 2048;
+
+    /// Creates a new query for the given polygon mesh.
+    pub fn new(mesh: &'a PolygonNavmesh) -> Self {
+        let mut query = Self::empty();
+        query.add_mesh(mesh, PolyRef::new(1, 0, 0));
+        query
+    }
+
+    fn empty() -> Self {
+        Self {
+            tiles: Vec::new(),
+            tile_lookup: BTreeMap::new(),
+            polygon_vertices: Vec::new(),
+            polygon_offsets: vec![0],
+            polygon_bounds: Vec::new(),
+            neighbors: Vec::new(),
+            tile_links: Vec::new(),
+            off_mesh_connections: Vec::new(),
+            off_mesh_links: Vec::new(),
+        }
+    }
+
+    /// Creates a query for the given tiles of a [`TiledNavmesh`](crate::TiledNavmesh), including the links between them.
+    ///
+    /// Tiles whose polygons and off-mesh connections do not fit into the remaining polygon indices are left out.
+    pub(crate) fn from_tiles(
+        tiles: impl IntoIterator<
+            Item = (
+                PolyRef,
+                &'a NavmeshTile,
+                &'a [TileLink],
+                &'a [OffMeshConnectionLink],
+            ),
+        >,
+    ) -> Self {
+        let mut query = Self::empty();
+        let mut index_count = 0;
+        let mut tiles_with_links = Vec::new();
+        for (tile_ref, tile, links, connection_links) in tiles {
+            let count = tile.poly_mesh.polygon_count() + tile.off_mesh_connections.len();
+            if index_count + count >= usize::from(PolygonNavmesh::NO_INDEX) {
+                continue;
+            }
+            index_count += count;
+            query.add_mesh(&tile.poly_mesh, tile_ref);
+            query.set_detail_mesh(query.tiles.len() - 1, &tile.detail_mesh);
+            tiles_with_links.push((tile, links, connection_links));
+        }
+        for (index, (tile, _, _)) in tiles_with_links.iter().enumerate() {
+            query.add_off_mesh_connections(index, &tile.off_mesh_connections);
+        }
+        for (index, (_, _, connection_links)) in tiles_with_links.iter().enumerate() {
+            let tile = &query.tiles[index];
+            let first_connection = usize::from(tile.first_connection) - query.polygon_count();
+            let connection_count = tile.connection_count;
+            for link in *connection_links {
+                // Connections into tiles that are not part of the query are never entered.
+                let Ok(end_polygon) = query.polygon_by_ref(link.end_polygon) else {
+                    continue;
+                };
+                if link.connection >= connection_count || !query.is_valid_polygon(end_polygon) {
+                    continue;
+                }
+                let connection = &mut query.off_mesh_connections
+                    [first_connection + usize::from(link.connection)];
+                connection.end_polygon = end_polygon;
+                connection.end = link.end;
+            }
+        }
+        for (index, (_, links, _)) in tiles_with_links.iter().enumerate() {
+            let first_polygon = query.tiles[index].first_polygon;
+            for link in *links {
+                // Links into tiles that are not part of the query are walls.
+                let Ok(neighbor) = query.polygon_by_ref(link.neighbor) else {
+                    continue;
+                };
+                if !query.is_valid_polygon(neighbor) {
+                    continue;
+                }
+                query.tile_links.push(QueryLink {
+                    polygon: first_polygon + link.polygon,
+                    edge: link.edge,
+                    neighbor,
+                    range: link.range,
+                });
+            }
+        }
+        query
+            .tile_links
+            .sort_unstable_by_key(|link| (link.polygon, link.edge));
+        query.link_off_mesh_connections();
+        query
+    }
+
+    /// Appends the polygons of a mesh.
+    fn add_mesh(&mut self, mesh: &'a PolygonNavmesh, tile_ref: PolyRef) {
+        let nvp = mesh.max_vertices_per_polygon as usize;
+        let polygon_count = mesh.polygon_count();
+        let first_polygon = self.polygon_count() as u16;
+        for (polygon, indices) in mesh.polygons().enumerate() {
+            let start = self.polygon_vertices.len();
+            self.polygon_vertices
+                .extend(indices.map(|i| mesh.world_vertex(i)));
+            let vertex_count = self.polygon_vertices.len() - start;
+            self.neighbors.extend(
+                mesh.polygon_neighbors[polygon * nvp..][..vertex_count]
+                    .iter()
+                    .map(|&neighbor| {
+                        // Tile portals are stored as `0x8000 | side`, which also covers `NO_CONNECTION`.
+                        if neighbor & 0x8000 == 0 && usize::from(neighbor) < polygon_count {
+                            first_polygon + neighbor
+                        } else {
+                            PolygonNavmesh::NO_CONNECTION
+                        }
+                    }),
+            );
+            self.polygon_offsets
+                .push(self.polygon_vertices.len() as u32);
+            self.polygon_bounds
+                .push(bounds_of(&self.polygon_vertices[start..]));
+        }
+        self.tile_lookup.insert(tile_ref, self.tiles.len());
+        self.tiles.push(QueryTile {
+            mesh,
+            detail_mesh: None,
+            tile_ref,
+            first_polygon,
+            first_connection: PolygonNavmesh::NO_INDEX,
+            connection_count: 0,
+        });
+    }
+
+    /// Uses the given detail mesh for height queries.
+    ///
+    /// The detail mesh must have been built from the polygon mesh passed to [`NavmeshQuery::new`].
+    pub fn with_detail_mesh(mut self, detail_mesh: &'a DetailNavmesh) -> Self {
+        self.set_detail_mesh(0, detail_mesh);
+        self
+    }
+
+    fn set_detail_mesh(&mut self, tile: usize, detail_mesh: &'a DetailNavmesh) {
+        let tile = &mut self.tiles[tile];
+        tile.detail_mesh = Some(detail_mesh);
+        let first_polygon = tile.first_polygon as usize;
+        for (polygon, sub_mesh) in detail_mesh
+            .meshes
+            .iter()
+            .enumerate()
+            .take(tile.mesh.polygon_count())
+        {
+            let vertices = &detail_mesh.vertices[sub_mesh.base_vertex_index as usize..]
+                [..sub_mesh.vertex_count as usize];
+            let bounds = &mut self.polygon_bounds[first_polygon + polygon];
+            for vertex in vertices {
+                bounds.min = bounds.min.min(*vertex);
+                bounds.max = bounds.max.max(*vertex);
+            }
+        }
+    }
+
+    /// Makes the given off-mesh connections traversable by [`NavmeshQuery::find_path`], usually the
+    /// [`NavmeshTile::off_mesh_connections`](crate::NavmeshTile::off_mesh_connections) of the queried tile.
+    ///
+    /// Connection `i` is referenced like a polygon with the index `polygon_count() + i`, so that paths through it can be
+    /// expressed as polygon paths. Connections that are not snapped to polygons of the mesh are never entered.
+    pub fn with_off_mesh_connections(mut self, connections: &[SnappedOffMeshConnection]) -> Self {
+        self.off_mesh_connections.clear();
+        self.add_off_mesh_connections(0, connections);
+        self.link_off_mesh_connections();
+        self
+    }
+
+    /// Appends the off-mesh connections of a tile, whose polygons are given as indices of the tile's mesh.
+    fn add_off_mesh_connections(&mut self, tile: usize, connections: &[SnappedOffMeshConnection]) {
+        let first_connection = self.polygon_count() + self.off_mesh_connections.len();
+        // Connections that cannot be referenced by a polygon index are ignored.
+        let connections = &connections[..connections
+            .len()
+            .min(usize::from(PolygonNavmesh::NO_INDEX) - first_connection)];
+        let tile = &mut self.tiles[tile];
+        tile.first_connection = first_connection as u16;
+        tile.connection_count = connections.len() as u16;
+        let first_polygon = tile.first_polygon;
+        let polygon_count = tile.mesh.polygon_count();
+        let to_query_index = |polygon: u16| {
+            if usize::from(polygon) < polygon_count {
+                first_polygon + polygon
+            } else {
+                PolygonNavmesh::NO_INDEX
+            }
+        };
+        self.off_mesh_connections
+            .extend(
+                connections
+                    .iter()
+                    .map(|connection| SnappedOffMeshConnection {
+                        start_polygon: to_query_index(connection.start_polygon),
+                        end_polygon: to_query_index(connection.end_polygon),
+                        ..connection.clone()
+                    }),
+            );
+    }
+
+    /// Collects the polygons from which each off-mesh connection can be entered.
+    ///
+    /// Connections are only entered if both of their end points lie on polygons of the query.
+    fn link_off_mesh_connections(&mut self) {
+        let polygon_count = self.polygon_count();
+        let is_valid = |polygon: u16| usize::from(polygon) < polygon_count;
+        self.off_mesh_links = self
+            .off_mesh_connections
+            .iter()
+            .enumerate()
+            .flat_map(|(i, connection)| {
+                let polygon = (polygon_count + i) as u16;
+                let end = connection
+                    .connection
+                    .bidirectional
+                    .then_some(connection.end_polygon);
+                [Some(connection.start_polygon), end]
+                    .into_iter()
+                    .flatten()
+                    .filter(move |&entry| {
+                        is_valid(entry) && is_valid(connection.exit_polygon(entry))
+                    })
+                    .map(move |entry| (entry, polygon))
+            })
+            .collect();
+        self.off_mesh_links.sort_unstable();
+    }
+
+    /// Sets the reference to the first polygon of the mesh passed to [`NavmeshQuery::new`],
+    /// which is used to convert between polygon indices and [`PolyRef`]s.
+    ///
+    /// Defaults to a reference to the first slot of a [`TiledNavmesh`](crate::TiledNavmesh) with a salt of `1`.
+    #[inline]
+    pub fn with_tile_ref(mut self, tile_ref: PolyRef) -> Self {
+        let tile_ref = tile_ref.with_polygon(0);
+        self.tile_lookup.remove(&self.tiles[0].tile_ref);
+        self.tile_lookup.insert(tile_ref, 0);
+        self.tiles[0].tile_ref = tile_ref;
+        self
+    }
+
+    /// Returns the stable reference to the given polygon or off-mesh connection,
+    /// or [`PolyRef::NULL`] if the index is not valid.
+    pub fn poly_ref(&self, polygon: u16) -> PolyRef {
+        if let Some((tile, index)) = self.tile_of(polygon) {
+            return tile.tile_ref.with_polygon(index as u16);
+        }
+        if !self.is_off_mesh_connection(polygon) {
+            return PolyRef::NULL;
+        }
+        // The tiles are ordered by their connections, too.
+        let tile = &self.tiles[self
+            .tiles
+            .partition_point(|tile| tile.first_connection <= polygon)
+            - 1];
+        tile.tile_ref
+            .with_polygon(tile.mesh.polygon_count() as u16 + (polygon - tile.first_connection))
+    }
+
+    /// Returns the polygon index of a reference created by this query or an earlier query of the same tiles.
+    /// The index may also reference an off-mesh connection.
+    ///
+    /// Fails if the reference belongs to a tile that is not part of the query, or the tile has been replaced since the reference was created.
+    pub fn polygon_by_ref(&self, poly_ref: PolyRef) -> Result<u16, NavmeshQueryError> {
+        let error = NavmeshQueryError::InvalidPolyRef(poly_ref);
+        let tile = &self.tiles[*self
+            .tile_lookup
+            .get(&poly_ref.with_polygon(0))
+            .ok_or(error.clone())?];
+        let index = poly_ref.polygon();
+        let polygon_count = tile.mesh.polygon_count() as u16;
+        if index < polygon_count {
+            Ok(tile.first_polygon + index)
+        } else if index - polygon_count < tile.connection_count {
+            Ok(tile.first_connection + (index - polygon_count))
+        } else {
+            Err(error)
+        }
+    }
+
+    /// Returns the tile containing `polygon` and the index of the polygon in the tile's mesh.
+    fn tile_of(&self, polygon: u16) -> Option<(&QueryTile<'a>, usize)> {
+        if !self.is_valid_polygon(polygon) {
+            return None;
+        }
+        let tile = &self.tiles[self
+            .tiles
+            .partition_point(|tile| tile.first_polygon <= polygon)
+            - 1];
+        Some((tile, usize::from(polygon - tile.first_polygon)))
+    }
+
+    /// The number of polygons that can be queried, not counting off-mesh connections.
+    #[inline]
+    pub fn polygon_count(&self) -> usize {
+        self.polygon_bounds.len()
+    }
+
+    /// Returns whether `polygon` references a polygon of the mesh.
+    #[inline]
+    pub fn is_valid_polygon(&self, polygon: u16) -> bool {
+        (polygon as usize) < self.polygon_count()
+    }
+
+    /// Returns the off-mesh connection referenced by `polygon`, if any.
+    ///
+    /// The polygons of the returned connection are indices of this query.
+    #[inline]
+    pub fn off_mesh_connection(&self, polygon: u16) -> Option<&SnappedOffMeshConnection> {
+        let index = (polygon as usize).checked_sub(self.polygon_count())?;
+        self.off_mesh_connections.get(index)
+    }
+
+    /// Returns whether `polygon` references an off-mesh connection.
+    #[inline]
+    pub fn is_off_mesh_connection(&self, polygon: u16) -> bool {
+        self.off_mesh_connection(polygon).is_some()
+    }
+
+    /// Returns the off-mesh connections together with the indices that reference them.
+    pub fn off_mesh_connections(
+        &self,
+    ) -> impl Iterator<Item = (u16, &SnappedOffMeshConnection)> + '_ {
+        let polygon_count = self.polygon_count();
+        self.off_mesh_connections
+            .iter()
+            .enumerate()
+            .map(move |(i, connection)| ((polygon_count + i) as u16, connection))
+    }
+
+    /// Returns the positions at which `connection` is entered and exited when coming from the polygon `from`,
+    /// or `None` if `connection` is not an off-mesh connection that can be entered from `from`.
+    pub fn off_mesh_connection_end_points(
+        &self,
+        from: u16,
+        connection: u16,
+    ) -> Option<(Vec3, Vec3)> {
+        self.off_mesh_connection(connection)?.end_points_from(from)
+    }
+
+    /// Returns whether `polygon` references a polygon of the mesh or an off-mesh connection.
+    #[inline]
+    pub(crate) fn is_valid_polygon_or_connection(&self, polygon: u16) -> bool {
+        (polygon as usize) < self.polygon_count() + self.off_mesh_connections.len()
+    }
+
+    /// Returns the polygons and off-mesh connections that can be entered from `polygon` when it was entered from `parent`.
+    pub(crate) fn graph_neighbors(
+        &self,
+        polygon: u16,
+        parent: Option<u16>,
+    ) -> impl Iterator<Item = u16> + '_ {
+        let edges = if self.is_valid_polygon(polygon) {
+            0..self.polygon_vertices(polygon).len()
+        } else {
+            0..0
+        };
+        let start = self.off_mesh_links.partition_point(|(p, _)| *p < polygon);
+        let links = self.off_mesh_links[start..]
+            .iter()
+            .take_while(move |(p, _)| *p == polygon)
+            .map(|(_, connection)| *connection);
+        // An off-mesh connection leads to the polygon at its other end.
+        let exit = self
+            .off_mesh_connection(polygon)
+            .zip(parent)
+            .map(|(connection, parent)| connection.exit_polygon(parent));
+        edges
+            .flat_map(move |edge| {
+                self.edge_neighbors(polygon, edge)
+                    .map(|(neighbor, _)| neighbor)
+            })
+            .chain(links)
+            .chain(exit)
+    }
+
+    /// Returns the flags and area type of a polygon or off-mesh connection.
+    #[inline]
+    fn flags_and_area(&self, polygon: u16) -> (u16, AreaType) {
+        if let Some(connection) = self.off_mesh_connection(polygon) {
+            (connection.connection.flags, connection.connection.area)
+        } else {
+            let (tile, index) = self.tile_of(polygon).expect("polygon should be valid");
+            (tile.mesh.flags[index], tile.mesh.areas[index])
+        }
+    }
+
+    /// Returns the world-space vertices of a polygon.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the polygon is not valid.
+    #[inline]
+    pub fn polygon_vertices(&self, polygon: u16) -> &[Vec3] {
+        let polygon = polygon as usize;
+        let start = self.polygon_offsets[polygon] as usize;
+        let end = self.polygon_offsets[polygon + 1] as usize;
+        &self.polygon_vertices[start..end]
+    }
+
+    /// Returns the world-space bounds of a polygon.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the polygon is not valid.
+    #[inline]
+    pub fn polygon_bounds(&self, polygon: u16) -> Aabb3d {
+        self.polygon_bounds[polygon as usize]
+    }
+
+    /// Returns the first polygon connected to `polygon` through the edge starting at vertex `edge`, if any.
+    ///
+    /// Solid edges and portals to tiles that are not part of the query have no neighbor. Off-mesh connections have no edges.
+    /// Use [`Self::edge_neighbors`] for portals to other tiles, which can lead to several polygons.
+    #[inline]
+    pub fn polygon_neighbor(&self, polygon: u16, edge: usize) -> Option<u16> {
+        self.edge_neighbors(polygon, edge)
+            .next()
+            .map(|(neighbor, _)| neighbor)
+    }
+
+    /// Returns the polygons connected to `polygon` through the edge starting at vertex `edge`, together with
+    /// the part of the edge each of them shares, as parameters from the start to the end vertex of the edge.
+    ///
+    /// Edges inside a tile lead to at most one polygon, which shares the whole edge.
+    /// Portals to another tile lead to every polygon of the neighboring tile they overlap.
+    pub fn edge_neighbors(
+        &self,
+        polygon: u16,
+        edge: usize,
+    ) -> impl Iterator<Item = (u16, (f32, f32))> + '_ {
+        let neighbor = self
+            .is_valid_polygon(polygon)
+            .then(|| self.neighbors[self.polygon_offsets[polygon as usize] as usize + edge])
+            .filter(|neighbor| *neighbor != PolygonNavmesh::NO_CONNECTION)
+            .map(|neighbor| (neighbor, (0.0, 1.0)));
+        let start = self
+            .tile_links
+            .partition_point(|link| (link.polygon, usize::from(link.edge)) < (polygon, edge));
+        let links = self.tile_links[start..]
+            .iter()
+            .take_while(move |link| link.polygon == polygon && usize::from(link.edge) == edge)
+            .map(|link| (link.neighbor, link.range));
+        neighbor.into_iter().chain(links)
+    }
+
+    /// Returns the world-space end points of the part of the edge of `polygon` starting at vertex `edge`
+    /// that is shared with a neighbor, given as the range returned by [`Self::edge_neighbors`].
+    fn portal_on_edge(
+        &self,
+        polygon: u16,
+        edge: usize,
+        (t_min, t_max): (f32, f32),
+    ) -> (Vec3, Vec3) {
+        let vertices = self.polygon_vertices(polygon);
+        let a = vertices[edge];
+        let b = vertices[(edge + 1) % vertices.len()];
+        let point = |t: f32| if t >= 1.0 { b } else { a.lerp(b, t) };
+        (point(t_min), point(t_max))
+    }
+
+    /// Returns the world-space end points of the edge that connects `from` to `to`,
+    /// or `None` if the polygons are not neighbors.
+    ///
+    /// For portals between tiles, only the part of the edge that is shared with `to` is returned.
+    /// If either polygon is an off-mesh connection, both points are the position at which the connection is entered or exited.
+    pub fn portal_points(&self, from: u16, to: u16) -> Option<(Vec3, Vec3)> {
+        if let Some(connection) = self.off_mesh_connection(to) {
+            let (entry, _) = connection.end_points_from(from)?;
+            return Some((entry, entry));
+        }
+        if let Some(connection) = self.off_mesh_connection(from) {
+            let exit = connection.exit_point(to)?;
+            return Some((exit, exit));
+        }
+        if !self.is_valid_polygon(from) {
+            return None;
+        }
+        (0..self.polygon_vertices(from).len()).find_map(|edge| {
+            let (_, range) = self
+                .edge_neighbors(from, edge)
+                .find(|(neighbor, _)| *neighbor == to)?;
+            Some(self.portal_on_edge(from, edge, range))
+        })
+    }
+
+    /// Returns whether the given polygon or off-mesh connection passes the filter.
+    #[inline]
+    pub fn passes_filter(&self, polygon: u16, filter: &(impl PolygonFilter + ?Sized)) -> bool {
+        let (flags, area) = self.flags_and_area(polygon);
+        filter.pass_filter(polygon, flags, area)
+    }
+
+    /// Returns the cost of moving from `from` to `to` across the given polygon or off-mesh connection, according to the filter.
+    #[inline]
+    pub fn traversal_cost(
+        &self,
+        polygon: u16,
+        from: Vec3,
+        to: Vec3,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> f32 {
+        let (flags, area) = self.flags_and_area(polygon);
+        filter.cost(from, to, polygon, flags, area)
+    }
+
+    /// Returns the height of the polygon at the given position on the xz-plane,
+    /// or `None` if the position is outside of the polygon.
+    pub fn polygon_height(&self, polygon: u16, position: Vec3) -> Option<f32> {
+        if let Some((tile, index)) = self.tile_of(polygon)
+            && let Some(detail_mesh) = tile.detail_mesh
+            && let Some(sub_mesh) = detail_mesh.meshes.get(index)
+        {
+            let vertices = &detail_mesh.vertices[sub_mesh.base_vertex_index as usize..]
+                [..sub_mesh.vertex_count as usize];
+            let triangles = &detail_mesh.triangles[sub_mesh.base_triangle_index as usize..]
+                [..sub_mesh.triangle_count as usize];
+            return triangles.iter().find_map(|triangle| {
+                closest_height_point_triangle(
+                    position,
+                    vertices[triangle[0] as usize],
+                    vertices[triangle[1] as usize],
+                    vertices[triangle[2] as usize],
+                )
+            });
+        }
+        let vertices = self.polygon_vertices(polygon);
+        (1..vertices.len().saturating_sub(1)).find_map(|i| {
+            closest_height_point_triangle(position, vertices[0], vertices[i], vertices[i + 1])
+        })
+    }
+
+    /// Finds the point on the polygon that is closest to `position`.
+    ///
+    /// Returns the closest point and whether `position` lies above or below the polygon on the xz-plane.
+    pub fn closest_point_on_polygon(&self, polygon: u16, position: Vec3) -> (Vec3, bool) {
+        if let Some(height) = self.polygon_height(polygon, position) {
+            return (Vec3::new(position.x, height, position.z), true);
+        }
+        let vertices = self.polygon_vertices(polygon);
+        let mut closest = vertices[0];
+        let mut closest_distance = f32::MAX;
+        for i in 0..vertices.len() {
+            let a = vertices[i];
+            let b = vertices[(i + 1) % vertices.len()];
+            let (distance, t) = distance_squared_between_point_and_segment_xz(position, (a, b));
+            if distance < closest_distance {
+                closest_distance = distance;
+                closest = a.lerp(b, t);
+            }
+        }
+        (closest, false)
+    }
+
+    /// Finds the polygon nearest to `center` within the box described by `half_extents`.
+    ///
+    /// Returns the polygon together with the closest point on it, or `None` if no polygon
+    /// passing the filter overlaps the search box.
+    pub fn find_nearest_polygon(
+        &self,
+        center: Vec3,
+        half_extents: Vec3,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Option<(u16, Vec3)> {
+        let search_bounds = Aabb3d::new(center, half_extents);
+        let mut nearest = None;
+        let mut nearest_distance = f32::MAX;
+        for polygon in 0..self.polygon_count() as u16 {
+            if !self.polygon_bounds(polygon).intersects(&search_bounds)
+                || !self.passes_filter(polygon, filter)
+            {
+                continue;
+            }
+            let (closest, is_over_polygon) = self.closest_point_on_polygon(polygon, center);
+            let diff = center - closest;
+            // If the point is directly over a polygon, only the height difference matters.
+            let distance = if is_over_polygon {
+                diff.y * diff.y
+            } else {
+                diff.length_squared()
+            };
+            if distance < nearest_distance {
+                nearest_distance = distance;
+                nearest = Some((polygon, closest));
+            }
+        }
+        nearest
+    }
+
+    /// Casts a ray along the surface of the navmesh from `start` towards `end`.
+    ///
+    /// The ray walks polygon edges starting at `start_polygon`, which should contain `start`.
+    /// It stops at the first edge without an accessible neighbor, so this is a cheap way to check whether
+    /// an agent can move in a straight line between two points, e.g. to skip pathfinding or to smooth paths.
+    ///
+    /// The check is done on the xz-plane, so the ray may pass under or over obstacles as long as
+    /// the polygons on the way are connected.
+    pub fn raycast(
+        &self,
+        start_polygon: u16,
+        start: Vec3,
+        end: Vec3,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Result<RaycastHit, NavmeshQueryError> {
+        if !self.is_valid_polygon(start_polygon) {
+            return Err(NavmeshQueryError::InvalidPolygon(start_polygon));
+        }
+        if !start.is_finite() || !end.is_finite() {
+            return Err(NavmeshQueryError::InvalidPosition);
+        }
+
+        let mut hit = RaycastHit::default();
+        let mut current = start_polygon;
+        loop {
+            let vertices = self.polygon_vertices(current);
+            let Some(intersection) = intersect_segment_polygon_xz(start, end, vertices) else {
+                // Could not hit the polygon, keep the old t and report hit.
+                return Ok(hit);
+            };
+            hit.hit_edge_index = intersection.edge_max;
+            hit.t = hit.t.max(intersection.t_max);
+            hit.path.push(current);
+
+            // Ray end is completely inside the polygon.
+            let Some(edge) = intersection.edge_max else {
+                hit.t = f32::MAX;
+                return Ok(hit);
+            };
+
+            // Portals to other tiles can lead to several polygons, so find the one the ray passes into.
+            let a = vertices[edge];
+            let b = vertices[(edge + 1) % vertices.len()];
+            let edge_delta = (b - a).xz();
+            let hit_position = start.lerp(end, intersection.t_max).xz();
+            let s = (hit_position - a.xz()).dot(edge_delta) / edge_delta.length_squared();
+            let next = self
+                .edge_neighbors(current, edge)
+                .find(|(neighbor, (t_min, t_max))| {
+                    let covers_edge = *t_min <= 0.0 && *t_max >= 1.0;
+                    (covers_edge || (*t_min..=*t_max).contains(&s))
+                        && self.passes_filter(*neighbor, filter)
+                })
+                .map(|(neighbor, _)| neighbor);
+            let Some(next) = next else {
+                // No neighbor, we hit a wall.
+                hit.hit_normal = Vec3::new(edge_delta.y, 0.0, -edge_delta.x).normalize_or_zero();
+                return Ok(hit);
+            };
+            current = next;
+        }
+    }
+
+    /// Moves from `start` towards `end`, constrained to the navmesh surface.
+    ///
+    /// If `end` cannot be reached in a straight line, the movement slides along the walls that are in the way
+    /// and the closest reachable position is returned instead. This is meant for small, per-frame displacements,
+    /// e.g. to keep a player or agent on the navmesh. Only polygons that lie within the circle spanned by `start`
+    /// and `end` are searched.
+    ///
+    /// The height of the returned position is snapped to the surface of the last visited polygon.
+    pub fn move_along_surface(
+        &self,
+        start_polygon: u16,
+        start: Vec3,
+        end: Vec3,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Result<MoveAlongSurfaceResult, NavmeshQueryError> {
+        const MAX_STACK: usize = 48;
+
+        if !self.is_valid_polygon(start_polygon) {
+            return Err(NavmeshQueryError::InvalidPolygon(start_polygon));
+        }
+        if !start.is_finite() || !end.is_finite() {
+            return Err(NavmeshQueryError::InvalidPosition);
+        }
+
+        // Visited polygons and the index of the node they were reached from.
+        let mut nodes: Vec<(u16, Option<usize>)> = vec![(start_polygon, None)];
+        let mut stack = VecDeque::with_capacity(MAX_STACK);
+        stack.push_back(0);
+
+        let mut best_position = start;
+        let mut best_distance = f32::MAX;
+        let mut best_node = 0;
+
+        // Search constraints
+        let search_position = start.lerp(end, 0.5);
+        let search_radius_squared = (start.distance(end) / 2.0 + 0.001).squared();
+
+        while let Some(current_node) = stack.pop_front() {
+            let current = nodes[current_node].0;
+            let vertices = self.polygon_vertices(current);
+
+            // If target is inside the polygon, stop search.
+            if point_in_polygon_xz(end, vertices) {
+                best_node = current_node;
+                best_position = end;
+                break;
+            }
+
+            // Find wall edges and find nearest point inside the walls.
+            for edge in 0..vertices.len() {
+                let a = vertices[edge];
+                let b = vertices[(edge + 1) % vertices.len()];
+                let mut neighbors = self
+                    .edge_neighbors(current, edge)
+                    .filter(|(neighbor, _)| self.passes_filter(*neighbor, filter))
+                    .peekable();
+                if neighbors.peek().is_none() {
+                    // Wall edge, calc distance.
+                    let (distance, t) = distance_squared_between_point_and_segment_xz(end, (a, b));
+                    if distance < best_distance {
+                        best_position = a.lerp(b, t);
+                        best_distance = distance;
+                        best_node = current_node;
+                    }
+                    continue;
+                }
+                for (neighbor, range) in neighbors {
+                    if nodes.iter().any(|(polygon, _)| *polygon == neighbor) {
+                        continue;
+                    }
+                    // Skip the link if it is too far from search constraint.
+                    let portal = self.portal_on_edge(current, edge, range);
+                    let (distance, _) =
+                        distance_squared_between_point_and_segment_xz(search_position, portal);
+                    if distance > search_radius_squared {
+                        continue;
+                    }
+                    if stack.len() < MAX_STACK {
+                        nodes.push((neighbor, Some(current_node)));
+                        stack.push_back(nodes.len() - 1);
+                    }
+                }
+            }
+        }
+
+        let mut visited = Vec::new();
+        let mut node = Some(best_node);
+        while let Some(index) = node {
+            visited.push(nodes[index].0);
+            node = nodes[index].1;
+        }
+        visited.reverse();
+
+        let last = visited[visited.len() - 1];
+        if let Some(height) = self.polygon_height(last, best_position) {
+            best_position.y = height;
+        }
+        Ok(MoveAlongSurfaceResult {
+            position: best_position,
+            visited,
+        })
+    }
+
+    /// Finds the distance from `center` to the nearest wall of the navmesh.
+    ///
+    /// Walls are polygon edges without an accessible neighbor, i.e. [`PolygonNavmesh::NO_CONNECTION`] edges
+    /// and edges to polygons rejected by the filter. The search expands from `start_polygon`, which should contain `center`,
+    /// to all polygons reachable within `max_radius`.
+    ///
+    /// Returns `None` if there is no wall within `max_radius`.
+    pub fn find_distance_to_wall(
+        &self,
+        start_polygon: u16,
+        center: Vec3,
+        max_radius: f32,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Result<Option<WallHit>, NavmeshQueryError> {
+        if !self.is_valid_polygon(start_polygon) {
+            return Err(NavmeshQueryError::InvalidPolygon(start_polygon));
+        }
+        if !center.is_finite() {
+            return Err(NavmeshQueryError::InvalidPosition);
+        }
+        if !(max_radius.is_finite() && max_radius >= 0.0) {
+            return Err(NavmeshQueryError::InvalidRadius(max_radius));
+        }
+
+        let mut pool = NodePool::new(Self::DEFAULT_MAX_NODES);
+        let mut open_list = OpenList::default();
+        let start_node = pool.get_or_insert(start_polygon).unwrap();
+        pool[start_node].position = center;
+        pool[start_node].state = NodeState::Open;
+        open_list.push(&pool, start_node);
+
+        let mut radius_squared = max_radius.squared();
+        let mut hit: Option<(Vec3, Vec3, Vec3)> = None;
+
+        while let Some(best_node) = open_list.pop(&pool) {
+            pool[best_node].state = NodeState::Closed;
+            let best = pool[best_node].polygon;
+            let parent = pool[best_node].parent.map(|parent| pool[parent].polygon);
+            let vertices = self.polygon_vertices(best);
+
+            for edge in 0..vertices.len() {
+                let a = vertices[edge];
+                let b = vertices[(edge + 1) % vertices.len()];
+                let mut neighbors = self
+                    .edge_neighbors(best, edge)
+                    .filter(|(neighbor, _)| self.passes_filter(*neighbor, filter))
+                    .peekable();
+
+                // Hit test walls.
+                if neighbors.peek().is_none() {
+                    let (distance, t) =
+                        distance_squared_between_point_and_segment_xz(center, (a, b));
+                    if distance <= radius_squared {
+                        radius_squared = distance;
+                        hit = Some((a.lerp(b, t), a, b));
+                    }
+                    continue;
+                }
+                for (neighbor, range) in neighbors {
+                    if Some(neighbor) == parent {
+                        continue;
+                    }
+
+                    // Skip the neighbor if the portal is out of range.
+                    let (a, b) = self.portal_on_edge(best, edge, range);
+                    let (distance, _) =
+                        distance_squared_between_point_and_segment_xz(center, (a, b));
+                    if distance > radius_squared {
+                        continue;
+                    }
+
+                    let Some(neighbor_node) = pool.get_or_insert(neighbor) else {
+                        continue;
+                    };
+                    if pool[neighbor_node].state == NodeState::Closed {
+                        continue;
+                    }
+                    // Cost
+                    if pool[neighbor_node].state == NodeState::New {
+                        pool[neighbor_node].position = a.lerp(b, 0.5);
+                    }
+                    let total = pool[best_node].total
+                        + pool[best_node]
+                            .position
+                            .distance(pool[neighbor_node].position);
+
+                    // The node is already in open list and the new result is worse, skip.
+                    if pool[neighbor_node].state == NodeState::Open
+                        && total >= pool[neighbor_node].total
+                    {
+                        continue;
+                    }
+                    let node = &mut pool[neighbor_node];
+                    node.parent = Some(best_node);
+                    node.total = total;
+                    node.state = NodeState::Open;
+                    open_list.push(&pool, neighbor_node);
+                }
+            }
+        }
+
+        Ok(hit.map(|(position, a, b)| {
+            let tangent = b - a;
+            WallHit {
+                distance: sqrt(radius_squared),
+                position,
+                normal: Vec3::new(tangent.z, 0.0, -tangent.x).normalize_or_zero(),
+            }
+        }))
+    }
+
+    /// Finds the polygons along the navigation graph that touch the circle around `center`.
+    ///
+    /// The search expands from `start_polygon` in order of path cost, like Dijkstra's algorithm,
+    /// so polygons that overlap the circle but are not connected to the start polygon through
+    /// other polygons touching the circle are not found.
+    pub fn find_polygons_around_circle(
+        &self,
+        start_polygon: u16,
+        center: Vec3,
+        radius: f32,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Result<PolygonSearchResult, NavmeshQueryError> {
+        if !(radius.is_finite() && radius >= 0.0) {
+            return Err(NavmeshQueryError::InvalidRadius(radius));
+        }
+        let radius_squared = radius.squared();
+        self.find_polygons_around(start_polygon, center, filter, |a, b| {
+            // If the circle is not touching the next polygon, skip it.
+            let (distance, _) = distance_squared_between_point_and_segment_xz(center, (a, b));
+            distance <= radius_squared
+        })
+    }
+
+    /// Finds the polygons along the navigation graph that touch the convex polygon `shape`.
+    ///
+    /// The search starts at the centroid of the shape and expands from `start_polygon` in order of path cost,
+    /// like Dijkstra's algorithm.
+    pub fn find_polygons_around_shape(
+        &self,
+        start_polygon: u16,
+        shape: &[Vec3],
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Result<PolygonSearchResult, NavmeshQueryError> {
+        if shape.is_empty() {
+            return Err(NavmeshQueryError::InvalidShape);
+        }
+        let center = shape.iter().sum::<Vec3>() / shape.len() as f32;
+        self.find_polygons_around(start_polygon, center, filter, |a, b| {
+            // If the shape is not touching the next polygon, skip it.
+            intersect_segment_polygon_xz(a, b, shape)
+                .is_some_and(|hit| hit.t_min <= 1.0 && hit.t_max >= 0.0)
+        })
+    }
+
+    /// Dijkstra search from `start_polygon` through all portals accepted by `touches_portal`.
+    fn find_polygons_around(
+        &self,
+        start_polygon: u16,
+        center: Vec3,
+        filter: &(impl PolygonFilter + ?Sized),
+        touches_portal: impl Fn(Vec3, Vec3) -> bool,
+    ) -> Result<PolygonSearchResult, NavmeshQueryError> {
+        if !self.is_valid_polygon(start_polygon) {
+            return Err(NavmeshQueryError::InvalidPolygon(start_polygon));
+        }
+        if !center.is_finite() {
+            return Err(NavmeshQueryError::InvalidPosition);
+        }
+
+        let mut pool = NodePool::new(Self::DEFAULT_MAX_NODES);
+        let mut open_list = OpenList::default();
+        let start_node = pool.get_or_insert(start_polygon).unwrap();
+        pool[start_node].position = center;
+        pool[start_node].state = NodeState::Open;
+        open_list.push(&pool, start_node);
+
+        let mut result = PolygonSearchResult::default();
+        while let Some(best_node) = open_list.pop(&pool) {
+            pool[best_node].state = NodeState::Closed;
+            let best = pool[best_node].polygon;
+            let parent = pool[best_node].parent.map(|parent| pool[parent].polygon);
+            result.push(best, parent, pool[best_node].total);
+
+            for edge in 0..self.polygon_vertices(best).len() {
+                for (neighbor, range) in self.edge_neighbors(best, edge) {
+                    if Some(neighbor) == parent {
+                        continue;
+                    }
+                    let (a, b) = self.portal_on_edge(best, edge, range);
+                    if !touches_portal(a, b) || !self.passes_filter(neighbor, filter) {
+                        continue;
+                    }
+
+                    let Some(neighbor_node) = pool.get_or_insert(neighbor) else {
+                        continue;
+                    };
+                    if pool[neighbor_node].state == NodeState::Closed {
+                        continue;
+                    }
+                    // Cost
+                    if pool[neighbor_node].state == NodeState::New {
+                        pool[neighbor_node].position = a.lerp(b, 0.5);
+                    }
+                    let total = pool[best_node].total
+                        + self.traversal_cost(
+                            best,
+                            pool[best_node].position,
+                            pool[neighbor_node].position,
+                            filter,
+                        );
+
+                    // The node is already in open list and the new result is worse, skip.
+                    if pool[neighbor_node].state == NodeState::Open
+                        && total >= pool[neighbor_node].total
+                    {
+                        continue;
+                    }
+                    let node = &mut pool[neighbor_node];
+                    node.parent = Some(best_node);
+                    node.total = total;
+                    node.state = NodeState::Open;
+                    open_list.push(&pool, neighbor_node);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Finds the non-overlapping polygons around `center` that are connected to `start_polygon`.
+    ///
+    /// The search is a breadth-first search limited to polygons that touch the circle around `center`.
+    /// Polygons that overlap an already found polygon without being connected to it, e.g. a floor above or below,
+    /// are skipped. This makes the result suitable for building the local boundary of an agent.
+    pub fn find_local_neighbourhood(
+        &self,
+        start_polygon: u16,
+        center: Vec3,
+        radius: f32,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Result<PolygonSearchResult, NavmeshQueryError> {
+        const MAX_STACK: usize = 48;
+
+        if !self.is_valid_polygon(start_polygon) {
+            return Err(NavmeshQueryError::InvalidPolygon(start_polygon));
+        }
+        if !center.is_finite() {
+            return Err(NavmeshQueryError::InvalidPosition);
+        }
+        if !(radius.is_finite() && radius >= 0.0) {
+            return Err(NavmeshQueryError::InvalidRadius(radius));
+        }
+        let radius_squared = radius.squared();
+
+        let mut result = PolygonSearchResult::default();
+        // Positions at which the search entered each found polygon.
+        let mut positions = vec![center];
+        let mut visited = vec![start_polygon];
+        let mut stack = VecDeque::with_capacity(MAX_STACK);
+        result.push(start_polygon, None, 0.0);
+        stack.push_back(0);
+
+        while let Some(current) = stack.pop_front() {
+            let current_polygon = result.polygons[current];
+            for edge in 0..self.polygon_vertices(current_polygon).len() {
+                for (neighbor, range) in self.edge_neighbors(current_polygon, edge) {
+                    // Skip if the neighbor was already visited or is not accessible.
+                    if visited.contains(&neighbor) || !self.passes_filter(neighbor, filter) {
+                        continue;
+                    }
+                    // Skip if cannot allocate more nodes.
+                    if visited.len() >= Self::DEFAULT_MAX_NODES {
+                        continue;
+                    }
+
+                    // If the circle is not touching the next polygon, skip it.
+                    let (a, b) = self.portal_on_edge(current_polygon, edge, range);
+                    let (distance, _) =
+                        distance_squared_between_point_and_segment_xz(center, (a, b));
+                    if distance > radius_squared {
+                        continue;
+                    }
+
+                    // Mark the neighbor visited before the overlap test so that we will not visit it again if the test fails.
+                    visited.push(neighbor);
+
+                    // Check that the polygon does not collide with existing polygons.
+                    let neighbor_vertices = self.polygon_vertices(neighbor);
+                    let overlaps = result.polygons.iter().any(|&other| {
+                        // Connected polygons do not overlap.
+                        let connected = (0..neighbor_vertices.len()).any(|edge| {
+                            self.edge_neighbors(neighbor, edge)
+                                .any(|(polygon, _)| polygon == other)
+                        });
+                        !connected
+                            && overlap_polygon_polygon_xz(
+                                neighbor_vertices,
+                                self.polygon_vertices(other),
+                            )
+                    });
+                    if overlaps {
+                        continue;
+                    }
+
+                    let position = a.lerp(b, 0.5);
+                    let cost = result.costs[current]
+                        + self.traversal_cost(
+                            current_polygon,
+                            positions[current],
+                            position,
+                            filter,
+                        );
+                    result.push(neighbor, Some(current_polygon), cost);
+                    positions.push(position);
+                    if stack.len() < MAX_STACK {
+                        stack.push_back(result.polygons.len() - 1);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Returns the solid edges of a polygon, i.e. the edges without a neighbor that passes the filter.
+    ///
+    /// Together with [`NavmeshQuery::find_local_neighbourhood`], this collects the walls around an agent.
+    pub fn polygon_wall_segments(
+        &self,
+        polygon: u16,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Result<Vec<(Vec3, Vec3)>, NavmeshQueryError> {
+        if !self.is_valid_polygon(polygon) {
+            return Err(NavmeshQueryError::InvalidPolygon(polygon));
+        }
+        let vertices = self.polygon_vertices(polygon);
+        let mut segments = Vec::new();
+        for edge in 0..vertices.len() {
+            let mut ranges: Vec<_> = self
+                .edge_neighbors(polygon, edge)
+                .filter(|(neighbor, _)| self.passes_filter(*neighbor, filter))
+                .map(|(_, range)| range)
+                .collect();
+            if ranges.is_empty() {
+                segments.push(self.portal_on_edge(polygon, edge, (0.0, 1.0)));
+                continue;
+            }
+            // The parts of the edge that are not shared with an accessible neighbor are walls.
+            // Gaps shorter than the tolerance used for linking tiles are ignored.
+            ranges.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+            let edge_length = vertices[edge]
+                .xz()
+                .distance(vertices[(edge + 1) % vertices.len()].xz());
+            let min_gap = 0.01 / edge_length;
+            let mut covered = 0.0;
+            for (t_min, t_max) in ranges.into_iter().chain([(1.0, 1.0)]) {
+                if t_min - covered > min_gap {
+                    segments.push(self.portal_on_edge(polygon, edge, (covered, t_min)));
+                }
+                covered = f32::max(covered, t_max);
+            }
+        }
+        Ok(segments)
+    }
+
+    /// Returns the area of a polygon on the xz-plane.
+    pub fn polygon_area(&self, polygon: u16) -> f32 {
+        let vertices = self.polygon_vertices(polygon);
+        (2..vertices.len())
+            .map(|i| abs(triangle_area_xz(vertices[0], vertices[i - 1], vertices[i])))
+            .sum::<f32>()
+            * 0.5
+    }
+
+    /// Returns a random point on the navmesh.
+    ///
+    /// The point is uniformly distributed over the area of all polygons that pass the filter,
+    /// and its height is taken from the detail mesh if there is one.
+    ///
+    /// `rng` must return uniformly distributed numbers in the range `[0, 1)`.
+    /// Passing a seeded generator makes the result deterministic, e.g. for replays.
+    ///
+    /// Returns `None` if no polygon passes the filter.
+    pub fn find_random_point(
+        &self,
+        filter: &(impl PolygonFilter + ?Sized),
+        mut rng: impl FnMut() -> f32,
+    ) -> Option<(u16, Vec3)> {
+        // Randomly pick one polygon weighted by polygon area.
+        let mut area_sum = 0.0;
+        let mut chosen = None;
+        for polygon in 0..self.polygon_count() as u16 {
+            if !self.passes_filter(polygon, filter) {
+                continue;
+            }
+            let area = self.polygon_area(polygon);
+            area_sum += area;
+            // Choose random polygon weighted by area, using reservoir sampling.
+            if rng() * area_sum <= area {
+                chosen = Some(polygon);
+            }
+        }
+        let polygon = chosen?;
+        Some((polygon, self.random_point_in_polygon(polygon, &mut rng)))
+    }
+
+    /// Returns a random point on the navmesh that is connected to `start_polygon`
+    /// through polygons touching the circle around `center`.
+    ///
+    /// The point is uniformly distributed over the area of the polygons found by [`Self::find_polygons_around_circle`].
+    /// Since whole polygons are sampled, the point is not guaranteed to lie inside the circle.
+    ///
+    /// `rng` must return uniformly distributed numbers in the range `[0, 1)`.
+    pub fn find_random_point_around_circle(
+        &self,
+        start_polygon: u16,
+        center: Vec3,
+        radius: f32,
+        filter: &(impl PolygonFilter + ?Sized),
+        mut rng: impl FnMut() -> f32,
+    ) -> Result<(u16, Vec3), NavmeshQueryError> {
+        if self.is_valid_polygon(start_polygon) && !self.passes_filter(start_polygon, filter) {
+            return Err(NavmeshQueryError::InvalidPolygon(start_polygon));
+        }
+        let found = self.find_polygons_around_circle(start_polygon, center, radius, filter)?;
+
+        let mut area_sum = 0.0;
+        let mut chosen = start_polygon;
+        for &polygon in &found.polygons {
+            let area = self.polygon_area(polygon);
+            area_sum += area;
+            // Choose random polygon weighted by area, using reservoir sampling.
+            if rng() * area_sum <= area {
+                chosen = polygon;
+            }
+        }
+        Ok((chosen, self.random_point_in_polygon(chosen, &mut rng)))
+    }
+
+    fn random_point_in_polygon(&self, polygon: u16, mut rng: impl FnMut() -> f32) -> Vec3 {
+        let s = rng();
+        let t = rng();
+        let point = random_point_in_convex_polygon(self.polygon_vertices(polygon), s, t);
+        self.closest_point_on_polygon(polygon, point).0
+    }
+}
+
+fn bounds_of(vertices: &[Vec3]) -> Aabb3d {
+    vertices.iter().fold(
+        Aabb3d {
+            min: Vec3::splat(f32::MAX),
+            max: Vec3::splat(f32::MIN),
+        },
+        |bounds, vertex| Aabb3d {
+            min: bounds.min.min(*vertex),
+            max: bounds.max.max(*vertex),
+        },
+    )
+}
+
+/// Errors that can occur when querying a [`NavmeshQuery`].
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum NavmeshQueryError {
+    /// The polygon does not exist in the queried mesh.
+    #[error("Polygon {0} does not exist in the queried mesh")]
+    InvalidPolygon(u16),
+    /// A position passed to the query is not finite.
+    #[error("A position passed to the query is not finite")]
+    InvalidPosition,
+    /// A radius passed to the query is negative or not finite.
+    #[error("Invalid search radius: {0}")]
+    InvalidRadius(f32),
+    /// A sliced search failed because polygons it visited disappeared between updates.
+    #[error("The search failed because visited polygons disappeared between updates")]
+    SearchFailed,
+    /// The search shape passed to the query has no vertices.
+    #[error("The search shape has no vertices")]
+    InvalidShape,
+    /// The polygon reference does not belong to the queried tile, or the tile has been replaced since it was created.
+    #[error("Polygon reference {0:?} is not valid for the queried tile")]
+    InvalidPolyRef(PolyRef),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{QueryFilter, ops::abs, test_meshes};
+
+    #[test]
+    fn raycast_reaches_end_inside_mesh() {
+        let mesh = test_meshes::grid(3, 1);
+        let query = NavmeshQuery::new(&mesh);
+        let hit = query
+            .raycast(
+                0,
+                Vec3::new(5.0, 0.0, 5.0),
+                Vec3::new(25.0, 0.0, 5.0),
+                &QueryFilter::default(),
+            )
+            .unwrap();
+        assert!(!hit.is_hit());
+        assert_eq!(hit.path, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn raycast_hits_outer_wall() {
+        let mesh = test_meshes::grid(3, 1);
+        let query = NavmeshQuery::new(&mesh);
+        let hit = query
+            .raycast(
+                0,
+                Vec3::new(5.0, 0.0, 5.0),
+                Vec3::new(35.0, 0.0, 5.0),
+                &QueryFilter::default(),
+            )
+            .unwrap();
+        assert!(hit.is_hit());
+        assert!(abs(hit.t - 25.0 / 30.0) < 1e-5);
+        assert_eq!(hit.hit_normal, Vec3::NEG_X);
+        assert_eq!(hit.path, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn raycast_respects_filter() {
+        let mut mesh = test_meshes::grid(3, 1);
+        mesh.flags[1] = 2;
+        let query = NavmeshQuery::new(&mesh);
+        let filter = QueryFilter {
+            exclude_flags: 2,
+            ..Default::default()
+        };
+        let hit = query
+            .raycast(
+                0,
+                Vec3::new(5.0, 0.0, 5.0),
+                Vec3::new(25.0, 0.0, 5.0),
+                &filter,
+            )
+            .unwrap();
+        assert!(abs(hit.t - 0.25) < 1e-5);
+        assert_eq!(hit.path, vec![0]);
+    }
+
+    #[test]
+    fn finds_nearest_polygon() {
+        let mesh = test_meshes::grid(3, 1);
+        let query = NavmeshQuery::new(&mesh);
+        let (polygon, point) = query
+            .find_nearest_polygon(
+                Vec3::new(15.0, 1.0, 5.0),
+                Vec3::splat(2.0),
+                &QueryFilter::default(),
+            )
+            .unwrap();
+        assert_eq!(polygon, 1);
+        assert_eq!(point, Vec3::new(15.0, 0.0, 5.0));
+    }
+
+    #[test]
+    fn move_along_surface_reaches_target() {
+        let mesh = test_meshes::grid(3, 1);
+        let query = NavmeshQuery::new(&mesh);
+        let result = query
+            .move_along_surface(
+                0,
+                Vec3::new(8.0, 0.0, 5.0),
+                Vec3::new(12.0, 0.0, 6.0),
+                &QueryFilter::default(),
+            )
+            .unwrap();
+        assert_eq!(result.position, Vec3::new(12.0, 0.0, 6.0));
+        assert_eq!(result.visited, vec![0, 1]);
+    }
+
+    #[test]
+    fn move_along_surface_slides_along_wall() {
+        let mesh = test_meshes::grid(3, 1);
+        let query = NavmeshQuery::new(&mesh);
+        let result = query
+            .move_along_surface(
+                1,
+                Vec3::new(15.0, 0.0, 8.0),
+                Vec3::new(17.0, 0.0, 12.0),
+                &QueryFilter::default(),
+            )
+            .unwrap();
+        assert_eq!(result.position, Vec3::new(17.0, 0.0, 10.0));
+        assert_eq!(result.visited, vec![1]);
+    }
+
+    #[test]
+    fn finds_distance_to_wall() {
+        let mesh = test_meshes::grid(3, 1);
+        let query = NavmeshQuery::new(&mesh);
+        let hit = query
+            .find_distance_to_wall(1, Vec3::new(15.0, 0.0, 7.0), 5.0, &QueryFilter::default())
+            .unwrap()
+            .unwrap();
+        assert!(abs(hit.distance - 3.0) < 1e-5);
+        assert_eq!(hit.position, Vec3::new(15.0, 0.0, 10.0));
+        assert_eq!(hit.normal, Vec3::NEG_Z);
+
+        let miss = query
+            .find_distance_to_wall(1, Vec3::new(15.0, 0.0, 5.0), 4.0, &QueryFilter::default())
+            .unwrap();
+        assert_eq!(miss, None);
+    }
+
+    #[test]
+    fn excluded_polygons_count_as_walls() {
+        let mut mesh = test_meshes::grid(3, 1);
+        mesh.flags[2] = 2;
+        let query = NavmeshQuery::new(&mesh);
+        let filter = QueryFilter {
+            exclude_flags: 2,
+            ..Default::default()
+        };
+        let hit = query
+            .find_distance_to_wall(1, Vec3::new(19.0, 0.0, 5.0), 5.0, &filter)
+            .unwrap()
+            .unwrap();
+        assert!(abs(hit.distance - 1.0) < 1e-5);
+        assert_eq!(hit.normal, Vec3::NEG_X);
+    }
+
+    #[test]
+    fn finds_polygons_around_circle() {
+        let mesh = test_meshes::grid(2, 2);
+        let query = NavmeshQuery::new(&mesh);
+        let filter = QueryFilter::default();
+
+        let small = query
+            .find_polygons_around_circle(0, Vec3::new(5.0, 0.0, 5.0), 4.0, &filter)
+            .unwrap();
+        assert_eq!(small.polygons, vec![0]);
+
+        let result = query
+            .find_polygons_around_circle(0, Vec3::new(8.0, 0.0, 5.0), 4.0, &filter)
+            .unwrap();
+        assert_eq!(result.polygons, vec![0, 1]);
+        assert_eq!(result.parents, vec![None, Some(0)]);
+        assert!(abs(result.costs[1] - 2.0) < 1e-5);
+
+        let all = query
+            .find_polygons_around_circle(0, Vec3::new(8.0, 0.0, 8.0), 4.0, &filter)
+            .unwrap();
+        assert_eq!(all.polygons.len(), 4);
+        assert_eq!(all.polygons[0], 0);
+        assert!(all.costs.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn finds_polygons_around_shape() {
+        let mesh = test_meshes::grid(2, 2);
+        let query = NavmeshQuery::new(&mesh);
+        let shape = [
+            Vec3::new(2.0, 0.0, 2.0),
+            Vec3::new(2.0, 0.0, 8.0),
+            Vec3::new(14.0, 0.0, 8.0),
+            Vec3::new(14.0, 0.0, 2.0),
+        ];
+        let result = query
+            .find_polygons_around_shape(0, &shape, &QueryFilter::default())
+            .unwrap();
+        assert_eq!(result.polygons, vec![0, 1]);
+        assert_eq!(result.parents, vec![None, Some(0)]);
+    }
+
+    #[test]
+    fn finds_local_neighbourhood() {
+        let mesh = test_meshes::grid(2, 2);
+        let query = NavmeshQuery::new(&mesh);
+        let result = query
+            .find_local_neighbourhood(0, Vec3::new(9.0, 0.0, 9.0), 2.0, &QueryFilter::default())
+            .unwrap();
+        assert_eq!(result.polygons.len(), 4);
+        assert_eq!(result.polygons[0], 0);
+        assert_eq!(result.parents[0], None);
+    }
+
+    /// A deterministic generator of numbers in `[0, 1)` for tests.
+    fn lcg(seed: u32) -> impl FnMut() -> f32 {
+        let mut state = seed;
+        move || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 8) as f32 / (1 << 24) as f32
+        }
+    }
+
+    #[test]
+    fn random_points_are_on_the_mesh() {
+        let mesh = test_meshes::grid(2, 2);
+        let query = NavmeshQuery::new(&mesh);
+        let filter = QueryFilter::default();
+        let mut rng = lcg(7);
+        let mut hits = [0; 4];
+        for _ in 0..400 {
+            let (polygon, point) = query.find_random_point(&filter, &mut rng).unwrap();
+            assert!(point_in_polygon_xz(point, query.polygon_vertices(polygon)));
+            hits[polygon as usize] += 1;
+        }
+        // All polygons have the same area, so all of them should be picked regularly.
+        assert!(hits.iter().all(|hits| *hits > 50));
+    }
+
+    #[test]
+    fn random_points_are_deterministic() {
+        let mesh = test_meshes::grid(2, 2);
+        let query = NavmeshQuery::new(&mesh);
+        let filter = QueryFilter::default();
+        assert_eq!(
+            query.find_random_point(&filter, lcg(3)),
+            query.find_random_point(&filter, lcg(3))
+        );
+    }
+
+    #[test]
+    fn random_points_around_circle_are_connected() {
+        let mut mesh = test_meshes::grid(2, 2);
+        mesh.flags[1] = 2;
+        let query = NavmeshQuery::new(&mesh);
+        let filter = QueryFilter {
+            exclude_flags: 2,
+            ..Default::default()
+        };
+        let mut rng = lcg(11);
+        for _ in 0..100 {
+            let (polygon, _) = query
+                .find_random_point_around_circle(
+                    0,
+                    Vec3::new(5.0, 0.0, 5.0),
+                    6.0,
+                    &filter,
+                    &mut rng,
+                )
+                .unwrap();
+            assert!(polygon == 0 || polygon == 2);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Search node bookkeeping shared by the graph searches of [`NavmeshQuery`](crate::NavmeshQuery).
+
+use alloc::{collections::BTreeMap, collections::BinaryHeap, vec::Vec};
+use core::{
+    cmp::Ordering,
+    ops::{Index, IndexMut},
+};
+use glam::Vec3;
+
+/// The state of a [`Node`] during a search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NodeState {
+    /// The node has been allocated, but not yet been added to the open list.
+    New,
+    /// The node is in the open list.
+    Open,
+    /// The node has been fully expanded.
+    Closed,
+}
+
+/// A polygon visited by a search.
+///
+/// Polygons are identified by their index in the query, or by their [`PolyRef`](crate::PolyRef) for searches that outlive a query.
+#[derive(Debug, Clone)]
+pub(crate) struct Node<K = u16> {
+    /// The polygon this node represents.
+    pub(crate) polygon: K,
+    /// The position at which the search entered the polygon.
+    pub(crate) position: Vec3,
+    /// The cost from the start to this node.
+    pub(crate) cost: f32,
+    /// The total cost from the start to this node, including a heuristic if the search uses one.
+    pub(crate) total: f32,
+    /// The index of the node this node was reached from.
+    pub(crate) parent: Option<usize>,
+    /// The state of this node.
+    pub(crate) state: NodeState,
+}
+
+/// A bounded pool of search nodes with one node per polygon.
+#[derive(Debug, Clone)]
+pub(crate) struct NodePool<K = u16> {
+    nodes: Vec<Node<K>>,
+    lookup: BTreeMap<K, usize>,
+    max_nodes: usize,
+}
+
+impl<K: Copy + Ord> NodePool<K> {
+    pub(crate) fn new(max_nodes: usize) -> Self {
+        Self {
+            nodes: Vec::new(),
+            lookup: BTreeMap::new(),
+            max_nodes,
+        }
+    }
+
+    /// Returns the node of the given polygon, if it has been allocated.
+    #[inline]
+    pub(crate) fn find(&self, polygon: K) -> Option<usize> {
+        self.lookup.get(&polygon).copied()
+    }
+
+    /// Returns the node of the given polygon, allocating a new one if needed.
+    ///
+    /// Returns `None` if the pool is exhausted.
+    pub(crate) fn get_or_insert(&mut self, polygon: K) -> Option<usize> {
+        if let Some(index) = self.find(polygon) {
+            return Some(index);
+        }
+        if self.nodes.len() >= self.max_nodes {
+            return None;
+        }
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            polygon,
+            position: Vec3::ZERO,
+            cost: 0.0,
+            total: 0.0,
+            parent: None,
+            state: NodeState::New,
+        });
+        self.lookup.insert(polygon, index);
+        Some(index)
+    }
+
+    /// Returns the polygons from the start of the search to `node`, in order.
+    pub(crate) fn path_to(&self, node: usize) -> Vec<K> {
+        let mut path = Vec::new();
+        let mut current = Some(node);
+        while let Some(index) = current {
+            path.push(self.nodes[index].polygon);
+            current = self.nodes[index].parent;
+        }
+        path.reverse();
+        path
+    }
+}
+
+impl<K> Index<usize> for NodePool<K> {
+    type Output = Node<K>;
+
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.nodes[index]
+    }
+}
+
+impl<K> IndexMut<usize> for NodePool<K> {
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.nodes[index]
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OpenEntry {
+    total: f32,
+    node: usize,
+}
+
+impl PartialEq for OpenEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenEntry {}
+
+impl PartialOrd for OpenEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so that the heap pops the cheapest node first.
+        other
+            .total
+            .total_cmp(&self.total)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+/// A priority queue of open nodes, ordered by their total cost.
+///
+/// Updating the cost of a node pushes it again; outdated entries are skipped when popping.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OpenList(BinaryHeap<OpenEntry>);
+
+impl OpenList {
+    #[inline]
+    pub(crate) fn push<K>(&mut self, pool: &NodePool<K>, node: usize) {
+        self.0.push(OpenEntry {
+            total: pool[node].total,
+            node,
+        });
+    }
+
+    /// Returns whether there are no more open nodes.
+    pub(crate) fn is_empty<K>(&mut self, pool: &NodePool<K>) -> bool {
+        while let Some(entry) = self.0.peek() {
+            let node = &pool[entry.node];
+            if node.state == NodeState::Open && node.total == entry.total {
+                return false;
+            }
+            self.0.pop();
+        }
+        true
+    }
+
+    /// Removes the open node with the lowest total cost.
+    pub(crate) fn pop<K>(&mut self, pool: &NodePool<K>) -> Option<usize> {
+        while let Some(entry) = self.0.pop() {
+            let node = &pool[entry.node];
+            if node.state == NodeState::Open && node.total == entry.total {
+                return Some(entry.node);
+            }
+        }
+        None
+    }
+}
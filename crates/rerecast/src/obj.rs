@@ -0,0 +1,400 @@
+//! Reading and writing of the Wavefront OBJ format, for inspecting the stages of the build process in other tools.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write as _;
+use glam::{UVec3, Vec3, Vec3A};
+use thiserror::Error;
+
+use crate::{
+    AreaType, CompactHeightfield, ContourSet, DetailNavmesh, Heightfield, PolygonNavmesh,
+    TiledNavmesh, TriMesh,
+};
+
+impl TriMesh {
+    /// Parses a mesh from the Wavefront OBJ format.
+    ///
+    /// Only vertices (`v`) and faces (`f`) are read, faces with more than three vertices are triangulated as a fan.
+    /// Triangles in a group or object (`g` or `o`) listed in `group_areas` get the given area type,
+    /// all others are [`AreaType::NOT_WALKABLE`] so that they can be marked with [`TriMesh::mark_walkable_triangles`].
+    pub fn from_obj(source: &str, group_areas: &[(&str, AreaType)]) -> Result<Self, ObjError> {
+        let mut trimesh = TriMesh::default();
+        let mut area = AreaType::NOT_WALKABLE;
+        for (line_index, line) in source.lines().enumerate() {
+            let line_number = line_index + 1;
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => {
+                    let mut coordinates = tokens.map(str::parse::<f32>);
+                    let mut next = || {
+                        coordinates
+                            .next()
+                            .and_then(Result::ok)
+                            .ok_or(ObjError::InvalidVertex { line: line_number })
+                    };
+                    trimesh.vertices.push(Vec3A::new(next()?, next()?, next()?));
+                }
+                Some("f") => {
+                    let indices = tokens
+                        .map(|token| trimesh.parse_face_index(token, line_number))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if indices.len() < 3 {
+                        return Err(ObjError::InvalidFace { line: line_number });
+                    }
+                    for j in 2..indices.len() {
+                        trimesh
+                            .indices
+                            .push(UVec3::new(indices[0], indices[j - 1], indices[j]));
+                        trimesh.area_types.push(area);
+                    }
+                }
+                Some("g" | "o") => {
+                    let name = tokens.next().unwrap_or_default();
+                    area = group_areas
+                        .iter()
+                        .find(|(group, _)| *group == name)
+                        .map_or(AreaType::NOT_WALKABLE, |(_, area)| *area);
+                }
+                _ => {}
+            }
+        }
+        Ok(trimesh)
+    }
+
+    /// Parses the vertex index of a face element such as `3`, `3/1` or `-1//2` into a zero-based index.
+    fn parse_face_index(&self, token: &str, line: usize) -> Result<u32, ObjError> {
+        let index: i64 = token
+            .split('/')
+            .next()
+            .and_then(|index| index.parse().ok())
+            .ok_or(ObjError::InvalidFace { line })?;
+        let vertex_count = self.vertices.len() as i64;
+        // Negative indices count backwards from the last vertex.
+        let resolved = if index < 0 {
+            vertex_count + index
+        } else {
+            index - 1
+        };
+        if !(0..vertex_count).contains(&resolved) {
+            return Err(ObjError::IndexOutOfRange { line, index });
+        }
+        Ok(resolved as u32)
+    }
+
+    /// Writes the mesh in the Wavefront OBJ format.
+    pub fn to_obj(&self) -> String {
+        let mut obj = ObjWriter::new("Triangle mesh");
+        for vertex in &self.vertices {
+            obj.vertex(Vec3::from(*vertex));
+        }
+        for triangle in &self.indices {
+            obj.face(triangle.to_array().map(|i| i as usize));
+        }
+        obj.finish()
+    }
+}
+
+impl Heightfield {
+    /// Writes the spans of the heightfield as boxes in the Wavefront OBJ format.
+    pub fn to_obj(&self) -> String {
+        let mut obj = ObjWriter::new("Heightfield");
+        for z in 0..self.height {
+            for x in 0..self.width {
+                let mut span_key = self.span_key_at(x, z);
+                while let Some(key) = span_key {
+                    let span = self.span(key);
+                    let min = self.aabb.min
+                        + Vec3::new(x as f32, 0.0, z as f32) * self.cell_size
+                        + Vec3::Y * span.min as f32 * self.cell_height;
+                    let max = Vec3::new(
+                        min.x + self.cell_size,
+                        self.aabb.min.y + span.max as f32 * self.cell_height,
+                        min.z + self.cell_size,
+                    );
+                    obj.cuboid(min, max);
+                    span_key = span.next;
+                }
+            }
+        }
+        obj.finish()
+    }
+}
+
+impl CompactHeightfield {
+    /// Writes the walkable surface of each span as a quad in the Wavefront OBJ format.
+    pub fn to_obj(&self) -> String {
+        let mut obj = ObjWriter::new("Compact heightfield");
+        for z in 0..self.height {
+            for x in 0..self.width {
+                let cell = &self.cells[x as usize + z as usize * self.width as usize];
+                for i in cell.index_range() {
+                    if !self.areas[i].is_walkable() {
+                        continue;
+                    }
+                    let min = Vec3::new(
+                        self.aabb.min.x + x as f32 * self.cell_size,
+                        self.aabb.min.y + self.spans[i].y as f32 * self.cell_height,
+                        self.aabb.min.z + z as f32 * self.cell_size,
+                    );
+                    obj.quad(min, self.cell_size);
+                }
+            }
+        }
+        obj.finish()
+    }
+}
+
+impl ContourSet {
+    /// Writes the simplified contours as closed lines in the Wavefront OBJ format.
+    pub fn to_obj(&self) -> String {
+        let mut obj = ObjWriter::new("Contour set");
+        for contour in &self.contours {
+            if contour.vertices.is_empty() {
+                continue;
+            }
+            let first = obj.vertex_count;
+            for (vertex, _) in &contour.vertices {
+                obj.vertex(Vec3::new(
+                    self.aabb.min.x + vertex.x as f32 * self.cell_size,
+                    self.aabb.min.y + vertex.y as f32 * self.cell_height,
+                    self.aabb.min.z + vertex.z as f32 * self.cell_size,
+                ));
+            }
+            obj.line((first..obj.vertex_count).chain([first]));
+        }
+        obj.finish()
+    }
+}
+
+impl PolygonNavmesh {
+    /// Writes the polygons in world space in the Wavefront OBJ format.
+    pub fn to_obj(&self) -> String {
+        let mut obj = ObjWriter::new("Polygon navmesh");
+        obj.polygon_mesh(self);
+        obj.finish()
+    }
+}
+
+impl TiledNavmesh {
+    /// Writes the polygons of all tiles in world space in the Wavefront OBJ format, each tile as an object named `tile <x> <z>`.
+    pub fn to_obj(&self) -> String {
+        let mut obj = ObjWriter::new("Tiled navmesh");
+        for (_, tile) in self.tiles() {
+            _ = writeln!(obj.obj, "o tile {} {}", tile.coord.x, tile.coord.z);
+            obj.polygon_mesh(&tile.poly_mesh);
+        }
+        obj.finish()
+    }
+}
+
+impl DetailNavmesh {
+    /// Writes the triangles of all sub-meshes in the Wavefront OBJ format.
+    pub fn to_obj(&self) -> String {
+        let mut obj = ObjWriter::new("Detail navmesh");
+        for vertex in &self.vertices {
+            obj.vertex(*vertex);
+        }
+        for submesh in &self.meshes {
+            let base = submesh.base_vertex_index as usize;
+            let triangles = &self.triangles[submesh.base_triangle_index as usize..]
+                [..submesh.triangle_count as usize];
+            for triangle in triangles {
+                obj.face(triangle.map(|i| base + i as usize));
+            }
+        }
+        obj.finish()
+    }
+}
+
+/// Builds an OBJ file, keeping track of the vertex indices.
+struct ObjWriter {
+    obj: String,
+    vertex_count: usize,
+}
+
+impl ObjWriter {
+    fn new(name: &str) -> Self {
+        let mut obj = String::new();
+        _ = writeln!(obj, "# {name} exported by rerecast");
+        Self {
+            obj,
+            vertex_count: 0,
+        }
+    }
+
+    fn polygon_mesh(&mut self, poly_mesh: &PolygonNavmesh) {
+        let first = self.vertex_count;
+        for i in 0..poly_mesh.vertices.len() {
+            self.vertex(poly_mesh.world_vertex(i as u16));
+        }
+        for polygon in poly_mesh.polygons() {
+            self.face(polygon.map(|i| first + i as usize));
+        }
+    }
+
+    fn vertex(&mut self, vertex: Vec3) {
+        _ = writeln!(self.obj, "v {} {} {}", vertex.x, vertex.y, vertex.z);
+        self.vertex_count += 1;
+    }
+
+    /// Writes a face of zero-based vertex indices.
+    fn face(&mut self, indices: impl IntoIterator<Item = usize>) {
+        self.elements("f", indices);
+    }
+
+    /// Writes a polyline of zero-based vertex indices.
+    fn line(&mut self, indices: impl IntoIterator<Item = usize>) {
+        self.elements("l", indices);
+    }
+
+    fn elements(&mut self, kind: &str, indices: impl IntoIterator<Item = usize>) {
+        self.obj.push_str(kind);
+        for index in indices {
+            _ = write!(self.obj, " {}", index + 1);
+        }
+        self.obj.push('\n');
+    }
+
+    /// Writes an upward facing square at height `min.y` with `min` as its corner.
+    fn quad(&mut self, min: Vec3, size: f32) {
+        let first = self.vertex_count;
+        for (x, z) in [(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)] {
+            self.vertex(min + Vec3::new(x, 0.0, z) * size);
+        }
+        self.face(first..first + 4);
+    }
+
+    /// Writes an axis-aligned box with outward facing sides.
+    fn cuboid(&mut self, min: Vec3, max: Vec3) {
+        let first = self.vertex_count;
+        for i in 0..8 {
+            self.vertex(Vec3::select(
+                glam::BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0),
+                max,
+                min,
+            ));
+        }
+        for face in [
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+        ] {
+            self.face(face.map(|i| first + i));
+        }
+    }
+
+    fn finish(self) -> String {
+        self.obj
+    }
+}
+
+/// Errors that can occur when parsing a Wavefront OBJ file.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ObjError {
+    /// A vertex does not have three valid coordinates.
+    #[error("Invalid vertex in line {line}")]
+    InvalidVertex {
+        /// The one-based line number.
+        line: usize,
+    },
+    /// A face has fewer than three vertices or an invalid vertex index.
+    #[error("Invalid face in line {line}")]
+    InvalidFace {
+        /// The one-based line number.
+        line: usize,
+    },
+    /// A face references a vertex that is not defined before it.
+    #[error("Vertex index {index} in line {line} is out of range")]
+    IndexOutOfRange {
+        /// The one-based line number.
+        line: usize,
+        /// The index as written in the file.
+        index: i64,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_obj_with_groups_and_polygons() {
+        let source = "\
+# A quad on the ground and a wall triangle
+o ground
+v 0 0 0
+v 1 0 0
+v 1 0 1
+v 0 0 1
+f 1/1/1 2/2/1 3/3/1 4/4/1
+g wall
+v 0 1 0
+f -5 -4 -1
+";
+        let trimesh = TriMesh::from_obj(source, &[("ground", AreaType::DEFAULT_WALKABLE)]).unwrap();
+        assert_eq!(trimesh.vertices.len(), 5);
+        assert_eq!(
+            trimesh.indices,
+            [
+                UVec3::new(0, 1, 2),
+                UVec3::new(0, 2, 3),
+                UVec3::new(0, 1, 4)
+            ]
+        );
+        assert_eq!(
+            trimesh.area_types,
+            [
+                AreaType::DEFAULT_WALKABLE,
+                AreaType::DEFAULT_WALKABLE,
+                AreaType::NOT_WALKABLE
+            ]
+        );
+
+        // Export and import again.
+        let reparsed = TriMesh::from_obj(&trimesh.to_obj(), &[]).unwrap();
+        assert_eq!(reparsed.vertices, trimesh.vertices);
+        assert_eq!(reparsed.indices, trimesh.indices);
+
+        assert_eq!(
+            TriMesh::from_obj("v 0 0 0\nf 1 2 3", &[]),
+            Err(ObjError::IndexOutOfRange { line: 2, index: 2 })
+        );
+        assert_eq!(
+            TriMesh::from_obj("v 0 0", &[]),
+            Err(ObjError::InvalidVertex { line: 1 })
+        );
+    }
+
+    #[test]
+    fn writes_polygons_in_world_space() {
+        let mesh = PolygonNavmesh {
+            vertices: vec![
+                glam::U16Vec3::new(0, 0, 0),
+                glam::U16Vec3::new(0, 0, 2),
+                glam::U16Vec3::new(2, 0, 2),
+                glam::U16Vec3::new(2, 0, 0),
+            ],
+            polygons: vec![
+                0,
+                1,
+                2,
+                3,
+                PolygonNavmesh::NO_INDEX,
+                PolygonNavmesh::NO_INDEX,
+            ],
+            max_vertices_per_polygon: 6,
+            aabb: crate::Aabb3d {
+                min: Vec3::new(10.0, 1.0, 0.0),
+                max: Vec3::new(11.0, 2.0, 1.0),
+            },
+            cell_size: 0.5,
+            cell_height: 0.25,
+            ..Default::default()
+        };
+        let obj = mesh.to_obj();
+        assert!(obj.contains("v 11 1 1\n"));
+        assert!(obj.ends_with("f 1 2 3 4\n"));
+    }
+}
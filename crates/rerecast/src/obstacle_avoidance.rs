@@ -0,0 +1,864 @@
+//! Velocity-based local collision avoidance against moving circles and static segments.
+//!
+//! Velocities are either sampled like Detour's `dtObstacleAvoidanceQuery`, or solved with
+//! ORCA (optimal reciprocal collision avoidance), as in the RVO2 library.
+
+use alloc::vec::Vec;
+use core::f32::consts::TAU;
+use glam::{Vec2, Vec3, Vec3Swizzles as _};
+
+use crate::{
+    NavmeshQuery, NavmeshQueryError, PolygonFilter,
+    math::{distance_squared_between_point_and_segment_xz, perp_xz, triangle_area_xz},
+    ops::{FloatPow as _, abs, sin_cos, sqrt},
+};
+
+/// The algorithm used by [`ObstacleAvoidanceQuery::sample_velocity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum ObstacleAvoidanceMode {
+    /// Detour's adaptive sampling, see [`ObstacleAvoidanceQuery::sample_velocity_adaptive`].
+    #[default]
+    AdaptiveSampling,
+    /// Optimal reciprocal collision avoidance, see [`ObstacleAvoidanceQuery::sample_velocity_orca`].
+    Orca,
+}
+
+/// Parameters that control how [`ObstacleAvoidanceQuery`] weighs candidate velocities.
+///
+/// The defaults match the high quality setting of Detour's crowd.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct ObstacleAvoidanceParams {
+    /// The algorithm used to find the new velocity.
+    pub mode: ObstacleAvoidanceMode,
+    /// How much the samples are centered around the desired velocity instead of around zero, in the range `[0, 1]`.
+    pub velocity_bias: f32,
+    /// The penalty weight for deviating from the desired velocity.
+    pub weight_desired_velocity: f32,
+    /// The penalty weight for deviating from the current velocity.
+    pub weight_current_velocity: f32,
+    /// The penalty weight for passing other agents on the wrong side.
+    pub weight_side: f32,
+    /// The penalty weight for colliding soon.
+    pub weight_time_of_impact: f32,
+    /// How far into the future, in seconds, collisions are anticipated.
+    pub horizon_time: f32,
+    /// The time in seconds within which ORCA resolves overlaps that already exist, usually the simulation time step.
+    pub time_step: f32,
+    /// The number of sample directions per ring of the sampling pattern, in the range `[1, 32]`.
+    pub adaptive_divisions: u8,
+    /// The number of rings of the sampling pattern, in the range `[1, 4]`.
+    pub adaptive_rings: u8,
+    /// The number of times the sampling pattern is refined around the best sample so far.
+    pub adaptive_depth: u8,
+}
+
+impl Default for ObstacleAvoidanceParams {
+    fn default() -> Self {
+        Self {
+            mode: ObstacleAvoidanceMode::default(),
+            velocity_bias: 0.4,
+            weight_desired_velocity: 2.0,
+            weight_current_velocity: 0.75,
+            weight_side: 0.75,
+            weight_time_of_impact: 2.5,
+            horizon_time: 2.5,
+            time_step: 0.1,
+            adaptive_divisions: 7,
+            adaptive_rings: 2,
+            adaptive_depth: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ObstacleCircle {
+    position: Vec3,
+    velocity: Vec3,
+    desired_velocity: Vec3,
+    radius: f32,
+    /// Direction from the agent to the obstacle.
+    dp: Vec3,
+    /// The side on which the agent should pass the obstacle.
+    np: Vec3,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ObstacleSegment {
+    p: Vec3,
+    q: Vec3,
+    /// Whether the agent touches the segment.
+    touch: bool,
+}
+
+/// Finds a velocity that avoids nearby obstacles while staying close to a desired velocity.
+///
+/// Obstacles are moving circles, usually other agents, and static segments, usually walls of the navmesh.
+/// The query is reused across agents: call [`ObstacleAvoidanceQuery::reset`], add the obstacles around the agent
+/// and sample a new velocity.
+#[derive(Debug, Clone, Default)]
+pub struct ObstacleAvoidanceQuery {
+    circles: Vec<ObstacleCircle>,
+    segments: Vec<ObstacleSegment>,
+}
+
+/// The state of a single velocity sampling.
+struct Sampler<'a> {
+    query: &'a ObstacleAvoidanceQuery,
+    params: &'a ObstacleAvoidanceParams,
+    position: Vec3,
+    radius: f32,
+    velocity: Vec3,
+    desired_velocity: Vec3,
+    inv_horizon_time: f32,
+    inv_max_speed: f32,
+}
+
+impl ObstacleAvoidanceQuery {
+    /// Creates a query without obstacles.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes all obstacles.
+    pub fn reset(&mut self) {
+        self.circles.clear();
+        self.segments.clear();
+    }
+
+    /// Adds a moving circular obstacle, e.g. another agent.
+    pub fn add_circle(
+        &mut self,
+        position: Vec3,
+        radius: f32,
+        velocity: Vec3,
+        desired_velocity: Vec3,
+    ) {
+        self.circles.push(ObstacleCircle {
+            position,
+            velocity,
+            desired_velocity,
+            radius,
+            dp: Vec3::ZERO,
+            np: Vec3::ZERO,
+        });
+    }
+
+    /// Adds a static segment obstacle, e.g. a wall of the navmesh.
+    pub fn add_segment(&mut self, p: Vec3, q: Vec3) {
+        self.segments.push(ObstacleSegment { p, q, touch: false });
+    }
+
+    /// The number of circular obstacles.
+    #[inline]
+    pub fn circle_count(&self) -> usize {
+        self.circles.len()
+    }
+
+    /// The number of segment obstacles.
+    #[inline]
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Adds the solid navmesh edges within `range` of `position` as segment obstacles.
+    ///
+    /// The walls are collected from the polygons found by [`NavmeshQuery::find_local_neighbourhood`] around `polygon`,
+    /// which should contain `position`. Walls facing away from `position` are skipped.
+    pub fn add_navmesh_walls(
+        &mut self,
+        query: &NavmeshQuery,
+        polygon: u16,
+        position: Vec3,
+        range: f32,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Result<(), NavmeshQueryError> {
+        let (_, walls) = navmesh_walls_around(query, polygon, position, range, filter)?;
+        for (_, (p, q)) in walls {
+            if triangle_area_xz(position, p, q) >= 0.0 {
+                self.add_segment(p, q);
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds a collision-free velocity with the algorithm selected by [`ObstacleAvoidanceParams::mode`].
+    pub fn sample_velocity(
+        &mut self,
+        position: Vec3,
+        radius: f32,
+        max_speed: f32,
+        velocity: Vec3,
+        desired_velocity: Vec3,
+        params: &ObstacleAvoidanceParams,
+    ) -> Vec3 {
+        match params.mode {
+            ObstacleAvoidanceMode::AdaptiveSampling => self.sample_velocity_adaptive(
+                position,
+                radius,
+                max_speed,
+                velocity,
+                desired_velocity,
+                params,
+            ),
+            ObstacleAvoidanceMode::Orca => self.sample_velocity_orca(
+                position,
+                radius,
+                max_speed,
+                velocity,
+                desired_velocity,
+                params,
+            ),
+        }
+    }
+
+    /// Finds the velocity closest to `desired_velocity` that avoids all obstacles for [`ObstacleAvoidanceParams::horizon_time`],
+    /// using optimal reciprocal collision avoidance.
+    ///
+    /// Each obstacle restricts the velocity to a half-plane, and the closest velocity within all half-planes is found with
+    /// linear programming. Circles are assumed to be agents that also avoid this agent, so each side takes half of the
+    /// responsibility for avoiding a collision. If the constraints cannot be satisfied, e.g. in dense crowds,
+    /// the velocity that violates them the least is returned, giving priority to the segments.
+    /// The sampling weights of the parameters are ignored.
+    pub fn sample_velocity_orca(
+        &mut self,
+        position: Vec3,
+        radius: f32,
+        max_speed: f32,
+        velocity: Vec3,
+        desired_velocity: Vec3,
+        params: &ObstacleAvoidanceParams,
+    ) -> Vec3 {
+        let inv_time_horizon = 1.0 / params.horizon_time;
+        let inv_time_step = 1.0 / params.time_step;
+        let velocity = velocity.xz();
+        let mut lines = Vec::with_capacity(self.segments.len() + self.circles.len());
+
+        // Create obstacle ORCA lines. Static obstacles take full responsibility.
+        for segment in &self.segments {
+            let (_, t) =
+                distance_squared_between_point_and_segment_xz(position, (segment.p, segment.q));
+            let relative_position = (segment.p.lerp(segment.q, t) - position).xz();
+            let distance = relative_position.length();
+            if distance < 1e-6 {
+                continue;
+            }
+            let normal = relative_position / distance;
+            // Outside of the segment, the velocity may approach it within the time horizon. Overlaps are resolved within a time step.
+            let allowed = if distance > radius {
+                (distance - radius) * inv_time_horizon
+            } else {
+                (distance - radius) * inv_time_step
+            };
+            lines.push(OrcaLine {
+                point: normal * allowed,
+                direction: Vec2::new(-normal.y, normal.x),
+            });
+        }
+        let obstacle_line_count = lines.len();
+
+        // Create agent ORCA lines.
+        for circle in &self.circles {
+            let relative_position = (circle.position - position).xz();
+            let relative_velocity = velocity - circle.velocity.xz();
+            let distance_squared = relative_position.length_squared();
+            let combined_radius = radius + circle.radius;
+            let combined_radius_squared = combined_radius.squared();
+
+            let (direction, u) = if distance_squared > combined_radius_squared {
+                // No collision. Vector from cutoff center to relative velocity.
+                let w = relative_velocity - inv_time_horizon * relative_position;
+                let w_length_squared = w.length_squared();
+                let dot_product = w.dot(relative_position);
+                if dot_product < 0.0
+                    && dot_product.squared() > combined_radius_squared * w_length_squared
+                {
+                    // Project on cut-off circle.
+                    let w_length = sqrt(w_length_squared);
+                    let unit_w = w / w_length;
+                    (
+                        Vec2::new(unit_w.y, -unit_w.x),
+                        (combined_radius * inv_time_horizon - w_length) * unit_w,
+                    )
+                } else {
+                    // Project on legs.
+                    let leg = sqrt(distance_squared - combined_radius_squared);
+                    let (x, y) = (relative_position.x, relative_position.y);
+                    let direction = if relative_position.perp_dot(w) > 0.0 {
+                        // Project on left leg.
+                        Vec2::new(x * leg - y * combined_radius, x * combined_radius + y * leg)
+                            / distance_squared
+                    } else {
+                        // Project on right leg.
+                        -Vec2::new(
+                            x * leg + y * combined_radius,
+                            -x * combined_radius + y * leg,
+                        ) / distance_squared
+                    };
+                    let dot_product = relative_velocity.dot(direction);
+                    (direction, dot_product * direction - relative_velocity)
+                }
+            } else {
+                // Collision. Project on cut-off circle of time step.
+                let w = relative_velocity - inv_time_step * relative_position;
+                let w_length = w.length();
+                let unit_w = w.normalize_or_zero();
+                (
+                    Vec2::new(unit_w.y, -unit_w.x),
+                    (combined_radius * inv_time_step - w_length) * unit_w,
+                )
+            };
+            lines.push(OrcaLine {
+                point: velocity + 0.5 * u,
+                direction,
+            });
+        }
+
+        let mut result = Vec2::ZERO;
+        let line_fail =
+            linear_program_2(&lines, max_speed, desired_velocity.xz(), false, &mut result);
+        if line_fail < lines.len() {
+            linear_program_3(
+                &lines,
+                obstacle_line_count,
+                line_fail,
+                max_speed,
+                &mut result,
+            );
+        }
+        Vec3::new(result.x, 0.0, result.y)
+    }
+
+    /// Samples velocities in a pattern around the desired velocity, refining the pattern around the best sample,
+    /// and returns the velocity with the lowest penalty.
+    ///
+    /// `position`, `radius` and `velocity` describe the avoiding agent. The returned velocity is never faster than `max_speed`.
+    pub fn sample_velocity_adaptive(
+        &mut self,
+        position: Vec3,
+        radius: f32,
+        max_speed: f32,
+        velocity: Vec3,
+        desired_velocity: Vec3,
+        params: &ObstacleAvoidanceParams,
+    ) -> Vec3 {
+        const MAX_PATTERN_DIVISIONS: usize = 32;
+        const MAX_PATTERN_RINGS: usize = 4;
+
+        self.prepare(position, desired_velocity);
+        let sampler = Sampler {
+            query: self,
+            params,
+            position,
+            radius,
+            velocity,
+            desired_velocity,
+            inv_horizon_time: 1.0 / params.horizon_time,
+            inv_max_speed: if max_speed > 0.0 {
+                1.0 / max_speed
+            } else {
+                f32::MAX
+            },
+        };
+
+        // Build sampling pattern aligned to desired velocity.
+        let divisions = (params.adaptive_divisions as usize).clamp(1, MAX_PATTERN_DIVISIONS);
+        let rings = (params.adaptive_rings as usize).clamp(1, MAX_PATTERN_RINGS);
+        let depth = params.adaptive_depth.max(1);
+        let (sa, ca) = sin_cos(TAU / divisions as f32);
+        let rotate_cw = |v: Vec2| Vec2::new(v.x * ca + v.y * sa, -v.x * sa + v.y * ca);
+        let rotate_ccw = |v: Vec2| Vec2::new(v.x * ca - v.y * sa, v.x * sa + v.y * ca);
+
+        let direction = desired_velocity.xz().normalize_or_zero();
+        let (sh, ch) = sin_cos(TAU / divisions as f32 * 0.5);
+        let directions = [
+            direction,
+            Vec2::new(
+                direction.x * ch - direction.y * sh,
+                direction.x * sh + direction.y * ch,
+            ),
+        ];
+
+        let mut pattern = Vec::with_capacity(divisions * rings + 1);
+        pattern.push(Vec2::ZERO);
+        for ring in 0..rings {
+            let r = (rings - ring) as f32 / rings as f32;
+            let first = directions[ring % 2] * r;
+            pattern.push(first);
+            let mut last_right = first;
+            let mut last_left = first;
+            for _ in (1..divisions - 1).step_by(2) {
+                // Get next point on the "right" and on the "left".
+                last_right = rotate_cw(last_right);
+                last_left = rotate_ccw(last_left);
+                pattern.push(last_right);
+                pattern.push(last_left);
+            }
+            if divisions.is_multiple_of(2) {
+                pattern.push(rotate_ccw(last_left));
+            }
+        }
+
+        // Start sampling.
+        let mut radius = max_speed * (1.0 - params.velocity_bias);
+        let mut result = Vec3::new(
+            desired_velocity.x * params.velocity_bias,
+            0.0,
+            desired_velocity.z * params.velocity_bias,
+        );
+        for _ in 0..depth {
+            let mut min_penalty = f32::MAX;
+            let mut best_velocity = Vec3::ZERO;
+            for sample in &pattern {
+                let candidate = Vec3::new(
+                    result.x + sample.x * radius,
+                    0.0,
+                    result.z + sample.y * radius,
+                );
+                if candidate.xz().length_squared() > (max_speed + 0.001).squared() {
+                    continue;
+                }
+                let penalty = sampler.process_sample(candidate, min_penalty);
+                if penalty < min_penalty {
+                    min_penalty = penalty;
+                    best_velocity = candidate;
+                }
+            }
+            result = best_velocity;
+            radius *= 0.5;
+        }
+        result
+    }
+
+    /// Precomputes the passing sides of circles and which segments the agent touches.
+    fn prepare(&mut self, position: Vec3, desired_velocity: Vec3) {
+        for circle in &mut self.circles {
+            // Side
+            let dp = (circle.position - position).with_y(0.0).normalize_or_zero();
+            let dv = circle.desired_velocity - desired_velocity;
+            circle.dp = dp;
+            circle.np = if triangle_area_xz(Vec3::ZERO, dp, dv) < 0.01 {
+                Vec3::new(-dp.z, 0.0, dp.x)
+            } else {
+                Vec3::new(dp.z, 0.0, -dp.x)
+            };
+        }
+        for segment in &mut self.segments {
+            // Precalc if the agent is really close to the segment.
+            let (distance, _) =
+                distance_squared_between_point_and_segment_xz(position, (segment.p, segment.q));
+            segment.touch = distance < 0.01.squared();
+        }
+    }
+}
+
+impl Sampler<'_> {
+    /// Returns the penalty of a candidate velocity, or `min_penalty` as soon as it is clear that the candidate is worse.
+    fn process_sample(&self, candidate: Vec3, min_penalty: f32) -> f32 {
+        let params = self.params;
+        // Penalty for straying away from the desired and current velocities.
+        let desired_penalty = params.weight_desired_velocity
+            * candidate.xz().distance(self.desired_velocity.xz())
+            * self.inv_max_speed;
+        let current_penalty = params.weight_current_velocity
+            * candidate.xz().distance(self.velocity.xz())
+            * self.inv_max_speed;
+
+        // Find the threshold hit time to bail out based on the early out penalty.
+        // (see how the penalty is calculated below to understand)
+        let min_penalty_left = min_penalty - desired_penalty - current_penalty;
+        let time_threshold =
+            (params.weight_time_of_impact / min_penalty_left - 0.1) * params.horizon_time;
+        if time_threshold - params.horizon_time > -f32::EPSILON {
+            // Already too much.
+            return min_penalty;
+        }
+
+        // Find the min time of impact and the exit amongst all obstacles.
+        let mut time_of_impact = params.horizon_time;
+        let mut side = 0.0;
+        for circle in &self.query.circles {
+            // RVO
+            let relative_velocity = candidate * 2.0 - self.velocity - circle.velocity;
+
+            // Side
+            side += (circle.dp.xz().dot(relative_velocity.xz()) * 0.5 + 0.5)
+                .min(circle.np.xz().dot(relative_velocity.xz()) * 2.0)
+                .clamp(0.0, 1.0);
+
+            let Some((mut t_min, t_max)) = sweep_circle_circle(
+                self.position,
+                self.radius,
+                relative_velocity,
+                circle.position,
+                circle.radius,
+            ) else {
+                continue;
+            };
+
+            // Handle overlapping obstacles.
+            if t_min < 0.0 && t_max > 0.0 {
+                // Avoid more when overlapped.
+                t_min = -t_min * 0.5;
+            }
+            if t_min >= 0.0 && t_min < time_of_impact {
+                // The closest obstacle is somewhere ahead of us, keep track of nearest obstacle.
+                time_of_impact = t_min;
+                if time_of_impact < time_threshold {
+                    return min_penalty;
+                }
+            }
+        }
+
+        for segment in &self.query.segments {
+            let t_min = if segment.touch {
+                // Special case when the agent is very close to the segment.
+                let direction = segment.q - segment.p;
+                let normal = Vec3::new(-direction.z, 0.0, direction.x);
+                // If the velocity is pointing towards the segment, no collision.
+                if normal.xz().dot(candidate.xz()) < 0.0 {
+                    continue;
+                }
+                // Else immediate collision.
+                0.0
+            } else {
+                let Some(t) = intersect_ray_segment(self.position, candidate, segment.p, segment.q)
+                else {
+                    continue;
+                };
+                t
+            };
+
+            // Avoid less when facing walls.
+            let t_min = t_min * 2.0;
+
+            // The closest obstacle is somewhere ahead of us, keep track of nearest obstacle.
+            if t_min < time_of_impact {
+                time_of_impact = t_min;
+                if time_of_impact < time_threshold {
+                    return min_penalty;
+                }
+            }
+        }
+
+        // Normalize side bias, to prevent it dominating too much.
+        if !self.query.circles.is_empty() {
+            side /= self.query.circles.len() as f32;
+        }
+
+        let side_penalty = params.weight_side * side;
+        let time_penalty =
+            params.weight_time_of_impact * (1.0 / (0.1 + time_of_impact * self.inv_horizon_time));
+        desired_penalty + current_penalty + side_penalty + time_penalty
+    }
+}
+
+/// Collects the solid navmesh edges within `range` of `position` from the polygons found by
+/// [`NavmeshQuery::find_local_neighbourhood`] around `polygon`.
+///
+/// Returns the polygons of the neighbourhood and the walls together with their squared distance to `position`.
+pub(crate) fn navmesh_walls_around(
+    query: &NavmeshQuery,
+    polygon: u16,
+    position: Vec3,
+    range: f32,
+    filter: &(impl PolygonFilter + ?Sized),
+) -> Result<(Vec<u16>, Vec<(f32, (Vec3, Vec3))>), NavmeshQueryError> {
+    let neighbourhood = query.find_local_neighbourhood(polygon, position, range, filter)?;
+    let mut walls = Vec::new();
+    for &polygon in &neighbourhood.polygons {
+        for segment in query.polygon_wall_segments(polygon, filter)? {
+            // Skip too distant segments.
+            let (distance, _) = distance_squared_between_point_and_segment_xz(position, segment);
+            if distance <= range.squared() {
+                walls.push((distance, segment));
+            }
+        }
+    }
+    Ok((neighbourhood.polygons, walls))
+}
+
+/// A half-plane of permitted velocities: those on the left of the directed line through `point`.
+#[derive(Debug, Clone, Copy)]
+struct OrcaLine {
+    point: Vec2,
+    direction: Vec2,
+}
+
+/// Solves a one-dimensional linear program on the line `line` subject to the lines before it and a circular constraint.
+///
+/// Returns `false` if the program is infeasible.
+fn linear_program_1(
+    lines: &[OrcaLine],
+    line: usize,
+    radius: f32,
+    optimization_velocity: Vec2,
+    optimize_direction: bool,
+    result: &mut Vec2,
+) -> bool {
+    const EPS: f32 = 1e-5;
+    let OrcaLine { point, direction } = lines[line];
+    let dot_product = point.dot(direction);
+    let discriminant = dot_product.squared() + radius.squared() - point.length_squared();
+    if discriminant < 0.0 {
+        // Max speed circle fully invalidates line.
+        return false;
+    }
+    let sqrt_discriminant = sqrt(discriminant);
+    let mut t_left = -dot_product - sqrt_discriminant;
+    let mut t_right = -dot_product + sqrt_discriminant;
+
+    for other in &lines[..line] {
+        let denominator = direction.perp_dot(other.direction);
+        let numerator = other.direction.perp_dot(point - other.point);
+        if abs(denominator) <= EPS {
+            // Lines are (almost) parallel.
+            if numerator < 0.0 {
+                return false;
+            }
+            continue;
+        }
+        let t = numerator / denominator;
+        if denominator >= 0.0 {
+            // Line bounds line on the right.
+            t_right = t_right.min(t);
+        } else {
+            // Line bounds line on the left.
+            t_left = t_left.max(t);
+        }
+        if t_left > t_right {
+            return false;
+        }
+    }
+
+    let t = if optimize_direction {
+        // Optimize direction.
+        if optimization_velocity.dot(direction) > 0.0 {
+            t_right
+        } else {
+            t_left
+        }
+    } else {
+        // Optimize closest point.
+        direction
+            .dot(optimization_velocity - point)
+            .clamp(t_left, t_right)
+    };
+    *result = point + t * direction;
+    true
+}
+
+/// Solves a two-dimensional linear program subject to the lines and a circular constraint.
+///
+/// Returns the index of the first line for which the program is infeasible, or the number of lines on success.
+fn linear_program_2(
+    lines: &[OrcaLine],
+    radius: f32,
+    optimization_velocity: Vec2,
+    optimize_direction: bool,
+    result: &mut Vec2,
+) -> usize {
+    *result = if optimize_direction {
+        // Optimize direction. Note that the optimization velocity is of unit length in this case.
+        optimization_velocity * radius
+    } else if optimization_velocity.length_squared() > radius.squared() {
+        // Optimize closest point and outside circle.
+        optimization_velocity.normalize_or_zero() * radius
+    } else {
+        // Optimize closest point and inside circle.
+        optimization_velocity
+    };
+
+    for (i, line) in lines.iter().enumerate() {
+        if line.direction.perp_dot(line.point - *result) > 0.0 {
+            // Result does not satisfy constraint i. Compute new optimal result.
+            let previous = *result;
+            if !linear_program_1(
+                lines,
+                i,
+                radius,
+                optimization_velocity,
+                optimize_direction,
+                result,
+            ) {
+                *result = previous;
+                return i;
+            }
+        }
+    }
+    lines.len()
+}
+
+/// Finds the velocity that minimizes the maximum violation of the agent lines, while satisfying the obstacle lines.
+fn linear_program_3(
+    lines: &[OrcaLine],
+    obstacle_line_count: usize,
+    begin_line: usize,
+    radius: f32,
+    result: &mut Vec2,
+) {
+    const EPS: f32 = 1e-5;
+    let mut distance = 0.0;
+    for i in begin_line..lines.len() {
+        let line = lines[i];
+        if line.direction.perp_dot(line.point - *result) <= distance {
+            continue;
+        }
+        // Result does not satisfy constraint of line i.
+        let mut projected_lines = lines[..obstacle_line_count].to_vec();
+        for other in &lines[obstacle_line_count..i] {
+            let denominator = line.direction.perp_dot(other.direction);
+            let point = if abs(denominator) <= EPS {
+                // Line i and the other line are parallel.
+                if line.direction.dot(other.direction) > 0.0 {
+                    // Lines point in the same direction.
+                    continue;
+                }
+                // Lines point in opposite direction.
+                0.5 * (line.point + other.point)
+            } else {
+                line.point
+                    + (other.direction.perp_dot(line.point - other.point) / denominator)
+                        * line.direction
+            };
+            projected_lines.push(OrcaLine {
+                point,
+                direction: (other.direction - line.direction).normalize_or_zero(),
+            });
+        }
+
+        let previous = *result;
+        let direction = Vec2::new(-line.direction.y, line.direction.x);
+        if linear_program_2(&projected_lines, radius, direction, true, result)
+            < projected_lines.len()
+        {
+            // This should in principle not happen. The result is by definition already in the feasible region of this
+            // linear program. If it fails, it is due to small floating point error, and the current result is kept.
+            *result = previous;
+        }
+        distance = line.direction.perp_dot(line.point - *result);
+    }
+}
+
+/// Returns the times at which a circle at `c0` moving with `v` starts and stops overlapping the static circle at `c1`.
+fn sweep_circle_circle(c0: Vec3, r0: f32, v: Vec3, c1: Vec3, r1: f32) -> Option<(f32, f32)> {
+    const EPS: f32 = 0.0001;
+    let s = (c1 - c0).xz();
+    let r = r0 + r1;
+    let c = s.length_squared() - r * r;
+    let a = v.xz().length_squared();
+    if a < EPS {
+        // Not moving.
+        return None;
+    }
+    // Overlap, calc time to exit.
+    let b = v.xz().dot(s);
+    let d = b * b - a * c;
+    if d < 0.0 {
+        // No intersection.
+        return None;
+    }
+    let a = 1.0 / a;
+    let rd = sqrt(d);
+    Some(((b - rd) * a, (b + rd) * a))
+}
+
+/// Returns the parameter along the ray `ap + u * t` at which it crosses the segment `(bp, bq)`, if it does for `t` in `[0, 1]`.
+fn intersect_ray_segment(ap: Vec3, u: Vec3, bp: Vec3, bq: Vec3) -> Option<f32> {
+    let v = bq - bp;
+    let w = ap - bp;
+    let d = perp_xz(u, v);
+    if abs(d) < 1e-6 {
+        return None;
+    }
+    let d = 1.0 / d;
+    let t = perp_xz(v, w) * d;
+    if !(0.0..=1.0).contains(&t) {
+        return None;
+    }
+    let s = perp_xz(u, w) * d;
+    if !(0.0..=1.0).contains(&s) {
+        return None;
+    }
+    Some(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{QueryFilter, test_meshes};
+
+    #[test]
+    fn free_space_keeps_desired_velocity() {
+        let mut query = ObstacleAvoidanceQuery::new();
+        let desired = Vec3::new(2.0, 0.0, 0.0);
+        let velocity = query.sample_velocity_adaptive(
+            Vec3::ZERO,
+            0.5,
+            2.0,
+            desired,
+            desired,
+            &ObstacleAvoidanceParams::default(),
+        );
+        assert!(velocity.distance(desired) < 0.1, "{velocity}");
+    }
+
+    #[test]
+    fn avoids_oncoming_agent() {
+        let mut query = ObstacleAvoidanceQuery::new();
+        let desired = Vec3::new(2.0, 0.0, 0.0);
+        query.add_circle(Vec3::new(2.0, 0.0, 0.0), 0.5, -desired, -desired);
+        let velocity = query.sample_velocity_adaptive(
+            Vec3::ZERO,
+            0.5,
+            2.0,
+            desired,
+            desired,
+            &ObstacleAvoidanceParams::default(),
+        );
+        assert!(velocity.length() <= 2.0 + 0.001);
+        // The agent swerves to the side instead of running into the other agent.
+        assert!(abs(velocity.z) > 0.1, "{velocity}");
+    }
+
+    #[test]
+    fn orca_avoids_oncoming_agent() {
+        let mut query = ObstacleAvoidanceQuery::new();
+        let desired = Vec3::new(2.0, 0.0, 0.0);
+        query.add_circle(Vec3::new(2.0, 0.0, 0.0), 0.5, -desired, -desired);
+        let params = ObstacleAvoidanceParams {
+            mode: ObstacleAvoidanceMode::Orca,
+            ..Default::default()
+        };
+        let velocity = query.sample_velocity(Vec3::ZERO, 0.5, 2.0, desired, desired, &params);
+        assert!(velocity.length() <= 2.0 + 0.001);
+        assert!(abs(velocity.z) > 0.1, "{velocity}");
+    }
+
+    #[test]
+    fn orca_slows_down_before_navmesh_wall() {
+        let mesh = test_meshes::grid(1, 1);
+        let navmesh = NavmeshQuery::new(&mesh);
+        let position = Vec3::new(5.0, 0.0, 8.0);
+
+        let mut query = ObstacleAvoidanceQuery::new();
+        query
+            .add_navmesh_walls(&navmesh, 0, position, 3.0, &QueryFilter::default())
+            .unwrap();
+        // Only the wall at z = 10 is in range.
+        assert_eq!(query.segment_count(), 1);
+
+        let params = ObstacleAvoidanceParams {
+            mode: ObstacleAvoidanceMode::Orca,
+            ..Default::default()
+        };
+        let desired = Vec3::new(0.0, 0.0, 2.0);
+        let velocity = query.sample_velocity(position, 0.5, 2.0, desired, desired, &params);
+        // The agent may only approach the wall by its distance divided by the time horizon.
+        assert!(
+            velocity.z <= (2.0 - 0.5) / params.horizon_time + 0.001,
+            "{velocity}"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Links between navmesh positions that are not connected by walkable surface, such as jumps, ladders or teleporters.
+
+use glam::{Vec3, Vec3Swizzles as _};
+
+use crate::{AreaType, NavmeshQuery, PolygonFilter, PolygonNavmesh, TileCoord, ops::FloatPow as _};
+
+/// A link between two positions on the navmesh that agents can traverse without walking, e.g. a jump, a ladder or a teleporter.
+///
+/// Connections are passed to the build process through [`Config::off_mesh_connections`](crate::Config::off_mesh_connections)
+/// and snapped to the generated polygons, see [`OffMeshConnection::snap`]. Paths through a connection
+/// are reported by [`NavmeshQuery::find_straight_path`] with [`StraightPathFlags::OFF_MESH_CONNECTION`](crate::StraightPathFlags::OFF_MESH_CONNECTION),
+/// so that gameplay code can e.g. play the matching animation.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct OffMeshConnection {
+    /// The position at which the connection is entered. `[Units: wu]`
+    pub start: Vec3,
+    /// The position at which the connection is exited. `[Units: wu]`
+    pub end: Vec3,
+    /// The distance on the xz-plane within which the end points are snapped to the navmesh. `[Limit: > 0] [Units: wu]`
+    pub radius: f32,
+    /// Whether the connection can also be traversed from [`Self::end`] to [`Self::start`].
+    pub bidirectional: bool,
+    /// The area type of the connection, used by [`QueryFilter`](crate::QueryFilter) to determine its traversal cost.
+    pub area: AreaType,
+    /// The user-defined flags of the connection, used by [`QueryFilter`](crate::QueryFilter) to determine whether it may be traversed.
+    pub flags: u16,
+    /// An arbitrary value identifying the connection, e.g. to look up the animation to play when traversing it.
+    pub user_id: u32,
+}
+
+impl Default for OffMeshConnection {
+    fn default() -> Self {
+        Self {
+            start: Vec3::ZERO,
+            end: Vec3::ZERO,
+            radius: 0.6,
+            bidirectional: true,
+            area: AreaType::DEFAULT_WALKABLE,
+            flags: 1,
+            user_id: 0,
+        }
+    }
+}
+
+/// An [`OffMeshConnection`] whose end points have been snapped to the polygons of a navmesh tile.
+///
+/// A connection that leads into another tile is only snapped at its start. Its end is snapped to the polygons of
+/// [`Self::end_tile`] by [`TiledNavmesh`](crate::TiledNavmesh) once both tiles have been added.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnappedOffMeshConnection {
+    /// The connection as it was placed.
+    pub connection: OffMeshConnection,
+    /// The start position, moved onto [`Self::start_polygon`].
+    pub start: Vec3,
+    /// The end position, moved onto [`Self::end_polygon`].
+    pub end: Vec3,
+    /// The polygon containing [`Self::start`].
+    pub start_polygon: u16,
+    /// The polygon containing [`Self::end`].
+    ///
+    /// [`PolygonNavmesh::NO_INDEX`] if the connection leads into another tile.
+    pub end_polygon: u16,
+    /// The tile the connection leads into, if it ends outside of the tile it starts in.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub end_tile: Option<TileCoord>,
+}
+
+impl OffMeshConnection {
+    /// Snaps the end points of the connection to the nearest polygons of the query's mesh.
+    ///
+    /// Each end point must lie within [`Self::radius`] of a polygon on the xz-plane and within `max_climb` of it vertically.
+    /// Returns `None` if an end point cannot be snapped, or if both end points snap to the same polygon.
+    /// Polygons are considered regardless of their flags.
+    pub fn snap(&self, query: &NavmeshQuery, max_climb: f32) -> Option<SnappedOffMeshConnection> {
+        let (start_polygon, start) = self.snap_point(self.start, query, max_climb)?;
+        let (end_polygon, end) = self.snap_point(self.end, query, max_climb)?;
+        (start_polygon != end_polygon).then(|| SnappedOffMeshConnection {
+            connection: self.clone(),
+            start,
+            end,
+            start_polygon,
+            end_polygon,
+            end_tile: None,
+        })
+    }
+
+    /// Snaps only the start of a connection that leads into the tile `end_tile`, see [`SnappedOffMeshConnection::end_tile`].
+    pub(crate) fn snap_start(
+        &self,
+        query: &NavmeshQuery,
+        max_climb: f32,
+        end_tile: TileCoord,
+    ) -> Option<SnappedOffMeshConnection> {
+        let (start_polygon, start) = self.snap_point(self.start, query, max_climb)?;
+        Some(SnappedOffMeshConnection {
+            connection: self.clone(),
+            start,
+            end: self.end,
+            start_polygon,
+            end_polygon: PolygonNavmesh::NO_INDEX,
+            end_tile: Some(end_tile),
+        })
+    }
+
+    /// Snaps one end point of the connection to the nearest polygon of the query's mesh, see [`Self::snap`].
+    pub(crate) fn snap_point(
+        &self,
+        position: Vec3,
+        query: &NavmeshQuery,
+        max_climb: f32,
+    ) -> Option<(u16, Vec3)> {
+        let half_extents = Vec3::new(self.radius, max_climb, self.radius);
+        let (polygon, nearest) = query.find_nearest_polygon(position, half_extents, &AnyPolygon)?;
+        (nearest.xz().distance_squared(position.xz()) <= self.radius.squared())
+            .then_some((polygon, nearest))
+    }
+}
+
+impl SnappedOffMeshConnection {
+    /// Returns the positions at which the connection is entered and exited when coming from `from`,
+    /// or `None` if the connection cannot be entered from `from`.
+    pub fn end_points_from(&self, from: u16) -> Option<(Vec3, Vec3)> {
+        if from == self.start_polygon {
+            Some((self.start, self.end))
+        } else if self.connection.bidirectional && from == self.end_polygon {
+            Some((self.end, self.start))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the position at which the connection is exited into `to`, or `None` if it does not lead to `to`.
+    pub(crate) fn exit_point(&self, to: u16) -> Option<Vec3> {
+        if to == self.end_polygon {
+            Some(self.end)
+        } else if self.connection.bidirectional && to == self.start_polygon {
+            Some(self.start)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the polygon the connection leads to when entered from `from`.
+    pub(crate) fn exit_polygon(&self, from: u16) -> u16 {
+        if from == self.start_polygon {
+            self.end_polygon
+        } else {
+            self.start_polygon
+        }
+    }
+}
+
+/// Lets every polygon pass, so that connections are snapped independently of polygon flags.
+struct AnyPolygon;
+
+impl PolygonFilter for AnyPolygon {
+    fn pass_filter(&self, _polygon: u16, _flags: u16, _area: AreaType) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PolygonNavmesh, QueryFilter, StraightPathFlags, test_meshes};
+    use alloc::vec::Vec;
+
+    /// Two disconnected 10x10 squares with a gap of 10 between them along x.
+    fn gap_mesh() -> PolygonNavmesh {
+        test_meshes::grid_where(3, 1, |x, _| x != 1)
+    }
+
+    #[test]
+    fn snaps_end_points_within_radius() {
+        let mesh = gap_mesh();
+        let query = NavmeshQuery::new(&mesh);
+        let connection = OffMeshConnection {
+            start: Vec3::new(10.5, 0.5, 5.0),
+            end: Vec3::new(20.0, 0.0, 5.0),
+            radius: 1.0,
+            ..Default::default()
+        };
+        let snapped = connection.snap(&query, 1.0).unwrap();
+        assert_eq!((snapped.start_polygon, snapped.end_polygon), (0, 1));
+        assert_eq!(snapped.start, Vec3::new(10.0, 0.0, 5.0));
+
+        let too_far = OffMeshConnection {
+            end: Vec3::new(15.0, 0.0, 5.0),
+            ..connection
+        };
+        assert_eq!(too_far.snap(&query, 1.0), None);
+    }
+
+    #[test]
+    fn paths_cross_off_mesh_connections() {
+        let mesh = gap_mesh();
+        let connection = OffMeshConnection {
+            start: Vec3::new(9.0, 0.0, 5.0),
+            end: Vec3::new(21.0, 0.0, 5.0),
+            bidirectional: false,
+            user_id: 7,
+            ..Default::default()
+        };
+        let connections = [connection.snap(&NavmeshQuery::new(&mesh), 1.0).unwrap()];
+        let query = NavmeshQuery::new(&mesh).with_off_mesh_connections(&connections);
+        let filter = QueryFilter::default();
+        let (start, end) = (Vec3::new(2.0, 0.0, 5.0), Vec3::new(28.0, 0.0, 5.0));
+
+        let result = query.find_path(0, 1, start, end, &filter).unwrap();
+        assert_eq!(result.path, [0, 2, 1]);
+        assert!(!result.is_partial);
+        assert_eq!(query.off_mesh_connection(2).unwrap().connection.user_id, 7);
+
+        let path = query
+            .find_straight_path(start, end, &result.path, 16)
+            .unwrap();
+        let positions: Vec<_> = path.iter().map(|vertex| vertex.position).collect();
+        assert_eq!(positions, [start, connection.start, connection.end, end]);
+        assert_eq!(path[1].flags, StraightPathFlags::OFF_MESH_CONNECTION);
+        assert_eq!(path[1].polygon, Some(2));
+
+        // The connection only leads one way.
+        assert!(
+            query
+                .find_path(1, 0, end, start, &filter)
+                .unwrap()
+                .is_partial
+        );
+    }
+}
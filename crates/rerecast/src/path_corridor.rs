@@ -0,0 +1,497 @@
+//! Incremental maintenance of the polygon path of a moving agent, modelled after Detour's `dtPathCorridor`.
+
+use alloc::vec::Vec;
+use glam::{Vec3, Vec3Swizzles as _};
+
+use crate::{
+    NavmeshQuery, NavmeshQueryError, PolyRef, PolygonFilter, SlicedFindPathStatus,
+    StraightPathFlags, StraightPathVertex, ops::FloatPow as _,
+};
+
+/// The polygon path of an agent from its current position to its target.
+///
+/// Instead of searching a new path every frame, the corridor is adjusted as the agent and its target move.
+/// Small movements are applied with [`PathCorridor::move_position`] and [`PathCorridor::move_target`],
+/// and the path is shortened over time with [`PathCorridor::optimize_path_visibility`] and
+/// [`PathCorridor::optimize_path_topology`]. The agent steers towards the corners returned by [`PathCorridor::find_corners`].
+///
+/// The path is stored as [`PolyRef`]s, so it survives across queries, and [`PathCorridor::is_valid`] detects when a tile
+/// it passes through has been replaced in the [`TiledNavmesh`](crate::TiledNavmesh). The path is then usually replanned.
+/// The first polygon of the path always contains the agent's position and the last polygon contains the target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathCorridor {
+    position: Vec3,
+    target: Vec3,
+    path: Vec<PolyRef>,
+    max_path: usize,
+}
+
+impl Default for PathCorridor {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX_PATH)
+    }
+}
+
+impl PathCorridor {
+    /// The maximum length of the path, unless specified otherwise.
+    pub const DEFAULT_MAX_PATH: usize = 256;
+
+    /// Creates an empty corridor whose path holds at most `max_path` polygons.
+    pub fn new(max_path: usize) -> Self {
+        Self {
+            position: Vec3::ZERO,
+            target: Vec3::ZERO,
+            path: Vec::new(),
+            max_path: max_path.max(1),
+        }
+    }
+
+    /// Resets the corridor to a single polygon, with both position and target at `position`.
+    pub fn reset(&mut self, polygon: PolyRef, position: Vec3) {
+        self.position = position;
+        self.target = position;
+        self.path.clear();
+        self.path.push(polygon);
+    }
+
+    /// Replaces the path of the corridor, e.g. with a newly searched path.
+    ///
+    /// The path must start at the polygon containing the current position and end at the polygon containing `target`.
+    pub fn set_corridor(&mut self, target: Vec3, path: &[PolyRef]) {
+        self.target = target;
+        self.path.clear();
+        self.path
+            .extend_from_slice(&path[..path.len().min(self.max_path)]);
+    }
+
+    /// The current position of the agent.
+    #[inline]
+    pub fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    /// The current target of the agent.
+    #[inline]
+    pub fn target(&self) -> Vec3 {
+        self.target
+    }
+
+    /// The polygons from the agent's position to its target.
+    #[inline]
+    pub fn path(&self) -> &[PolyRef] {
+        &self.path
+    }
+
+    /// The polygon containing the agent's position, or [`PolyRef::NULL`] if the path is empty.
+    #[inline]
+    pub fn first_polygon(&self) -> PolyRef {
+        self.path.first().copied().unwrap_or_default()
+    }
+
+    /// The polygon containing the target, or [`PolyRef::NULL`] if the path is empty.
+    #[inline]
+    pub fn last_polygon(&self) -> PolyRef {
+        self.path.last().copied().unwrap_or_default()
+    }
+
+    /// Returns whether the first `max_look_ahead` polygons of the path still exist and pass the filter.
+    ///
+    /// Polygons fail this check once their tile has been replaced or removed, or if the query belongs to another tile.
+    pub fn is_valid(
+        &self,
+        max_look_ahead: usize,
+        query: &NavmeshQuery,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> bool {
+        self.path.iter().take(max_look_ahead).all(|poly_ref| {
+            query
+                .polygon_by_ref(*poly_ref)
+                .is_ok_and(|polygon| query.passes_filter(polygon, filter))
+        })
+    }
+
+    /// Finds up to `max_corners` corners the agent should steer towards, in order.
+    ///
+    /// Corners that the agent has already reached are skipped. The last corner is the target
+    /// if it is among the returned corners, which is indicated by [`StraightPathFlags::END`].
+    /// Corners after the start of an off-mesh connection are not returned, see [`PathCorridor::move_over_off_mesh_connection`].
+    pub fn find_corners(
+        &self,
+        max_corners: usize,
+        query: &NavmeshQuery,
+    ) -> Result<Vec<StraightPathVertex>, NavmeshQueryError> {
+        const MIN_TARGET_DISTANCE: f32 = 0.01;
+
+        let path = self.local_path(query)?;
+        // One extra vertex for the start, which is usually pruned.
+        let mut corners =
+            query.find_straight_path(self.position, self.target, &path, max_corners + 1)?;
+
+        // Prune points in the beginning of the path which are too close,
+        // but stop at an off-mesh connection, which the agent still has to trigger.
+        let too_close = corners
+            .iter()
+            .take_while(|corner| {
+                !corner
+                    .flags
+                    .contains(StraightPathFlags::OFF_MESH_CONNECTION)
+                    && corner.position.xz().distance_squared(self.position.xz())
+                        <= MIN_TARGET_DISTANCE.squared()
+            })
+            .count();
+        corners.drain(..too_close);
+
+        // Prune points after an off-mesh connection.
+        if let Some(i) = corners.iter().position(|corner| {
+            corner
+                .flags
+                .contains(StraightPathFlags::OFF_MESH_CONNECTION)
+        }) {
+            corners.truncate(i + 1);
+        }
+        corners.truncate(max_corners);
+        Ok(corners)
+    }
+
+    /// Shortens the start of the path if `next`, usually the next corner, is directly visible from the current position.
+    ///
+    /// A ray of at most `path_optimization_range` is cast towards `next`, and the polygons it crosses replace the start of the path.
+    /// This is cheap enough to be called every frame and smooths the path where the navmesh was split into many small polygons.
+    pub fn optimize_path_visibility(
+        &mut self,
+        next: Vec3,
+        path_optimization_range: f32,
+        query: &NavmeshQuery,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Result<(), NavmeshQueryError> {
+        // Clamp the ray to max distance.
+        let distance = self.position.xz().distance(next.xz());
+        // If too close to the goal, do not try to optimize.
+        if distance < 0.01 {
+            return Ok(());
+        }
+        // Overshoot a little. This helps to optimize open fields in tiled meshes.
+        let distance = (distance + 0.01).min(path_optimization_range);
+        let goal = self.position + (next - self.position) * (path_optimization_range / distance);
+
+        let start = query.polygon_by_ref(self.first_polygon())?;
+        let hit = query.raycast(start, self.position, goal, filter)?;
+        if hit.path.len() > 1 && hit.t > 0.99 {
+            let visited: Vec<_> = hit.path.iter().map(|p| query.poly_ref(*p)).collect();
+            merge_corridor_start_shortcut(&mut self.path, &visited, self.max_path);
+        }
+        Ok(())
+    }
+
+    /// Shortens the path by searching a cheaper route through the nearby polygons with a small, bounded path search.
+    ///
+    /// This is more expensive than [`PathCorridor::optimize_path_visibility`] and should be called only occasionally,
+    /// e.g. every few hundred milliseconds. Returns whether the path was changed.
+    pub fn optimize_path_topology(
+        &mut self,
+        query: &NavmeshQuery,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Result<bool, NavmeshQueryError> {
+        const MAX_ITERATIONS: usize = 32;
+        const MAX_RESULTS: usize = 32;
+
+        if self.path.len() < 3 {
+            return Ok(false);
+        }
+        let path = self.local_path(query)?;
+        let mut search = query.init_sliced_find_path(
+            path[0],
+            path[path.len() - 1],
+            self.position,
+            self.target,
+            filter,
+            NavmeshQuery::DEFAULT_MAX_NODES,
+        )?;
+        if query.update_sliced_find_path(&mut search, MAX_ITERATIONS).0
+            == SlicedFindPathStatus::Failure
+        {
+            return Ok(false);
+        }
+        let result = query.finalize_sliced_find_path_partial(search, &path)?;
+        let shortcut: Vec<_> = result
+            .path
+            .iter()
+            .take(MAX_RESULTS)
+            .map(|p| query.poly_ref(*p))
+            .collect();
+        let length = self.path.len();
+        merge_corridor_start_shortcut(&mut self.path, &shortcut, self.max_path);
+        Ok(self.path.len() != length)
+    }
+
+    /// Moves the agent towards `new_position`, constrained to the navmesh, and updates the start of the path.
+    ///
+    /// This is meant for small, per-frame displacements. If the agent leaves the corridor, e.g. because it was pushed,
+    /// the polygons back to the corridor are prepended to the path.
+    pub fn move_position(
+        &mut self,
+        new_position: Vec3,
+        query: &NavmeshQuery,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Result<(), NavmeshQueryError> {
+        let start = query.polygon_by_ref(self.first_polygon())?;
+        let result = query.move_along_surface(start, self.position, new_position, filter)?;
+        let visited: Vec<_> = result.visited.iter().map(|p| query.poly_ref(*p)).collect();
+        merge_corridor_start_moved(&mut self.path, &visited, self.max_path);
+        self.position = result.position;
+        Ok(())
+    }
+
+    /// Moves the target towards `new_target`, constrained to the navmesh, and updates the end of the path.
+    ///
+    /// This is meant for small displacements, e.g. when following a moving agent. Larger changes require a new path search.
+    pub fn move_target(
+        &mut self,
+        new_target: Vec3,
+        query: &NavmeshQuery,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Result<(), NavmeshQueryError> {
+        let end = query.polygon_by_ref(self.last_polygon())?;
+        let result = query.move_along_surface(end, self.target, new_target, filter)?;
+        let visited: Vec<_> = result.visited.iter().map(|p| query.poly_ref(*p)).collect();
+        merge_corridor_end_moved(&mut self.path, &visited, self.max_path);
+        self.target = result.position;
+        Ok(())
+    }
+
+    /// Advances the path over the off-mesh connection `connection` and moves the position to its exit.
+    ///
+    /// This is called once the agent reaches the start of the connection, usually the last corner returned by
+    /// [`PathCorridor::find_corners`]. Returns the positions at which the connection is entered and exited,
+    /// so that the caller can animate the agent along the connection.
+    pub fn move_over_off_mesh_connection(
+        &mut self,
+        connection: PolyRef,
+        query: &NavmeshQuery,
+    ) -> Result<(Vec3, Vec3), NavmeshQueryError> {
+        // The connection must be entered from and lead to polygons of the path.
+        let index = self
+            .path
+            .iter()
+            .position(|p| *p == connection)
+            .filter(|i| *i > 0 && *i + 1 < self.path.len())
+            .ok_or(NavmeshQueryError::InvalidPolyRef(connection))?;
+        let from = query.polygon_by_ref(self.path[index - 1])?;
+        let (start, end) = query
+            .off_mesh_connection_end_points(from, query.polygon_by_ref(connection)?)
+            .ok_or(NavmeshQueryError::InvalidPolyRef(connection))?;
+        // Prune the path up to and including the connection.
+        self.path.drain(..=index);
+        self.position = end;
+        Ok((start, end))
+    }
+
+    /// Moves the agent to `safe_position` on `safe_polygon`, which replaces the first polygon of the path.
+    ///
+    /// This is used to recover an agent whose first polygon has become invalid. The safe polygon is not necessarily connected
+    /// to the rest of the path, so the path should be replanned afterwards.
+    pub fn fix_path_start(&mut self, safe_polygon: PolyRef, safe_position: Vec3) {
+        self.position = safe_position;
+        match self.path.first_mut() {
+            Some(first) => *first = safe_polygon,
+            None => self.path.push(safe_polygon),
+        }
+    }
+
+    /// Converts the path to the polygon indices of the query.
+    fn local_path(&self, query: &NavmeshQuery) -> Result<Vec<u16>, NavmeshQueryError> {
+        if self.path.is_empty() {
+            return Err(NavmeshQueryError::InvalidPolyRef(PolyRef::NULL));
+        }
+        self.path.iter().map(|p| query.polygon_by_ref(*p)).collect()
+    }
+}
+
+/// Returns the indices of the furthest polygon of `path` that also occurs in `visited`, and of its first occurrence in `visited`.
+fn furthest_common_polygon(
+    mut path_indices: impl Iterator<Item = usize>,
+    path: &[PolyRef],
+    visited: &[PolyRef],
+) -> Option<(usize, usize)> {
+    path_indices.find_map(|i| {
+        let j = visited.iter().position(|v| *v == path[i])?;
+        Some((i, j))
+    })
+}
+
+/// Replaces the start of the path with the polygons visited while moving the agent, which start at the old first polygon.
+fn merge_corridor_start_moved(path: &mut Vec<PolyRef>, visited: &[PolyRef], max_path: usize) {
+    let Some((furthest_path, furthest_visited)) =
+        furthest_common_polygon((0..path.len()).rev(), path, visited)
+    else {
+        return;
+    };
+    // The agent has to go back from the polygon it is on to the furthest common polygon.
+    let mut merged: Vec<_> = visited[furthest_visited..].iter().rev().copied().collect();
+    merged.extend_from_slice(&path[furthest_path + 1..]);
+    merged.truncate(max_path);
+    *path = merged;
+}
+
+/// Replaces the end of the path with the polygons visited while moving the target, which start at the old last polygon.
+fn merge_corridor_end_moved(path: &mut Vec<PolyRef>, visited: &[PolyRef], max_path: usize) {
+    let Some((furthest_path, furthest_visited)) =
+        furthest_common_polygon(0..path.len(), path, visited)
+    else {
+        return;
+    };
+    path.truncate(furthest_path + 1);
+    let count = (visited.len() - furthest_visited - 1).min(max_path.saturating_sub(path.len()));
+    path.extend_from_slice(&visited[furthest_visited + 1..][..count]);
+}
+
+/// Replaces the start of the path with a shortcut that starts at the first polygon and joins the path later on.
+fn merge_corridor_start_shortcut(path: &mut Vec<PolyRef>, visited: &[PolyRef], max_path: usize) {
+    let Some((furthest_path, furthest_visited)) =
+        furthest_common_polygon((0..path.len()).rev(), path, visited)
+    else {
+        return;
+    };
+    if furthest_visited == 0 {
+        return;
+    }
+    let mut merged = visited[..furthest_visited].to_vec();
+    merged.extend_from_slice(&path[furthest_path..]);
+    merged.truncate(max_path);
+    *path = merged;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OffMeshConnection, QueryFilter, TileCoord, TiledNavmesh, test_meshes};
+
+    fn refs(query: &NavmeshQuery, polygons: &[u16]) -> Vec<PolyRef> {
+        polygons.iter().map(|p| query.poly_ref(*p)).collect()
+    }
+
+    #[test]
+    fn moving_updates_path_ends() {
+        let mesh = test_meshes::grid(3, 2);
+        let query = NavmeshQuery::new(&mesh);
+        let filter = QueryFilter::default();
+        let mut corridor = PathCorridor::default();
+        corridor.reset(query.poly_ref(0), Vec3::new(5.0, 0.0, 5.0));
+        corridor.set_corridor(Vec3::new(25.0, 0.0, 5.0), &refs(&query, &[0, 1, 2]));
+
+        corridor
+            .move_position(Vec3::new(15.0, 0.0, 5.0), &query, &filter)
+            .unwrap();
+        assert_eq!(corridor.path(), refs(&query, &[1, 2]));
+
+        // Pushed off the corridor, the agent has to walk back.
+        corridor
+            .move_position(Vec3::new(15.0, 0.0, 15.0), &query, &filter)
+            .unwrap();
+        assert_eq!(corridor.path(), refs(&query, &[4, 1, 2]));
+
+        corridor
+            .move_target(Vec3::new(25.0, 0.0, 15.0), &query, &filter)
+            .unwrap();
+        assert_eq!(corridor.path(), refs(&query, &[4, 1, 2, 5]));
+        assert_eq!(corridor.target(), Vec3::new(25.0, 0.0, 15.0));
+        assert!(corridor.is_valid(usize::MAX, &query, &filter));
+    }
+
+    #[test]
+    fn optimizations_shorten_detours() {
+        let mesh = test_meshes::grid(3, 2);
+        let query = NavmeshQuery::new(&mesh);
+        let filter = QueryFilter::default();
+        let mut corridor = PathCorridor::default();
+        corridor.reset(query.poly_ref(0), Vec3::new(5.0, 0.0, 5.0));
+        corridor.set_corridor(Vec3::new(25.0, 0.0, 5.0), &refs(&query, &[0, 3, 4, 1, 2]));
+
+        assert!(corridor.optimize_path_topology(&query, &filter).unwrap());
+        assert_eq!(corridor.path(), refs(&query, &[0, 1, 2]));
+
+        corridor.set_corridor(Vec3::new(25.0, 0.0, 5.0), &refs(&query, &[0, 3, 4, 1, 2]));
+        corridor
+            .optimize_path_visibility(Vec3::new(25.0, 0.0, 5.0), 20.0, &query, &filter)
+            .unwrap();
+        assert_eq!(corridor.path(), refs(&query, &[0, 1, 2]));
+
+        let corners = corridor.find_corners(4, &query).unwrap();
+        assert_eq!(corners.len(), 1);
+        assert_eq!(corners[0].position, Vec3::new(25.0, 0.0, 5.0));
+        assert!(corners[0].flags.contains(StraightPathFlags::END));
+    }
+
+    #[test]
+    fn keeps_off_mesh_connection_at_agent_position() {
+        let mesh = test_meshes::grid_where(3, 1, |x, _| x != 1);
+        let connection = OffMeshConnection {
+            start: Vec3::new(10.0, 0.0, 5.0),
+            end: Vec3::new(20.0, 0.0, 5.0),
+            ..Default::default()
+        };
+        let connections = [connection.snap(&NavmeshQuery::new(&mesh), 1.0).unwrap()];
+        let query = NavmeshQuery::new(&mesh).with_off_mesh_connections(&connections);
+        let mut corridor = PathCorridor::default();
+        // The agent is closer to the start of the connection than corners are usually pruned at.
+        corridor.reset(query.poly_ref(0), Vec3::new(9.995, 0.0, 5.0));
+        corridor.set_corridor(Vec3::new(25.0, 0.0, 5.0), &refs(&query, &[0, 2, 1]));
+
+        let corners = corridor.find_corners(4, &query).unwrap();
+        assert_eq!(corners.len(), 1);
+        assert_eq!(corners[0].position, connection.start);
+        assert!(
+            corners[0]
+                .flags
+                .contains(StraightPathFlags::OFF_MESH_CONNECTION)
+        );
+    }
+
+    #[test]
+    fn stale_references_invalidate_the_corridor() {
+        let mesh = test_meshes::grid(3, 2);
+        let query = NavmeshQuery::new(&mesh);
+        let filter = QueryFilter::default();
+        let mut corridor = PathCorridor::default();
+        corridor.reset(query.poly_ref(0), Vec3::new(5.0, 0.0, 5.0));
+        corridor.set_corridor(Vec3::new(25.0, 0.0, 5.0), &refs(&query, &[0, 1, 2]));
+
+        // The same tile with a new salt, as after regenerating it.
+        let query = NavmeshQuery::new(&mesh).with_tile_ref(PolyRef::new(2, 0, 0));
+        assert!(!corridor.is_valid(usize::MAX, &query, &filter));
+        assert!(corridor.find_corners(4, &query).is_err());
+
+        corridor.fix_path_start(query.poly_ref(0), Vec3::new(5.0, 0.0, 5.0));
+        assert!(corridor.is_valid(1, &query, &filter));
+    }
+
+    #[test]
+    fn corridor_crosses_tile_borders() {
+        let navmesh = TiledNavmesh::from_tiles(
+            (0..3).map(|x| test_meshes::grid_tile(TileCoord { x, z: 0 }, 1, 1)),
+        );
+        let tile_refs: Vec<_> = (0..3)
+            .map(|x| navmesh.tile_ref_at(TileCoord { x, z: 0 }).unwrap())
+            .collect();
+        let query = navmesh.query();
+        let filter = QueryFilter::default();
+        let mut corridor = PathCorridor::default();
+        corridor.reset(tile_refs[0], Vec3::new(5.0, 0.0, 5.0));
+        let target = Vec3::new(25.0, 0.0, 5.0);
+        let end = query.polygon_by_ref(tile_refs[2]).unwrap();
+        let path = query
+            .find_path(0, end, corridor.position(), target, &filter)
+            .unwrap()
+            .path;
+        corridor.set_corridor(target, &refs(&query, &path));
+        assert_eq!(corridor.path(), tile_refs);
+
+        corridor
+            .move_position(Vec3::new(15.0, 0.0, 5.0), &query, &filter)
+            .unwrap();
+        assert_eq!(corridor.path(), &tile_refs[1..]);
+        let corners = corridor.find_corners(4, &query).unwrap();
+        assert_eq!(corners.len(), 1);
+        assert_eq!(corners[0].position, target);
+    }
+}
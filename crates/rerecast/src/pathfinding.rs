@@ -0,0 +1,541 @@
+//! A* pathfinding over the polygons of a [`NavmeshQuery`], either in one go or sliced across multiple frames.
+
+use alloc::vec::Vec;
+use glam::Vec3;
+
+use crate::{
+    NavmeshQuery, NavmeshQueryError, PolyRef, PolygonFilter, QueryFilter,
+    node_pool::{NodePool, NodeState, OpenList},
+};
+
+/// Scales the A* heuristic slightly below the real distance to keep it admissible.
+const HEURISTIC_SCALE: f32 = 0.999;
+
+/// The result of a path search.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FindPathResult {
+    /// The polygons from the start polygon to the end polygon, in order.
+    ///
+    /// If the end polygon could not be reached, the path leads to the polygon closest to the end position instead.
+    pub path: Vec<u16>,
+    /// Whether the path does not reach the end polygon, either because it is not connected to the start polygon
+    /// or because the search ran out of nodes.
+    pub is_partial: bool,
+}
+
+/// The state of a [`SlicedFindPath`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlicedFindPathStatus {
+    /// The search needs more calls to [`NavmeshQuery::update_sliced_find_path`].
+    InProgress,
+    /// The search is done and can be finalized with [`NavmeshQuery::finalize_sliced_find_path`].
+    Success,
+    /// The search failed, e.g. because a polygon it visited disappeared between updates.
+    Failure,
+}
+
+/// An A* path search that can be advanced across multiple frames with a fixed iteration budget.
+///
+/// Create it with [`NavmeshQuery::init_sliced_find_path`], advance it with [`NavmeshQuery::update_sliced_find_path`]
+/// and retrieve the path with [`NavmeshQuery::finalize_sliced_find_path`].
+///
+/// The search does not borrow the navmesh, so the navmesh may change between updates, e.g. when tiles are regenerated,
+/// and each update may use a new query. The search tracks the polygons it visited by their [`PolyRef`]s.
+/// If a tile the search visited is replaced or removed in the meantime, or a polygon the search is expanding from disappears,
+/// the search fails instead of producing a broken path.
+#[derive(Debug, Clone)]
+pub struct SlicedFindPath<F = QueryFilter> {
+    pool: NodePool<PolyRef>,
+    open_list: OpenList,
+    start_polygon: PolyRef,
+    end_polygon: PolyRef,
+    end: Vec3,
+    filter: F,
+    status: SlicedFindPathStatus,
+    last_best_node: usize,
+    last_best_node_cost: f32,
+}
+
+impl<F> SlicedFindPath<F> {
+    /// The current state of the search.
+    #[inline]
+    pub fn status(&self) -> SlicedFindPathStatus {
+        self.status
+    }
+
+    /// The polygon the search started at.
+    #[inline]
+    pub fn start_polygon(&self) -> PolyRef {
+        self.start_polygon
+    }
+
+    /// The polygon the search is looking for.
+    #[inline]
+    pub fn end_polygon(&self) -> PolyRef {
+        self.end_polygon
+    }
+
+    /// The filter the search uses.
+    #[inline]
+    pub fn filter(&self) -> &F {
+        &self.filter
+    }
+}
+
+impl NavmeshQuery<'_> {
+    /// Finds a path of polygons from `start_polygon` to `end_polygon` using A*.
+    ///
+    /// `start` and `end` are the positions inside the respective polygons and are used to calculate the path cost.
+    /// If the end polygon cannot be reached, the path leads to the reachable polygon that is closest to `end`
+    /// and [`FindPathResult::is_partial`] is set.
+    pub fn find_path(
+        &self,
+        start_polygon: u16,
+        end_polygon: u16,
+        start: Vec3,
+        end: Vec3,
+        filter: &(impl PolygonFilter + ?Sized),
+    ) -> Result<FindPathResult, NavmeshQueryError> {
+        let mut search = self.init_sliced_find_path(
+            start_polygon,
+            end_polygon,
+            start,
+            end,
+            filter,
+            Self::DEFAULT_MAX_NODES,
+        )?;
+        self.update_sliced_find_path(&mut search, usize::MAX);
+        self.finalize_sliced_find_path(search)
+    }
+
+    /// Starts a sliced path search from `start_polygon` to `end_polygon`.
+    ///
+    /// The search visits at most `max_nodes` polygons, which bounds its memory usage.
+    /// If the budget is exhausted, the search returns a partial path towards the end position.
+    ///
+    /// The search keeps `filter` until it is finalized. Pass a reference to share a filter between searches.
+    pub fn init_sliced_find_path<F: PolygonFilter>(
+        &self,
+        start_polygon: u16,
+        end_polygon: u16,
+        start: Vec3,
+        end: Vec3,
+        filter: F,
+        max_nodes: usize,
+    ) -> Result<SlicedFindPath<F>, NavmeshQueryError> {
+        for polygon in [start_polygon, end_polygon] {
+            if !self.is_valid_polygon(polygon) {
+                return Err(NavmeshQueryError::InvalidPolygon(polygon));
+            }
+        }
+        if !start.is_finite() || !end.is_finite() {
+            return Err(NavmeshQueryError::InvalidPosition);
+        }
+
+        let mut pool = NodePool::new(max_nodes.max(1));
+        let mut open_list = OpenList::default();
+        let start_node = pool.get_or_insert(self.poly_ref(start_polygon)).unwrap();
+        let total = start.distance(end) * HEURISTIC_SCALE;
+        let node = &mut pool[start_node];
+        node.position = start;
+        node.total = total;
+        node.state = NodeState::Open;
+        open_list.push(&pool, start_node);
+
+        Ok(SlicedFindPath {
+            pool,
+            open_list,
+            start_polygon: self.poly_ref(start_polygon),
+            end_polygon: self.poly_ref(end_polygon),
+            end,
+            filter,
+            status: if start_polygon == end_polygon {
+                SlicedFindPathStatus::Success
+            } else {
+                SlicedFindPathStatus::InProgress
+            },
+            last_best_node: start_node,
+            last_best_node_cost: total,
+        })
+    }
+
+    /// Advances a sliced path search by at most `max_iterations` expanded polygons.
+    ///
+    /// Returns the new status of the search and the number of iterations that were performed,
+    /// so that a fixed per-frame budget can be shared between multiple searches.
+    pub fn update_sliced_find_path<F: PolygonFilter>(
+        &self,
+        search: &mut SlicedFindPath<F>,
+        max_iterations: usize,
+    ) -> (SlicedFindPathStatus, usize) {
+        if search.status != SlicedFindPathStatus::InProgress {
+            return (search.status, 0);
+        }
+        let pool = &mut search.pool;
+
+        let mut iterations = 0;
+        while iterations < max_iterations {
+            let Some(best_node) = search.open_list.pop(pool) else {
+                break;
+            };
+            iterations += 1;
+            pool[best_node].state = NodeState::Closed;
+
+            // The polygon or its parent has disappeared during the sliced query, fail.
+            let best_ref = pool[best_node].polygon;
+            let parent = pool[best_node]
+                .parent
+                .map(|parent| self.polygon_by_ref(pool[parent].polygon))
+                .transpose();
+            let (Ok(best), Ok(parent)) = (self.polygon_by_ref(best_ref), parent) else {
+                search.status = SlicedFindPathStatus::Failure;
+                return (search.status, iterations);
+            };
+
+            // Reached the goal, stop searching.
+            if best_ref == search.end_polygon {
+                search.last_best_node = best_node;
+                search.status = SlicedFindPathStatus::Success;
+                return (search.status, iterations);
+            }
+
+            for neighbor in self.graph_neighbors(best, parent) {
+                if Some(neighbor) == parent || !self.passes_filter(neighbor, &search.filter) {
+                    continue;
+                }
+
+                let neighbor_ref = self.poly_ref(neighbor);
+                let Some(neighbor_node) = pool.get_or_insert(neighbor_ref) else {
+                    // Out of nodes, the path will be partial.
+                    continue;
+                };
+
+                // If the node is visited the first time, calculate node position.
+                if pool[neighbor_node].state == NodeState::New
+                    && let Some((a, b)) = self.portal_points(best, neighbor)
+                {
+                    pool[neighbor_node].position = a.lerp(b, 0.5);
+                }
+                let position = pool[neighbor_node].position;
+
+                // Calculate cost and heuristic.
+                let current_cost =
+                    self.traversal_cost(best, pool[best_node].position, position, &search.filter);
+                let (cost, heuristic) = if neighbor_ref == search.end_polygon {
+                    // Special case for last node.
+                    let end_cost =
+                        self.traversal_cost(neighbor, position, search.end, &search.filter);
+                    (pool[best_node].cost + current_cost + end_cost, 0.0)
+                } else {
+                    (
+                        pool[best_node].cost + current_cost,
+                        position.distance(search.end) * HEURISTIC_SCALE,
+                    )
+                };
+                let total = cost + heuristic;
+
+                // The node is already visited and processed, and the new result is worse, skip.
+                if pool[neighbor_node].state != NodeState::New && total >= pool[neighbor_node].total
+                {
+                    continue;
+                }
+
+                // Add or update the node.
+                let node = &mut pool[neighbor_node];
+                node.parent = Some(best_node);
+                node.cost = cost;
+                node.total = total;
+                node.state = NodeState::Open;
+                search.open_list.push(pool, neighbor_node);
+
+                // Update nearest node to target so far.
+                if heuristic < search.last_best_node_cost {
+                    search.last_best_node_cost = heuristic;
+                    search.last_best_node = neighbor_node;
+                }
+            }
+        }
+
+        // Exhausted all nodes, but could not find path.
+        if search.open_list.is_empty(pool) {
+            search.status = SlicedFindPathStatus::Success;
+        }
+        (search.status, iterations)
+    }
+
+    /// Finishes a sliced path search and returns the found path.
+    ///
+    /// If the search is still in progress, the path to the polygon closest to the end position found so far is returned.
+    pub fn finalize_sliced_find_path<F>(
+        &self,
+        search: SlicedFindPath<F>,
+    ) -> Result<FindPathResult, NavmeshQueryError> {
+        self.finalize_sliced_find_path_partial(search, &[])
+    }
+
+    /// Finishes a sliced path search and returns the path to the furthest polygon of `existing` that the search visited.
+    ///
+    /// This is used to shortcut an existing path, e.g. by [`PathCorridor::optimize_path_topology`](crate::PathCorridor::optimize_path_topology),
+    /// without waiting for the search to reach the end polygon. If no polygon of `existing` was visited,
+    /// this behaves like [`NavmeshQuery::finalize_sliced_find_path`].
+    pub fn finalize_sliced_find_path_partial<F>(
+        &self,
+        search: SlicedFindPath<F>,
+        existing: &[u16],
+    ) -> Result<FindPathResult, NavmeshQueryError> {
+        if search.status == SlicedFindPathStatus::Failure {
+            return Err(NavmeshQueryError::SearchFailed);
+        }
+        // Find furthest existing node that was visited.
+        let node = existing
+            .iter()
+            .rev()
+            .find_map(|polygon| {
+                search
+                    .pool
+                    .find(self.poly_ref(*polygon))
+                    .filter(|node| search.pool[*node].state != NodeState::New)
+            })
+            .unwrap_or(search.last_best_node);
+        // The path fails to resolve if a tile along it has been replaced since it was visited.
+        let path = search
+            .pool
+            .path_to(node)
+            .into_iter()
+            .map(|poly_ref| self.polygon_by_ref(poly_ref))
+            .collect::<Result<Vec<_>, _>>()?;
+        let reached_end = search.pool[node].polygon == search.end_polygon;
+        Ok(FindPathResult {
+            path,
+            is_partial: !reached_end,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AreaType, NavmeshTile, PolygonNavmesh, TileCoord, TiledNavmesh, test_meshes};
+
+    /// A 4x1 strip of 10x10 squares with a dead-end square attached to the second one.
+    fn corridor_mesh() -> PolygonNavmesh {
+        test_meshes::grid_where(4, 2, |x, z| z == 0 || x == 1)
+    }
+
+    #[test]
+    fn finds_path_along_corridor() {
+        let mesh = corridor_mesh();
+        let query = NavmeshQuery::new(&mesh);
+        let result = query
+            .find_path(
+                0,
+                3,
+                Vec3::new(5.0, 0.0, 5.0),
+                Vec3::new(35.0, 0.0, 5.0),
+                &QueryFilter::default(),
+            )
+            .unwrap();
+        assert_eq!(result.path, vec![0, 1, 2, 3]);
+        assert!(!result.is_partial);
+    }
+
+    #[test]
+    fn unreachable_end_returns_partial_path() {
+        let mut mesh = corridor_mesh();
+        mesh.flags[2] = 2;
+        let query = NavmeshQuery::new(&mesh);
+        let filter = QueryFilter {
+            exclude_flags: 2,
+            ..Default::default()
+        };
+        let result = query
+            .find_path(
+                0,
+                3,
+                Vec3::new(5.0, 0.0, 5.0),
+                Vec3::new(35.0, 0.0, 5.0),
+                &filter,
+            )
+            .unwrap();
+        // The dead end is closer to the end position than the corridor in front of the excluded polygon.
+        assert_eq!(result.path, vec![0, 1, 4]);
+        assert!(result.is_partial);
+    }
+
+    #[test]
+    fn sliced_search_matches_full_search() {
+        let mesh = corridor_mesh();
+        let query = NavmeshQuery::new(&mesh);
+        let filter = QueryFilter::default();
+        let (start, end) = (Vec3::new(5.0, 0.0, 5.0), Vec3::new(35.0, 0.0, 5.0));
+        let mut search = query
+            .init_sliced_find_path(0, 3, start, end, &filter, 16)
+            .unwrap();
+        let mut updates = 0;
+        while search.status() == SlicedFindPathStatus::InProgress {
+            let (_, iterations) = query.update_sliced_find_path(&mut search, 1);
+            assert!(iterations <= 1);
+            updates += 1;
+        }
+        assert!(updates > 1);
+        let sliced = query.finalize_sliced_find_path(search).unwrap();
+        let full = query.find_path(0, 3, start, end, &filter).unwrap();
+        assert_eq!(sliced, full);
+    }
+
+    #[test]
+    fn sliced_search_fails_when_polygons_disappear() {
+        let mesh = corridor_mesh();
+        let query = NavmeshQuery::new(&mesh);
+        let mut search = query
+            .init_sliced_find_path(
+                0,
+                3,
+                Vec3::new(5.0, 0.0, 5.0),
+                Vec3::new(35.0, 0.0, 5.0),
+                QueryFilter::default(),
+                16,
+            )
+            .unwrap();
+        query.update_sliced_find_path(&mut search, 2);
+
+        // Regenerate the navmesh with fewer polygons between updates.
+        let mut smaller = corridor_mesh();
+        smaller.polygons.truncate(2 * 6);
+        smaller.polygon_neighbors.truncate(2 * 6);
+        smaller.polygon_neighbors[6] = PolygonNavmesh::NO_CONNECTION;
+        smaller.polygon_neighbors[7] = PolygonNavmesh::NO_CONNECTION;
+        let smaller_query = NavmeshQuery::new(&smaller);
+        let (status, _) = smaller_query.update_sliced_find_path(&mut search, 16);
+        assert_eq!(status, SlicedFindPathStatus::Failure);
+        assert_eq!(
+            smaller_query.finalize_sliced_find_path(search),
+            Err(NavmeshQueryError::SearchFailed)
+        );
+    }
+
+    #[test]
+    fn sliced_search_fails_when_tile_is_replaced() {
+        let tile = NavmeshTile {
+            coord: TileCoord { x: 0, z: 0 },
+            poly_mesh: corridor_mesh(),
+            detail_mesh: Default::default(),
+            off_mesh_connections: Vec::new(),
+        };
+        let mut navmesh = TiledNavmesh::from_tiles([tile.clone()]);
+        let tile_ref = navmesh.tile_ref_at(tile.coord).unwrap();
+        let mut search = navmesh
+            .query()
+            .init_sliced_find_path(
+                0,
+                3,
+                Vec3::new(5.0, 0.0, 5.0),
+                Vec3::new(35.0, 0.0, 5.0),
+                QueryFilter::default(),
+                16,
+            )
+            .unwrap();
+        navmesh.query().update_sliced_find_path(&mut search, 2);
+
+        // Rebuild the tile with the same number of polygons, e.g. after a tile cache update.
+        let new_ref = navmesh.add_tile(tile);
+        assert_ne!(new_ref, tile_ref);
+        let query = navmesh.query();
+        let (status, _) = query.update_sliced_find_path(&mut search, 16);
+        assert_eq!(status, SlicedFindPathStatus::Failure);
+        assert_eq!(
+            query.finalize_sliced_find_path(search),
+            Err(NavmeshQueryError::SearchFailed)
+        );
+    }
+
+    #[test]
+    fn sliced_search_crosses_tiles() {
+        let far_away = test_meshes::grid_tile(TileCoord { x: 5, z: 5 }, 2, 1);
+        let mut navmesh = TiledNavmesh::from_tiles(
+            core::iter::once(far_away.clone())
+                .chain((0..3).map(|x| test_meshes::grid_tile(TileCoord { x, z: 0 }, 2, 1))),
+        );
+        let tile_refs: Vec<_> = (0..3)
+            .map(|x| navmesh.tile_ref_at(TileCoord { x, z: 0 }).unwrap())
+            .collect();
+        let query = navmesh.query();
+        let start = query.polygon_by_ref(tile_refs[0]).unwrap();
+        let end = query.polygon_by_ref(tile_refs[2].with_polygon(1)).unwrap();
+        let mut search = query
+            .init_sliced_find_path(
+                start,
+                end,
+                Vec3::new(5.0, 0.0, 5.0),
+                Vec3::new(55.0, 0.0, 5.0),
+                QueryFilter::default(),
+                16,
+            )
+            .unwrap();
+        query.update_sliced_find_path(&mut search, 2);
+
+        // Removing an unrelated tile renumbers the polygons of the queries created afterwards.
+        navmesh.remove_tile(far_away.coord);
+        let query = navmesh.query();
+        let (status, _) = query.update_sliced_find_path(&mut search, 16);
+        assert_eq!(status, SlicedFindPathStatus::Success);
+        let result = query.finalize_sliced_find_path(search).unwrap();
+        assert!(!result.is_partial);
+        let path: Vec<_> = result.path.iter().map(|p| query.poly_ref(*p)).collect();
+        let expected: Vec<_> = tile_refs
+            .iter()
+            .flat_map(|tile_ref| [*tile_ref, tile_ref.with_polygon(1)])
+            .collect();
+        assert_eq!(path, expected);
+    }
+
+    #[test]
+    fn bounded_node_pool_yields_partial_path() {
+        let mesh = corridor_mesh();
+        let query = NavmeshQuery::new(&mesh);
+        let mut search = query
+            .init_sliced_find_path(
+                0,
+                3,
+                Vec3::new(5.0, 0.0, 5.0),
+                Vec3::new(35.0, 0.0, 5.0),
+                QueryFilter::default(),
+                2,
+            )
+            .unwrap();
+        query.update_sliced_find_path(&mut search, usize::MAX);
+        let result = query.finalize_sliced_find_path(search).unwrap();
+        assert_eq!(result.path, vec![0, 1]);
+        assert!(result.is_partial);
+    }
+
+    #[test]
+    fn area_costs_steer_the_path() {
+        // A 2x2 grid of squares, so there are two equally long routes from corner to corner.
+        let mut mesh = test_meshes::grid(2, 2);
+        let water = AreaType(1);
+        let mud = AreaType(2);
+        mesh.areas[1] = water;
+        mesh.areas[2] = mud;
+        let query = NavmeshQuery::new(&mesh);
+        let (start, end) = (Vec3::new(2.0, 0.0, 2.0), Vec3::new(18.0, 0.0, 18.0));
+
+        let avoid_water = QueryFilter::default().with_area_cost(water, 10.0);
+        let path = query.find_path(0, 3, start, end, &avoid_water).unwrap();
+        assert_eq!(path.path, vec![0, 2, 3]);
+
+        let avoid_mud = QueryFilter::default().with_area_cost(mud, 10.0);
+        let path = query.find_path(0, 3, start, end, &avoid_mud).unwrap();
+        assert_eq!(path.path, vec![0, 1, 3]);
+
+        /// Never enters polygon 1, regardless of flags.
+        struct Custom;
+        impl PolygonFilter for Custom {
+            fn pass_filter(&self, polygon: u16, _flags: u16, _area: AreaType) -> bool {
+                polygon != 1
+            }
+        }
+        let path = query.find_path(0, 3, start, end, &Custom).unwrap();
+        assert_eq!(path.path, vec![0, 2, 3]);
+    }
+}
@@ -4,7 +4,7 @@ use crate::{
     math::{next, prev},
 };
 use alloc::vec::Vec;
-use glam::{U16Vec2, U16Vec3, Vec3Swizzles as _, u16vec3, uvec3};
+use glam::{U16Vec2, U16Vec3, Vec3, Vec3Swizzles as _, u16vec3, uvec3};
 use thiserror::Error;
 
 #[derive(Debug, Default, Clone, PartialEq)]
@@ -116,6 +116,8 @@ pub struct PolygonNavmesh {
     /// (i.e. The edge is a solid border.)
     pub polygon_neighbors: Vec<u16>,
     /// The user-defined flags assigned to each polygon.
+    ///
+    /// Generated polygons start with the flag `1`, which passes the default [`QueryFilter`](crate::QueryFilter).
     pub flags: Vec<u16>,
     /// The region id assigned to each polygon.
     pub regions: Vec<RegionId>,
@@ -158,6 +160,16 @@ impl PolygonNavmesh {
             .chunks_exact(self.max_vertices_per_polygon as usize)
             .map(|chunk| chunk.iter().take_while(|i| **i != Self::NO_INDEX).copied())
     }
+
+    /// Returns the world-space position of the vertex at `index` in [`Self::vertices`].
+    pub(crate) fn world_vertex(&self, index: u16) -> Vec3 {
+        let v = self.vertices[index as usize];
+        Vec3::new(
+            self.aabb.min.x + v.x as f32 * self.cell_size,
+            self.aabb.min.y + v.y as f32 * self.cell_height,
+            self.aabb.min.z + v.z as f32 * self.cell_size,
+        )
+    }
 }
 
 impl From<InternalPolygonNavmesh> for PolygonNavmesh {
@@ -415,8 +427,9 @@ impl ContourSet {
                 }
             }
         }
-        // Just allocate the mesh flags array. The user is resposible to fill it.
-        mesh.flags = vec![0; mesh.npolys];
+        // Recast leaves the flags to the user. Mark all polygons as walkable with the flag `1`,
+        // like the default `OffMeshConnection::flags`, so that the default `QueryFilter` accepts them.
+        mesh.flags = vec![1; mesh.npolys];
         // Jan: Rust's type system makes it impossible for the number of verts and polys to be greater than the max index.
 
         Ok(mesh.into())
@@ -0,0 +1,89 @@
+//! Stable references to polygons of a [`TiledNavmesh`](crate::TiledNavmesh).
+
+use core::fmt;
+
+/// A reference to a polygon of a specific tile of a [`TiledNavmesh`](crate::TiledNavmesh), modelled after Detour's `dtPolyRef`.
+///
+/// A reference encodes the index of the tile slot, the salt of the slot and the index of the polygon in the tile's
+/// [`PolygonNavmesh`](crate::PolygonNavmesh). The salt is incremented every time the tile in a slot is replaced or removed,
+/// so references held across such a change are detected as stale by [`TiledNavmesh::is_valid_poly_ref`](crate::TiledNavmesh::is_valid_poly_ref)
+/// instead of silently pointing at an unrelated polygon.
+///
+/// Valid references never have a salt of `0`, so [`PolyRef::NULL`] never refers to a polygon.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct PolyRef(pub u64);
+
+impl PolyRef {
+    /// A reference that never refers to a polygon.
+    pub const NULL: Self = Self(0);
+
+    const POLYGON_BITS: u32 = 16;
+    const TILE_BITS: u32 = 32;
+
+    /// Creates a reference from its components.
+    #[inline]
+    pub const fn new(salt: u16, tile: u32, polygon: u16) -> Self {
+        Self(
+            (salt as u64) << (Self::POLYGON_BITS + Self::TILE_BITS)
+                | (tile as u64) << Self::POLYGON_BITS
+                | polygon as u64,
+        )
+    }
+
+    /// Returns the salt of the tile slot at the time the reference was created.
+    #[inline]
+    pub const fn salt(self) -> u16 {
+        (self.0 >> (Self::POLYGON_BITS + Self::TILE_BITS)) as u16
+    }
+
+    /// Returns the index of the tile slot.
+    #[inline]
+    pub const fn tile(self) -> u32 {
+        (self.0 >> Self::POLYGON_BITS) as u32
+    }
+
+    /// Returns the index of the polygon in the tile's [`PolygonNavmesh`](crate::PolygonNavmesh).
+    #[inline]
+    pub const fn polygon(self) -> u16 {
+        self.0 as u16
+    }
+
+    /// Returns the reference to another polygon of the same tile.
+    #[inline]
+    pub const fn with_polygon(self, polygon: u16) -> Self {
+        Self::new(self.salt(), self.tile(), polygon)
+    }
+
+    /// Returns whether this is [`PolyRef::NULL`].
+    #[inline]
+    pub const fn is_null(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl fmt::Debug for PolyRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PolyRef")
+            .field("salt", &self.salt())
+            .field("tile", &self.tile())
+            .field("polygon", &self.polygon())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn components_round_trip() {
+        let poly_ref = PolyRef::new(0xbeef, 0x1234_5678, 0xabcd);
+        assert_eq!(poly_ref.salt(), 0xbeef);
+        assert_eq!(poly_ref.tile(), 0x1234_5678);
+        assert_eq!(poly_ref.polygon(), 0xabcd);
+        assert_eq!(poly_ref.with_polygon(7).polygon(), 7);
+        assert!(!poly_ref.is_null());
+        assert!(PolyRef::default().is_null());
+    }
+}
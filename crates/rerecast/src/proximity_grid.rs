@@ -0,0 +1,82 @@
+//! A uniform grid on the xz-plane for finding items near a position, modelled after Detour's `dtProximityGrid`.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use glam::{IVec2, Vec3};
+
+use crate::ops::floor;
+
+/// Buckets items by the grid cells their bounds overlap.
+///
+/// Cells are kept in a [`BTreeMap`] so that queries return items in a deterministic order.
+#[derive(Debug, Clone)]
+pub(crate) struct ProximityGrid<T> {
+    inv_cell_size: f32,
+    cells: BTreeMap<(i32, i32), Vec<T>>,
+}
+
+impl<T: Copy + Ord> ProximityGrid<T> {
+    pub(crate) fn new(cell_size: f32) -> Self {
+        Self {
+            inv_cell_size: 1.0 / cell_size,
+            cells: BTreeMap::new(),
+        }
+    }
+
+    /// Removes all items.
+    pub(crate) fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Adds an item whose bounds on the xz-plane are `min` to `max`.
+    pub(crate) fn add_item(&mut self, item: T, min: Vec3, max: Vec3) {
+        let (min, max) = (self.cell_of(min), self.cell_of(max));
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                self.cells.entry((x, y)).or_default().push(item);
+            }
+        }
+    }
+
+    /// Returns the items whose cells overlap the bounds `min` to `max` on the xz-plane, sorted and without duplicates.
+    pub(crate) fn query_items(&self, min: Vec3, max: Vec3) -> Vec<T> {
+        let (min, max) = (self.cell_of(min), self.cell_of(max));
+        let mut items = Vec::new();
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    items.extend_from_slice(cell);
+                }
+            }
+        }
+        items.sort_unstable();
+        items.dedup();
+        items
+    }
+
+    fn cell_of(&self, position: Vec3) -> IVec2 {
+        IVec2::new(
+            floor(position.x * self.inv_cell_size) as i32,
+            floor(position.z * self.inv_cell_size) as i32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_items_in_overlapping_cells() {
+        let mut grid = ProximityGrid::new(1.0);
+        grid.add_item(1, Vec3::new(0.1, 0.0, 0.1), Vec3::new(0.4, 0.0, 0.4));
+        grid.add_item(2, Vec3::new(0.5, 0.0, 0.5), Vec3::new(1.5, 0.0, 1.5));
+        grid.add_item(3, Vec3::new(5.0, 0.0, 5.0), Vec3::new(5.5, 0.0, 5.5));
+
+        assert_eq!(grid.query_items(Vec3::ZERO, Vec3::splat(0.9)), [1, 2]);
+        assert_eq!(grid.query_items(Vec3::splat(1.2), Vec3::splat(1.3)), [2]);
+        assert!(
+            grid.query_items(Vec3::splat(-3.0), Vec3::splat(-2.0))
+                .is_empty()
+        );
+    }
+}
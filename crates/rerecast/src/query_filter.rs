@@ -0,0 +1,140 @@
+//! Filters that decide which polygons a [`NavmeshQuery`](crate::NavmeshQuery) may visit and how much traversing them costs.
+
+use alloc::vec::Vec;
+use glam::Vec3;
+
+use crate::AreaType;
+
+/// Decides which polygons a query may visit and how expensive it is to move across them.
+///
+/// Implement this to fully customize polygon access and path costs, e.g. to make a unit type avoid
+/// polygons near enemies. [`QueryFilter`] is the standard implementation based on polygon flags and area costs.
+///
+/// All queries of [`NavmeshQuery`](crate::NavmeshQuery) accept any implementation of this trait.
+pub trait PolygonFilter {
+    /// Returns whether the polygon may be visited.
+    ///
+    /// `flags` and `area` are the polygon's entries in [`PolygonNavmesh::flags`](crate::PolygonNavmesh::flags)
+    /// and [`PolygonNavmesh::areas`](crate::PolygonNavmesh::areas).
+    fn pass_filter(&self, polygon: u16, flags: u16, area: AreaType) -> bool;
+
+    /// Returns the cost of moving from `from` to `to`, where both positions lie on `polygon`.
+    ///
+    /// Costs must not be negative. By default, the cost is the distance between the positions.
+    fn cost(&self, from: Vec3, to: Vec3, polygon: u16, flags: u16, area: AreaType) -> f32 {
+        let _ = (polygon, flags, area);
+        from.distance(to)
+    }
+}
+
+/// Defines which polygons are accessible to a query and how expensive their areas are to traverse.
+///
+/// A polygon passes the filter if it has at least one of the [`QueryFilter::include_flags`]
+/// and none of the [`QueryFilter::exclude_flags`] set in [`PolygonNavmesh::flags`](crate::PolygonNavmesh::flags).
+/// The cost of moving across a polygon is the distance travelled multiplied by the cost of the polygon's [`AreaType`],
+/// so that different unit types can e.g. prefer roads or avoid water.
+///
+/// Note that the build process leaves all polygon flags at `0`, which means that no polygon passes any filter
+/// until the flags are assigned, e.g. with `mesh.flags.fill(1)`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueryFilter {
+    /// Flags of which a polygon must have at least one to be accessible.
+    pub include_flags: u16,
+    /// Flags of which a polygon must have none to be accessible.
+    pub exclude_flags: u16,
+    /// The cost multiplier of each area type, indexed by the area type's value.
+    ///
+    /// Area types without an entry have a cost multiplier of `1.0`.
+    pub area_costs: Vec<f32>,
+}
+
+impl Default for QueryFilter {
+    fn default() -> Self {
+        Self {
+            include_flags: u16::MAX,
+            exclude_flags: 0,
+            area_costs: Vec::new(),
+        }
+    }
+}
+
+impl QueryFilter {
+    /// Returns the cost multiplier of the given area type. Defaults to `1.0`.
+    #[inline]
+    pub fn area_cost(&self, area: AreaType) -> f32 {
+        self.area_costs.get(*area as usize).copied().unwrap_or(1.0)
+    }
+
+    /// Sets the cost multiplier of the given area type.
+    ///
+    /// The multiplier should be at least `1.0`, otherwise the A* heuristic may overestimate costs and
+    /// [`NavmeshQuery::find_path`](crate::NavmeshQuery::find_path) may not find the cheapest path.
+    #[inline]
+    pub fn set_area_cost(&mut self, area: AreaType, cost: f32) {
+        let index = *area as usize;
+        if self.area_costs.len() <= index {
+            self.area_costs.resize(index + 1, 1.0);
+        }
+        self.area_costs[index] = cost;
+    }
+
+    /// Returns the filter with the cost multiplier of the given area type set.
+    #[inline]
+    pub fn with_area_cost(mut self, area: AreaType, cost: f32) -> Self {
+        self.set_area_cost(area, cost);
+        self
+    }
+}
+
+impl PolygonFilter for QueryFilter {
+    #[inline]
+    fn pass_filter(&self, _polygon: u16, flags: u16, _area: AreaType) -> bool {
+        (flags & self.include_flags) != 0 && (flags & self.exclude_flags) == 0
+    }
+
+    #[inline]
+    fn cost(&self, from: Vec3, to: Vec3, _polygon: u16, _flags: u16, area: AreaType) -> f32 {
+        from.distance(to) * self.area_cost(area)
+    }
+}
+
+impl<T: PolygonFilter + ?Sized> PolygonFilter for &T {
+    #[inline]
+    fn pass_filter(&self, polygon: u16, flags: u16, area: AreaType) -> bool {
+        (**self).pass_filter(polygon, flags, area)
+    }
+
+    #[inline]
+    fn cost(&self, from: Vec3, to: Vec3, polygon: u16, flags: u16, area: AreaType) -> f32 {
+        (**self).cost(from, to, polygon, flags, area)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_are_included_and_excluded() {
+        let filter = QueryFilter {
+            include_flags: 0b011,
+            exclude_flags: 0b010,
+            ..Default::default()
+        };
+        let area = AreaType::DEFAULT_WALKABLE;
+        assert!(filter.pass_filter(0, 0b001, area));
+        assert!(!filter.pass_filter(0, 0b011, area));
+        assert!(!filter.pass_filter(0, 0b100, area));
+        assert!(!filter.pass_filter(0, 0, area));
+    }
+
+    #[test]
+    fn cost_is_scaled_by_area() {
+        let water = AreaType(3);
+        let filter = QueryFilter::default().with_area_cost(water, 4.0);
+        let (from, to) = (Vec3::ZERO, Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(filter.cost(from, to, 0, 1, water), 8.0);
+        assert_eq!(filter.cost(from, to, 0, 1, AreaType::DEFAULT_WALKABLE), 2.0);
+    }
+}
@@ -0,0 +1,332 @@
+//! Top-down raster images of the build stages, encoded as PPM, for CI artifacts and bug reports.
+
+use alloc::{format, vec::Vec};
+use glam::{Vec2, Vec3, Vec3Swizzles as _};
+
+use crate::{
+    Aabb3d, DebugColor, DebugDraw, DebugDrawPrimitive,
+    ops::{ceil, floor},
+};
+
+/// A top-down image that can be drawn to as a [`DebugDraw`] and saved as a binary PPM file.
+///
+/// The x-axis of the world points right and the z-axis points down in the image.
+/// Where primitives overlap, the highest one is visible, with later primitives winning ties.
+/// Transparency is ignored, apart from fully transparent vertices, which are skipped.
+///
+/// ```
+/// # use rerecast::*;
+/// # fn example(compact_heightfield: &CompactHeightfield) {
+/// let mut image = RasterImage::top_down(compact_heightfield.aabb, compact_heightfield.cell_size);
+/// compact_heightfield.debug_draw_regions(&mut image);
+/// let ppm = image.to_ppm();
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RasterImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<[u8; 3]>,
+    depth: Vec<f32>,
+    origin: Vec2,
+    pixel_size: f32,
+    primitive: DebugDrawPrimitive,
+    vertices: Vec<(Vec3, DebugColor)>,
+}
+
+impl RasterImage {
+    /// The color of pixels nothing was drawn to.
+    pub const BACKGROUND: [u8; 3] = [48, 48, 48];
+
+    /// Creates an image covering `aabb` on the xz-plane, with each pixel covering `pixel_size` world units along both axes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixel_size` is not positive.
+    pub fn top_down(aabb: Aabb3d, pixel_size: f32) -> Self {
+        assert!(pixel_size > 0.0, "pixel size must be positive");
+        let size = (aabb.max.xz() - aabb.min.xz()) / pixel_size;
+        let width = ceil(size.x).max(1.0) as u32;
+        let height = ceil(size.y).max(1.0) as u32;
+        Self {
+            width,
+            height,
+            pixels: vec![Self::BACKGROUND; width as usize * height as usize],
+            depth: vec![f32::NEG_INFINITY; width as usize * height as usize],
+            origin: aabb.min.xz(),
+            pixel_size,
+            primitive: DebugDrawPrimitive::Points,
+            vertices: Vec::new(),
+        }
+    }
+
+    /// The width of the image in pixels.
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height of the image in pixels.
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the color of the pixel at column `x` and row `y`.
+    #[inline]
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        self.pixels[(x + y * self.width) as usize]
+    }
+
+    /// Encodes the image as a binary PPM (`P6`) file.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let header = format!("P6\n{} {}\n255\n", self.width, self.height);
+        let mut ppm = Vec::with_capacity(header.len() + self.pixels.len() * 3);
+        ppm.extend_from_slice(header.as_bytes());
+        ppm.extend(self.pixels.iter().flatten());
+        ppm
+    }
+
+    /// Converts a world position to continuous pixel coordinates.
+    fn project(&self, position: Vec3) -> Vec2 {
+        (position.xz() - self.origin) / self.pixel_size
+    }
+
+    fn plot(&mut self, x: i64, y: i64, depth: f32, color: DebugColor) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 || color[3] == 0 {
+            return;
+        }
+        let index = (x + y * self.width as i64) as usize;
+        if depth >= self.depth[index] {
+            self.depth[index] = depth;
+            self.pixels[index] = [color[0], color[1], color[2]];
+        }
+    }
+
+    fn point(&mut self, (position, color): (Vec3, DebugColor)) {
+        let p = self.project(position);
+        self.plot(floor(p.x) as i64, floor(p.y) as i64, position.y, color);
+    }
+
+    /// Draws a line with Bresenham's algorithm, in the color of its first vertex.
+    fn line(&mut self, (a, color): (Vec3, DebugColor), (b, _): (Vec3, DebugColor)) {
+        let Some((a, b)) = self.clip(a, b) else {
+            return;
+        };
+        let (pa, pb) = (self.project(a), self.project(b));
+        let (mut x, mut y) = (floor(pa.x) as i64, floor(pa.y) as i64);
+        let (x1, y1) = (floor(pb.x) as i64, floor(pb.y) as i64);
+        let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+        let (sx, sy) = ((x1 - x).signum(), (y1 - y).signum());
+        let steps = dx.max(-dy).max(1) as f32;
+        let mut error = dx + dy;
+        let mut step = 0.0;
+        loop {
+            self.plot(x, y, a.y + (b.y - a.y) * step / steps, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * error;
+            if e2 >= dy {
+                error += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                error += dx;
+                y += sy;
+            }
+            step += 1.0;
+        }
+    }
+
+    /// Clips the segment to the part inside the image with the Liang-Barsky algorithm.
+    fn clip(&self, a: Vec3, b: Vec3) -> Option<(Vec3, Vec3)> {
+        let (pa, pb) = (self.project(a), self.project(b));
+        let delta = pb - pa;
+        let (mut t0, mut t1) = (0.0_f32, 1.0_f32);
+        for (p, q) in [
+            (-delta.x, pa.x),
+            (delta.x, self.width as f32 - pa.x),
+            (-delta.y, pa.y),
+            (delta.y, self.height as f32 - pa.y),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else if p < 0.0 {
+                t0 = t0.max(q / p);
+            } else {
+                t1 = t1.min(q / p);
+            }
+        }
+        (t0 <= t1).then(|| (a.lerp(b, t0), a.lerp(b, t1)))
+    }
+
+    /// Fills the pixels whose centers lie in the triangle, in the color of its first vertex.
+    fn triangle(&mut self, vertices: [(Vec3, DebugColor); 3]) {
+        let color = vertices[0].1;
+        let [a, b, c] = vertices.map(|(position, _)| position);
+        let [pa, pb, pc] = [a, b, c].map(|position| self.project(position));
+        let area = (pb - pa).perp_dot(pc - pa);
+        if area == 0.0 {
+            // Seen from above, e.g. the sides of boxes.
+            return;
+        }
+        // Only visit the part of the bounds inside the image.
+        let min = pa.min(pb).min(pc).max(Vec2::ZERO);
+        let max = pa
+            .max(pb)
+            .max(pc)
+            .min(Vec2::new(self.width as f32, self.height as f32));
+        for y in floor(min.y) as i64..ceil(max.y) as i64 {
+            for x in floor(min.x) as i64..ceil(max.x) as i64 {
+                let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let wa = (pc - pb).perp_dot(p - pb) / area;
+                let wb = (pa - pc).perp_dot(p - pc) / area;
+                let wc = 1.0 - wa - wb;
+                if wa >= 0.0 && wb >= 0.0 && wc >= 0.0 {
+                    self.plot(x, y, wa * a.y + wb * b.y + wc * c.y, color);
+                }
+            }
+        }
+    }
+}
+
+impl DebugDraw for RasterImage {
+    fn begin(&mut self, primitive: DebugDrawPrimitive, _size: f32) {
+        self.primitive = primitive;
+        self.vertices.clear();
+    }
+
+    fn vertex(&mut self, position: Vec3, color: DebugColor) {
+        self.vertices.push((position, color));
+        let v = &self.vertices;
+        match (self.primitive, v.len()) {
+            (DebugDrawPrimitive::Points, 1) => self.point(v[0]),
+            (DebugDrawPrimitive::Lines, 2) => self.line(v[0], v[1]),
+            (DebugDrawPrimitive::Triangles, 3) => self.triangle([v[0], v[1], v[2]]),
+            (DebugDrawPrimitive::Quads, 4) => {
+                let quad = [v[0], v[1], v[2], v[3]];
+                self.triangle([quad[0], quad[1], quad[2]]);
+                self.triangle([quad[0], quad[2], quad[3]]);
+            }
+            _ => return,
+        }
+        self.vertices.clear();
+    }
+
+    fn end(&mut self) {
+        self.vertices.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AreaType, CompactCell, CompactHeightfield, CompactSpan, RegionId};
+
+    fn aabb(width: f32, height: f32) -> Aabb3d {
+        Aabb3d {
+            min: Vec3::ZERO,
+            max: Vec3::new(width, 1.0, height),
+        }
+    }
+
+    fn cell(index: u32, count: u8) -> CompactCell {
+        let mut cell = CompactCell::default();
+        cell.set_index(index);
+        cell.set_count(count);
+        cell
+    }
+
+    #[test]
+    fn encodes_ppm() {
+        let mut image = RasterImage::top_down(aabb(3.0, 2.0), 1.0);
+        image.begin(DebugDrawPrimitive::Lines, 1.0);
+        image.vertex(Vec3::new(0.5, 0.0, 1.5), [255, 0, 0, 255]);
+        image.vertex(Vec3::new(2.5, 0.0, 1.5), [255, 0, 0, 255]);
+        image.end();
+
+        let ppm = image.to_ppm();
+        let header = b"P6\n3 2\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        let pixels = &ppm[header.len()..];
+        assert_eq!(pixels.len(), 3 * 2 * 3);
+        assert_eq!(&pixels[..3], &RasterImage::BACKGROUND);
+        assert_eq!(&pixels[9..], &[255, 0, 0].repeat(3));
+    }
+
+    #[test]
+    fn clips_triangles_to_image() {
+        let mut image = RasterImage::top_down(aabb(2.0, 2.0), 1.0);
+        image.begin(DebugDrawPrimitive::Triangles, 1.0);
+        image.vertex(Vec3::new(-1e9, 0.0, -1e9), [0, 255, 0, 255]);
+        image.vertex(Vec3::new(-1e9, 0.0, 1e9), [0, 255, 0, 255]);
+        image.vertex(Vec3::new(1e9, 0.0, -1e9), [0, 255, 0, 255]);
+        image.end();
+        assert_eq!(image.pixel(0, 0), [0, 255, 0]);
+        assert_eq!(image.pixel(1, 1), [0, 255, 0]);
+    }
+
+    #[test]
+    fn clips_lines_to_image() {
+        let mut image = RasterImage::top_down(aabb(2.0, 2.0), 1.0);
+        image.begin(DebugDrawPrimitive::Lines, 1.0);
+        image.vertex(Vec3::new(-1e6, 0.0, 0.5), [0, 255, 0, 255]);
+        image.vertex(Vec3::new(1e6, 0.0, 0.5), [0, 255, 0, 255]);
+        image.vertex(Vec3::new(-1e6, 0.0, -1e6), [0, 255, 0, 255]);
+        image.vertex(Vec3::new(1e6, 0.0, -1e6), [0, 255, 0, 255]);
+        image.end();
+        assert_eq!(image.pixel(0, 0), [0, 255, 0]);
+        assert_eq!(image.pixel(1, 0), [0, 255, 0]);
+        assert_eq!(image.pixel(0, 1), RasterImage::BACKGROUND);
+    }
+
+    #[test]
+    #[should_panic = "pixel size must be positive"]
+    fn rejects_non_positive_pixel_size() {
+        RasterImage::top_down(aabb(2.0, 2.0), 0.0);
+    }
+
+    #[test]
+    fn draws_regions_of_compact_heightfield() {
+        // Two cells with a region each, the second one with a higher span on top of a lower one.
+        let compact_heightfield = CompactHeightfield {
+            width: 2,
+            height: 1,
+            aabb: aabb(2.0, 1.0),
+            cell_size: 1.0,
+            cell_height: 0.1,
+            cells: vec![cell(0, 1), cell(1, 2)],
+            spans: vec![
+                CompactSpan {
+                    y: 0,
+                    region: RegionId::from(1),
+                    ..Default::default()
+                },
+                CompactSpan {
+                    y: 0,
+                    region: RegionId::from(2),
+                    ..Default::default()
+                },
+                CompactSpan {
+                    y: 5,
+                    region: RegionId::from(3),
+                    ..Default::default()
+                },
+            ],
+            areas: vec![AreaType::DEFAULT_WALKABLE; 3],
+            ..Default::default()
+        };
+        let mut image = RasterImage::top_down(compact_heightfield.aabb, 1.0);
+        compact_heightfield.debug_draw_regions(&mut image);
+        assert_eq!((image.width(), image.height()), (2, 1));
+        let region_color = |region: u32| {
+            let [r, g, b, _] = crate::debug_draw::int_to_color(region, 192);
+            [r, g, b]
+        };
+        assert_eq!(image.pixel(0, 0), region_color(1));
+        assert_eq!(image.pixel(1, 0), region_color(3));
+    }
+}
@@ -0,0 +1,247 @@
+//! Conversion of polygon paths into the corner points an agent actually walks along.
+
+use alloc::vec::Vec;
+use glam::Vec3;
+
+use crate::{
+    NavmeshQuery, NavmeshQueryError,
+    math::{distance_squared_between_point_and_segment_xz, triangle_area_xz},
+    ops::FloatPow as _,
+};
+
+bitflags::bitflags! {
+    /// Flags describing a vertex of a straight path.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    #[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+    #[repr(transparent)]
+    pub struct StraightPathFlags: u8 {
+        /// The vertex is the start position of the path.
+        const START = 0x01;
+        /// The vertex is the end position of the path.
+        const END = 0x02;
+        /// The vertex is the start of an off-mesh connection, which is the polygon of the vertex.
+        const OFF_MESH_CONNECTION = 0x04;
+    }
+}
+
+/// A corner of a path returned by [`NavmeshQuery::find_straight_path`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StraightPathVertex {
+    /// The position of the corner.
+    pub position: Vec3,
+    /// Describes the role of the corner in the path.
+    pub flags: StraightPathFlags,
+    /// The polygon that is entered at this corner. `None` for the end of the path.
+    pub polygon: Option<u16>,
+}
+
+impl NavmeshQuery<'_> {
+    /// Finds the straight path from `start` to `end` through the given polygon path, using the funnel algorithm.
+    ///
+    /// `path` is usually the result of [`NavmeshQuery::find_path`]. `start` and `end` are clamped to the first and last polygon of the path.
+    /// At most `max_vertices` vertices are returned, so a small number can be used to only retrieve the next few corners.
+    pub fn find_straight_path(
+        &self,
+        start: Vec3,
+        end: Vec3,
+        path: &[u16],
+        max_vertices: usize,
+    ) -> Result<Vec<StraightPathVertex>, NavmeshQueryError> {
+        let (Some(&first), Some(&last)) = (path.first(), path.last()) else {
+            return Ok(Vec::new());
+        };
+        if let Some(&polygon) = path
+            .iter()
+            .find(|p| !self.is_valid_polygon_or_connection(**p))
+        {
+            return Err(NavmeshQueryError::InvalidPolygon(polygon));
+        }
+        if !start.is_finite() || !end.is_finite() {
+            return Err(NavmeshQueryError::InvalidPosition);
+        }
+
+        let mut straight_path = StraightPath {
+            vertices: Vec::new(),
+            max_vertices: max_vertices.max(1),
+        };
+        let start = self.closest_point_on_polygon(first, start).0;
+        let end = self.closest_point_on_polygon(last, end).0;
+        if !straight_path.append(start, StraightPathFlags::START, Some(first)) {
+            return Ok(straight_path.vertices);
+        }
+
+        if path.len() > 1 {
+            let mut portal_apex = start;
+            let mut portal_left = start;
+            let mut portal_right = start;
+            let mut left_index = 0;
+            let mut right_index = 0;
+            let mut left_polygon = Some(first);
+            let mut right_polygon = Some(first);
+
+            let mut i = 0;
+            while i < path.len() {
+                let (left, right) = if i + 1 < path.len() {
+                    // Next portal.
+                    let Some(portal) = self.portal_points(path[i], path[i + 1]) else {
+                        // The path is broken, return the straight path up to the last valid polygon.
+                        let end = self.closest_point_on_polygon(path[i], end).0;
+                        straight_path.append(end, StraightPathFlags::empty(), None);
+                        return Ok(straight_path.vertices);
+                    };
+
+                    // If starting really close to the portal, advance.
+                    if i == 0
+                        && distance_squared_between_point_and_segment_xz(portal_apex, portal).0
+                            < 0.001.squared()
+                    {
+                        i += 1;
+                        continue;
+                    }
+                    portal
+                } else {
+                    // End of the path.
+                    (end, end)
+                };
+                let next_polygon = path.get(i + 1).copied();
+
+                // Right vertex.
+                if triangle_area_xz(portal_apex, portal_right, right) <= 0.0 {
+                    if is_same_position(portal_apex, portal_right)
+                        || triangle_area_xz(portal_apex, portal_left, right) > 0.0
+                    {
+                        // Tighten the funnel.
+                        portal_right = right;
+                        right_polygon = next_polygon;
+                        right_index = i;
+                    } else {
+                        // Right over left, insert left to path and restart scan from portal left point.
+                        portal_apex = portal_left;
+                        let apex_index = left_index;
+                        if !straight_path.append(
+                            portal_apex,
+                            flags_of(self, left_polygon),
+                            left_polygon,
+                        ) {
+                            return Ok(straight_path.vertices);
+                        }
+                        portal_left = portal_apex;
+                        portal_right = portal_apex;
+                        left_index = apex_index;
+                        right_index = apex_index;
+                        i = apex_index + 1;
+                        continue;
+                    }
+                }
+
+                // Left vertex.
+                if triangle_area_xz(portal_apex, portal_left, left) >= 0.0 {
+                    if is_same_position(portal_apex, portal_left)
+                        || triangle_area_xz(portal_apex, portal_right, left) < 0.0
+                    {
+                        // Tighten the funnel.
+                        portal_left = left;
+                        left_polygon = next_polygon;
+                        left_index = i;
+                    } else {
+                        // Left over right, insert right to path and restart scan from portal right point.
+                        portal_apex = portal_right;
+                        let apex_index = right_index;
+                        if !straight_path.append(
+                            portal_apex,
+                            flags_of(self, right_polygon),
+                            right_polygon,
+                        ) {
+                            return Ok(straight_path.vertices);
+                        }
+                        portal_left = portal_apex;
+                        portal_right = portal_apex;
+                        left_index = apex_index;
+                        right_index = apex_index;
+                        i = apex_index + 1;
+                        continue;
+                    }
+                }
+                i += 1;
+            }
+        }
+
+        straight_path.append(end, StraightPathFlags::END, None);
+        Ok(straight_path.vertices)
+    }
+}
+
+/// Returns whether two positions are equal within a small tolerance.
+fn is_same_position(a: Vec3, b: Vec3) -> bool {
+    a.distance_squared(b) < (1.0 / 16384.0).squared()
+}
+
+/// Returns the flags of a corner at which `polygon` is entered.
+fn flags_of(query: &NavmeshQuery, polygon: Option<u16>) -> StraightPathFlags {
+    match polygon {
+        None => StraightPathFlags::END,
+        Some(polygon) if query.is_off_mesh_connection(polygon) => {
+            StraightPathFlags::OFF_MESH_CONNECTION
+        }
+        Some(_) => StraightPathFlags::empty(),
+    }
+}
+
+struct StraightPath {
+    vertices: Vec<StraightPathVertex>,
+    max_vertices: usize,
+}
+
+impl StraightPath {
+    /// Appends a vertex, merging it with the previous one if they are at the same position.
+    ///
+    /// Returns whether more vertices can be appended.
+    fn append(&mut self, position: Vec3, flags: StraightPathFlags, polygon: Option<u16>) -> bool {
+        if let Some(previous) = self.vertices.last_mut()
+            && is_same_position(previous.position, position)
+        {
+            // The vertices are equal, update flags and polygon.
+            previous.flags = flags;
+            previous.polygon = polygon;
+        } else {
+            self.vertices.push(StraightPathVertex {
+                position,
+                flags,
+                polygon,
+            });
+        }
+        self.vertices.len() < self.max_vertices && !flags.contains(StraightPathFlags::END)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PolygonNavmesh, test_meshes};
+
+    /// An L-shaped corridor of three 10x10 squares: two along x, then one along z.
+    fn l_mesh() -> PolygonNavmesh {
+        test_meshes::grid_where(2, 2, |x, z| z == 0 || x == 1)
+    }
+
+    #[test]
+    fn straight_path_bends_around_corner() {
+        let mesh = l_mesh();
+        let query = NavmeshQuery::new(&mesh);
+        let start = Vec3::new(2.0, 0.0, 2.0);
+        let end = Vec3::new(15.0, 0.0, 18.0);
+        let path = query
+            .find_straight_path(start, end, &[0, 1, 2], 16)
+            .unwrap();
+
+        let positions: Vec<_> = path.iter().map(|vertex| vertex.position).collect();
+        assert_eq!(positions, [start, Vec3::new(10.0, 0.0, 10.0), end]);
+        assert_eq!(path[0].flags, StraightPathFlags::START);
+        assert_eq!(path[1].polygon, Some(2));
+        assert_eq!(path[2].flags, StraightPathFlags::END);
+        assert_eq!(path[2].polygon, None);
+
+        let path = query.find_straight_path(start, end, &[0, 1, 2], 2).unwrap();
+        assert_eq!(path.len(), 2);
+    }
+}
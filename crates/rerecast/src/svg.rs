@@ -0,0 +1,304 @@
+//! Writing of the top-down projection of navmeshes and contours as SVG, e.g. for vector minimaps.
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt::Write as _, ops::Range};
+use glam::{Affine2, Vec2, Vec3, Vec3Swizzles as _};
+
+use crate::{Aabb3d, AreaType, ContourSet, DebugColor, PolygonNavmesh, debug_draw::int_to_color};
+
+/// Options for [`PolygonNavmesh::to_svg`] and [`ContourSet::to_svg`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    /// Maps world positions on the xz-plane to SVG coordinates.
+    /// Defaults to one SVG unit per world unit, with the x-axis pointing right and the z-axis pointing down.
+    pub transform: Affine2,
+    /// Only polygons and contours whose average height lies in this range are written, e.g. to export a single floor.
+    /// Defaults to all heights.
+    pub height_range: Range<f32>,
+    /// Colors polygons and contours by their region instead of their area type.
+    pub color_by_region: bool,
+    /// The width of outlines in SVG units.
+    pub stroke_width: f32,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            transform: Affine2::IDENTITY,
+            height_range: f32::NEG_INFINITY..f32::INFINITY,
+            color_by_region: false,
+            stroke_width: 0.1,
+        }
+    }
+}
+
+impl PolygonNavmesh {
+    /// Writes the polygons projected onto the xz-plane as SVG.
+    ///
+    /// Every polygon is a `<polygon>` of class `polygon` filled with the color of its area type or region.
+    /// Edges without a neighbor are drawn as a `<path>` of class `boundary`, portals to neighboring tiles as a `<path>` of class `portal`,
+    /// so they can be restyled with CSS.
+    pub fn to_svg(&self, options: &SvgOptions) -> String {
+        let nvp = self.max_vertices_per_polygon as usize;
+        let mut svg = SvgWriter::new(self.aabb, options);
+        let mut boundaries = String::new();
+        let mut portals = String::new();
+
+        _ = writeln!(svg.svg, r#"<g class="polygons">"#);
+        for (polygon, indices) in self.polygons().enumerate() {
+            let vertices: Vec<Vec3> = indices.map(|i| self.world_vertex(i)).collect();
+            if !svg.contains_height(&vertices) {
+                continue;
+            }
+            let area = self.areas.get(polygon).copied().unwrap_or_default();
+            let region = self.regions.get(polygon).map_or(0, |region| region.bits());
+            let color = svg.color(area, region);
+            _ = writeln!(
+                svg.svg,
+                r#"<polygon class="polygon" points="{}" {} data-area="{}" data-region="{region}"/>"#,
+                svg.points(&vertices),
+                fill(color),
+                area.0,
+            );
+
+            for j in 0..vertices.len() {
+                let neighbor = self
+                    .polygon_neighbors
+                    .get(polygon * nvp + j)
+                    .copied()
+                    .unwrap_or(Self::NO_CONNECTION);
+                if neighbor & 0x8000 == 0 {
+                    continue;
+                }
+                let edges = if neighbor != Self::NO_CONNECTION && neighbor & 0xf != 0xf {
+                    &mut portals
+                } else {
+                    &mut boundaries
+                };
+                let a = svg.project(vertices[j]);
+                let b = svg.project(vertices[(j + 1) % vertices.len()]);
+                _ = write!(edges, "M{} {}L{} {}", a.x, a.y, b.x, b.y);
+            }
+        }
+        _ = writeln!(svg.svg, "</g>");
+
+        let stroke_width = options.stroke_width;
+        for (class, d, stroke) in [
+            ("boundary", boundaries, "#00303c"),
+            ("portal", portals, "#ffffff"),
+        ] {
+            if !d.is_empty() {
+                _ = writeln!(
+                    svg.svg,
+                    r#"<path class="{class}" d="{d}" fill="none" stroke="{stroke}" stroke-width="{stroke_width}"/>"#
+                );
+            }
+        }
+        svg.finish()
+    }
+}
+
+impl ContourSet {
+    /// Writes the simplified contours projected onto the xz-plane as SVG.
+    ///
+    /// Every contour is an outlined `<polygon>` of class `contour` in the color of its area type or region.
+    pub fn to_svg(&self, options: &SvgOptions) -> String {
+        let mut svg = SvgWriter::new(self.aabb, options);
+        _ = writeln!(svg.svg, r#"<g class="contours" fill="none">"#);
+        for contour in &self.contours {
+            let vertices: Vec<Vec3> = contour
+                .vertices
+                .iter()
+                .map(|(v, _)| {
+                    self.aabb.min
+                        + Vec3::new(
+                            v.x as f32 * self.cell_size,
+                            v.y as f32 * self.cell_height,
+                            v.z as f32 * self.cell_size,
+                        )
+                })
+                .collect();
+            if vertices.is_empty() || !svg.contains_height(&vertices) {
+                continue;
+            }
+            let [r, g, b, _] = svg.color(contour.area, contour.region.bits());
+            _ = writeln!(
+                svg.svg,
+                r##"<polygon class="contour" points="{}" stroke="#{r:02x}{g:02x}{b:02x}" stroke-width="{}" data-area="{}" data-region="{}"/>"##,
+                svg.points(&vertices),
+                options.stroke_width,
+                contour.area.0,
+                contour.region.bits(),
+            );
+        }
+        _ = writeln!(svg.svg, "</g>");
+        svg.finish()
+    }
+}
+
+/// Formats the `fill` and, for translucent colors, `fill-opacity` attributes.
+fn fill([r, g, b, a]: DebugColor) -> String {
+    let mut fill = format!("fill=\"#{r:02x}{g:02x}{b:02x}\"");
+    if a != 255 {
+        _ = write!(fill, r#" fill-opacity="{}""#, a as f32 / 255.0);
+    }
+    fill
+}
+
+struct SvgWriter<'a> {
+    svg: String,
+    options: &'a SvgOptions,
+}
+
+impl<'a> SvgWriter<'a> {
+    /// Starts a document whose view box covers `aabb`.
+    fn new(aabb: Aabb3d, options: &'a SvgOptions) -> Self {
+        let corners = [
+            Vec2::new(aabb.min.x, aabb.min.z),
+            Vec2::new(aabb.max.x, aabb.min.z),
+            Vec2::new(aabb.min.x, aabb.max.z),
+            Vec2::new(aabb.max.x, aabb.max.z),
+        ]
+        .map(|corner| options.transform.transform_point2(corner));
+        let min = corners.into_iter().reduce(Vec2::min).unwrap_or_default();
+        let max = corners.into_iter().reduce(Vec2::max).unwrap_or_default();
+        let size = max - min;
+        let mut svg = String::new();
+        _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+            size.x, size.y, min.x, min.y, size.x, size.y
+        );
+        Self { svg, options }
+    }
+
+    fn project(&self, position: Vec3) -> Vec2 {
+        self.options.transform.transform_point2(position.xz())
+    }
+
+    /// Formats the `points` attribute of a polygon.
+    fn points(&self, vertices: &[Vec3]) -> String {
+        let mut points = String::new();
+        for (i, vertex) in vertices.iter().enumerate() {
+            let point = self.project(*vertex);
+            let separator = if i == 0 { "" } else { " " };
+            _ = write!(points, "{separator}{},{}", point.x, point.y);
+        }
+        points
+    }
+
+    /// Whether the average height of `vertices` lies in [`SvgOptions::height_range`].
+    fn contains_height(&self, vertices: &[Vec3]) -> bool {
+        let height = vertices.iter().map(|vertex| vertex.y).sum::<f32>() / vertices.len() as f32;
+        self.options.height_range.contains(&height)
+    }
+
+    fn color(&self, area: AreaType, region: u16) -> DebugColor {
+        if self.options.color_by_region {
+            int_to_color(region.into(), 255)
+        } else {
+            area.debug_color()
+        }
+    }
+
+    fn finish(mut self) -> String {
+        _ = writeln!(self.svg, "</svg>");
+        self.svg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RegionId;
+    use glam::U16Vec3;
+
+    /// Two triangles on different floors, the lower one with a boundary and a portal edge.
+    fn poly_mesh() -> PolygonNavmesh {
+        PolygonNavmesh {
+            vertices: vec![
+                U16Vec3::new(0, 0, 0),
+                U16Vec3::new(0, 0, 2),
+                U16Vec3::new(2, 0, 0),
+                U16Vec3::new(0, 10, 0),
+                U16Vec3::new(0, 10, 2),
+                U16Vec3::new(2, 10, 0),
+            ],
+            polygons: vec![0, 1, 2, 3, 4, 5],
+            polygon_neighbors: vec![
+                PolygonNavmesh::NO_CONNECTION,
+                0x8000,
+                PolygonNavmesh::NO_CONNECTION,
+                PolygonNavmesh::NO_CONNECTION,
+                PolygonNavmesh::NO_CONNECTION,
+                PolygonNavmesh::NO_CONNECTION,
+            ],
+            flags: vec![0; 2],
+            regions: vec![RegionId::from(1), RegionId::from(2)],
+            areas: vec![AreaType::DEFAULT_WALKABLE, AreaType(3)],
+            max_vertices_per_polygon: 3,
+            aabb: Aabb3d {
+                min: Vec3::ZERO,
+                max: Vec3::new(2.0, 1.0, 2.0),
+            },
+            cell_size: 1.0,
+            cell_height: 0.1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn writes_polygons_and_edges() {
+        let svg = poly_mesh().to_svg(&SvgOptions {
+            transform: Affine2::from_scale(Vec2::splat(10.0)),
+            ..Default::default()
+        });
+        assert!(svg.starts_with(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 20 20">"#
+        ));
+        assert!(svg.contains(r##"points="0,0 0,20 20,0" fill="#00c0ff" data-area="255""##));
+        assert_eq!(svg.matches(r#"class="polygon""#).count(), 2);
+        assert!(svg.contains(r#"<path class="portal" d="M0 20L20 0""#));
+        assert!(svg.contains(r#"<path class="boundary" d="M0 0L0 20M20 0L0 0M0 0L0 20"#));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn filters_floors_and_colors_regions() {
+        let options = SvgOptions {
+            height_range: 0.5..2.0,
+            color_by_region: true,
+            ..Default::default()
+        };
+        let svg = poly_mesh().to_svg(&options);
+        assert_eq!(svg.matches(r#"class="polygon""#).count(), 1);
+        let [r, g, b, _] = int_to_color(2, 255);
+        assert!(svg.contains(&format!(
+            r##"fill="#{r:02x}{g:02x}{b:02x}" data-area="3" data-region="2""##
+        )));
+        assert!(!svg.contains("portal"));
+
+        let contours = ContourSet {
+            contours: vec![crate::Contour {
+                vertices: vec![
+                    (U16Vec3::new(0, 0, 0), 0),
+                    (U16Vec3::new(0, 0, 2), 0),
+                    (U16Vec3::new(2, 0, 0), 0),
+                ],
+                region: RegionId::from(1),
+                area: AreaType::DEFAULT_WALKABLE,
+                ..Default::default()
+            }],
+            aabb: poly_mesh().aabb,
+            cell_size: 1.0,
+            cell_height: 0.1,
+            ..Default::default()
+        };
+        assert!(!contours.to_svg(&options).contains("<polygon"));
+        assert!(
+            contours
+                .to_svg(&SvgOptions::default())
+                .contains(r##"<polygon class="contour" points="0,0 0,2 2,0" stroke="#00c0ff""##)
+        );
+    }
+}
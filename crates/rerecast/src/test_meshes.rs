@@ -0,0 +1,126 @@
+//! Hand-made meshes shared by the tests.
+
+use alloc::vec::Vec;
+use glam::{U16Vec3, UVec3, Vec3, Vec3A};
+
+use crate::{Aabb3d, AreaType, DetailNavmesh, NavmeshTile, PolygonNavmesh, TileCoord, TriMesh};
+
+/// A walkable quad of two triangles spanning `aabb` on the xz-plane at the height `aabb.min.y`.
+pub(crate) fn flat_ground(aabb: Aabb3d) -> TriMesh {
+    let (min, max) = (aabb.min, aabb.max);
+    TriMesh {
+        vertices: [
+            (min.x, min.z),
+            (max.x, min.z),
+            (min.x, max.z),
+            (max.x, max.z),
+        ]
+        .map(|(x, z)| Vec3A::new(x, min.y, z))
+        .to_vec(),
+        indices: vec![UVec3::new(0, 2, 1), UVec3::new(1, 2, 3)],
+        area_types: vec![AreaType::DEFAULT_WALKABLE; 2],
+    }
+}
+
+/// A grid of `cols` x `rows` squares of 10x10 on the xz-plane at height 0, where polygon `z * cols + x` is the square at `(x, z)`.
+pub(crate) fn grid(cols: u16, rows: u16) -> PolygonNavmesh {
+    grid_where(cols, rows, |_, _| true)
+}
+
+/// Like [`grid`], but only with the squares for which `keep(x, z)` is true, numbered in row-major order.
+///
+/// Every square is connected to its kept neighbors. All vertices of the full grid are kept.
+pub(crate) fn grid_where(cols: u16, rows: u16, keep: impl Fn(u16, u16) -> bool) -> PolygonNavmesh {
+    const N: u16 = PolygonNavmesh::NO_INDEX;
+    let mut indices = vec![N; cols as usize * rows as usize];
+    let mut count = 0;
+    for z in 0..rows {
+        for x in 0..cols {
+            if keep(x, z) {
+                indices[(z * cols + x) as usize] = count;
+                count += 1;
+            }
+        }
+    }
+    let index = |x: i32, z: i32| {
+        if x < 0 || z < 0 || x >= cols as i32 || z >= rows as i32 {
+            N
+        } else {
+            indices[(z * cols as i32 + x) as usize]
+        }
+    };
+    let vertex = |x: u16, z: u16| z * (cols + 1) + x;
+
+    let vertices = (0..=rows)
+        .flat_map(|z| (0..=cols).map(move |x| U16Vec3::new(x * 10, 0, z * 10)))
+        .collect();
+    let mut polygons = Vec::new();
+    let mut polygon_neighbors = Vec::new();
+    for z in 0..rows {
+        for x in 0..cols {
+            if index(x.into(), z.into()) == N {
+                continue;
+            }
+            polygons.extend([
+                vertex(x, z + 1),
+                vertex(x + 1, z + 1),
+                vertex(x + 1, z),
+                vertex(x, z),
+                N,
+                N,
+            ]);
+            let (x, z) = (i32::from(x), i32::from(z));
+            polygon_neighbors.extend([
+                index(x, z + 1),
+                index(x + 1, z),
+                index(x, z - 1),
+                index(x - 1, z),
+                N,
+                N,
+            ]);
+        }
+    }
+    let count = count as usize;
+    PolygonNavmesh {
+        vertices,
+        polygons,
+        polygon_neighbors,
+        flags: vec![1; count],
+        regions: vec![Default::default(); count],
+        areas: vec![AreaType::DEFAULT_WALKABLE; count],
+        max_vertices_per_polygon: 6,
+        aabb: Aabb3d {
+            min: Vec3::ZERO,
+            max: Vec3::new(cols as f32 * 10.0, 1.0, rows as f32 * 10.0),
+        },
+        cell_size: 1.0,
+        cell_height: 1.0,
+        border_size: 0,
+        max_edge_error: 0.0,
+    }
+}
+
+/// A tile of a navmesh whose tiles are [`grid`]s of `cols` x `rows` squares, placed next to each other on the xz-plane.
+///
+/// The outer edges of the grid are portals to the neighboring tiles.
+pub(crate) fn grid_tile(coord: TileCoord, cols: u16, rows: u16) -> NavmeshTile {
+    let mut poly_mesh = grid(cols, rows);
+    let size = Vec3::new(cols as f32 * 10.0, 0.0, rows as f32 * 10.0);
+    let offset = Vec3::new(coord.x as f32, 0.0, coord.z as f32) * size;
+    poly_mesh.aabb.min += offset;
+    poly_mesh.aabb.max += offset;
+    // The edges of each square are ordered +z, +x, -z, -x, while portals are numbered -x, +z, +x, -z.
+    for neighbors in poly_mesh.polygon_neighbors.chunks_exact_mut(6) {
+        for (edge, side) in [1, 2, 3, 0].into_iter().enumerate() {
+            if neighbors[edge] == PolygonNavmesh::NO_CONNECTION {
+                neighbors[edge] = 0x8000 | side;
+            }
+        }
+    }
+    NavmeshTile {
+        coord,
+        poly_mesh,
+        detail_mesh: DetailNavmesh::default(),
+        off_mesh_connections: Vec::new(),
+    }
+}
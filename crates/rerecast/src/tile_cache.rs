@@ -0,0 +1,502 @@
+//! Runtime obstacles that are carved into a [`TiledNavmesh`] without re-rasterizing its source geometry,
+//! modelled after Detour's `dtTileCache`.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use core::iter;
+use glam::{Vec2, Vec3};
+use slotmap::SlotMap;
+
+use crate::{
+    Aabb3d, AreaType, CompactCell, CompactHeightfield, CompactSpan, ConvexVolume, NavmeshTile,
+    TileCoord, TiledNavmesh, TiledNavmeshConfig, TiledNavmeshError, TriMesh, ops::sin_cos,
+};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+slotmap::new_key_type! {
+    /// A key for an obstacle in a [`TileCache`].
+    pub struct ObstacleKey;
+}
+
+/// A temporary obstacle that makes the navmesh below it unwalkable, e.g. a crate pushed around by the player.
+///
+/// Obstacles only carve into walkable surface, they do not create new surface on top of themselves.
+/// The carved area is grown by [`Config::walkable_radius`](crate::Config::walkable_radius), like the rest of the navmesh borders.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum Obstacle {
+    /// A vertical cylinder.
+    Cylinder {
+        /// The center of the bottom of the cylinder. `[Units: wu]`
+        position: Vec3,
+        /// The radius of the cylinder. `[Limit: > 0] [Units: wu]`
+        radius: f32,
+        /// The height of the cylinder. `[Limit: > 0] [Units: wu]`
+        height: f32,
+    },
+    /// An axis-aligned box.
+    Box(Aabb3d),
+    /// A box rotated around the y-axis.
+    OrientedBox {
+        /// The center of the box. `[Units: wu]`
+        center: Vec3,
+        /// Half of the size of the box along each of its local axes. `[Limit: >= 0] [Units: wu]`
+        half_extents: Vec3,
+        /// The rotation of the box around the y-axis. `[Units: radians]`
+        y_rotation: f32,
+    },
+}
+
+impl Obstacle {
+    /// Returns the world-space bounds of the obstacle.
+    pub fn bounds(&self) -> Aabb3d {
+        match *self {
+            Obstacle::Cylinder {
+                position,
+                radius,
+                height,
+            } => Aabb3d {
+                min: position - Vec3::new(radius, 0.0, radius),
+                max: position + Vec3::new(radius, height, radius),
+            },
+            Obstacle::Box(aabb) => aabb,
+            Obstacle::OrientedBox {
+                center,
+                half_extents,
+                y_rotation,
+            } => {
+                let (sin, cos) = sin_cos(y_rotation);
+                let (sin, cos) = (sin.max(-sin), cos.max(-cos));
+                let extents = Vec3::new(
+                    cos * half_extents.x + sin * half_extents.z,
+                    half_extents.y,
+                    sin * half_extents.x + cos * half_extents.z,
+                );
+                Aabb3d::new(center, extents)
+            }
+        }
+    }
+
+    /// Marks the spans of `heightfield` within the obstacle as not walkable.
+    fn mark(&self, heightfield: &mut CompactHeightfield) {
+        match *self {
+            Obstacle::Cylinder {
+                position,
+                radius,
+                height,
+            } => heightfield.mark_cylinder_area(position, radius, height, AreaType::NOT_WALKABLE),
+            Obstacle::Box(aabb) => heightfield.mark_convex_poly_area(&ConvexVolume {
+                vertices: vec![
+                    Vec2::new(aabb.min.x, aabb.min.z),
+                    Vec2::new(aabb.max.x, aabb.min.z),
+                    Vec2::new(aabb.max.x, aabb.max.z),
+                    Vec2::new(aabb.min.x, aabb.max.z),
+                ],
+                min_y: aabb.min.y,
+                max_y: aabb.max.y,
+                area: AreaType::NOT_WALKABLE,
+            }),
+            Obstacle::OrientedBox {
+                center,
+                half_extents,
+                y_rotation,
+            } => {
+                let (sin, cos) = sin_cos(y_rotation);
+                let vertices = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                    .map(|(x, z)| {
+                        let (x, z) = (x * half_extents.x, z * half_extents.z);
+                        Vec2::new(center.x + cos * x + sin * z, center.z - sin * x + cos * z)
+                    })
+                    .to_vec();
+                heightfield.mark_convex_poly_area(&ConvexVolume {
+                    vertices,
+                    min_y: center.y - half_extents.y,
+                    max_y: center.y + half_extents.y,
+                    area: AreaType::NOT_WALKABLE,
+                });
+            }
+        }
+    }
+}
+
+/// Caches the rasterized geometry of every tile of a tiled navmesh, so that tiles can be rebuilt quickly
+/// when [`Obstacle`]s are added or removed at runtime.
+///
+/// Each tile's walkable spans are stored compressed, before erosion. Rebuilding a tile decompresses them, marks the obstacles
+/// touching the tile and runs the remaining stages of the build process, from erosion to the detail mesh.
+///
+/// Off-mesh connections from [`Config::off_mesh_connections`](crate::Config::off_mesh_connections) are kept, but
+/// [`Config::jump_links`](crate::Config::jump_links) are not generated for cached tiles, as they need the solid geometry.
+///
+/// ```no_run
+/// # use rerecast::*;
+/// # fn example(config: TiledNavmeshConfig, trimesh: TriMesh) -> Result<(), TiledNavmeshError> {
+/// let mut cache = TileCache::new(config, &trimesh)?;
+/// let mut navmesh = cache.build_navmesh()?;
+///
+/// let crate_obstacle = cache.add_obstacle(Obstacle::Cylinder {
+///     position: glam::Vec3::new(5.0, 0.0, 5.0),
+///     radius: 1.0,
+///     height: 2.0,
+/// });
+/// // Once per frame:
+/// cache.update(&mut navmesh, 4)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TileCache {
+    config: TiledNavmeshConfig,
+    layers: BTreeMap<TileCoord, CompressedLayer>,
+    obstacles: SlotMap<ObstacleKey, Obstacle>,
+    /// Tiles whose navmesh does not reflect the current obstacles yet.
+    dirty_tiles: BTreeSet<TileCoord>,
+}
+
+impl TileCache {
+    /// Rasterizes all tiles of `config` and caches their walkable spans.
+    pub fn new(config: TiledNavmeshConfig, trimesh: &TriMesh) -> Result<Self, TiledNavmeshError> {
+        let coords: Vec<_> = config.tile_coords().collect();
+        let rasterize = |&coord: &TileCoord| {
+            let (heightfield, _) = config.rasterize_tile(coord, trimesh)?;
+            Ok((coord, CompressedLayer::new(heightfield)))
+        };
+        #[cfg(feature = "parallel")]
+        let layers = coords.par_iter().map(rasterize).collect::<Result<_, _>>()?;
+        #[cfg(not(feature = "parallel"))]
+        let layers = coords.iter().map(rasterize).collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            config,
+            layers,
+            obstacles: SlotMap::with_key(),
+            dirty_tiles: BTreeSet::new(),
+        })
+    }
+
+    /// Returns the configuration the tiles are built with.
+    #[inline]
+    pub fn config(&self) -> &TiledNavmeshConfig {
+        &self.config
+    }
+
+    /// Returns the total size of the compressed tile data in bytes.
+    pub fn compressed_size(&self) -> usize {
+        self.layers.values().map(|layer| layer.data.len()).sum()
+    }
+
+    /// Builds a single tile from its cached spans and the current obstacles.
+    ///
+    /// Returns `None` if the tile is not part of the cache.
+    pub fn build_tile(&self, coord: TileCoord) -> Option<Result<NavmeshTile, TiledNavmeshError>> {
+        let mut heightfield = self.layers.get(&coord)?.decompress();
+        let reach = self.obstacle_reach();
+        let tile_aabb = self.config.tile_aabb(coord);
+        for obstacle in self.obstacles.values() {
+            if grow(obstacle.bounds(), reach).intersects(&tile_aabb) {
+                obstacle.mark(&mut heightfield);
+            }
+        }
+        Some(self.config.build_tile(coord, heightfield, None))
+    }
+
+    /// Builds all tiles into a new navmesh and marks them as up to date.
+    pub fn build_navmesh(&mut self) -> Result<TiledNavmesh, TiledNavmeshError> {
+        let navmesh = TiledNavmesh::from_tiles(
+            self.layers
+                .keys()
+                .filter_map(|&coord| self.build_tile(coord))
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+        self.dirty_tiles.clear();
+        Ok(navmesh)
+    }
+
+    /// Adds an obstacle. The navmesh reflects it after the affected tiles have been rebuilt by [`Self::update`].
+    pub fn add_obstacle(&mut self, obstacle: Obstacle) -> ObstacleKey {
+        self.mark_dirty(&obstacle);
+        self.obstacles.insert(obstacle)
+    }
+
+    /// Removes an obstacle. The navmesh reflects it after the affected tiles have been rebuilt by [`Self::update`].
+    pub fn remove_obstacle(&mut self, key: ObstacleKey) -> Option<Obstacle> {
+        let obstacle = self.obstacles.remove(key)?;
+        self.mark_dirty(&obstacle);
+        Some(obstacle)
+    }
+
+    /// Returns the obstacle with the given key.
+    #[inline]
+    pub fn obstacle(&self, key: ObstacleKey) -> Option<&Obstacle> {
+        self.obstacles.get(key)
+    }
+
+    /// Returns all obstacles.
+    pub fn obstacles(&self) -> impl Iterator<Item = (ObstacleKey, &Obstacle)> {
+        self.obstacles.iter()
+    }
+
+    /// Returns whether all tiles reflect the current obstacles.
+    #[inline]
+    pub fn is_up_to_date(&self) -> bool {
+        self.dirty_tiles.is_empty()
+    }
+
+    /// Rebuilds up to `max_tiles` tiles affected by added or removed obstacles and replaces them in `navmesh`.
+    ///
+    /// Returns the number of rebuilt tiles. Replacing a tile invalidates the [`PolyRef`](crate::PolyRef)s to its polygons.
+    pub fn update(
+        &mut self,
+        navmesh: &mut TiledNavmesh,
+        max_tiles: usize,
+    ) -> Result<usize, TiledNavmeshError> {
+        let mut rebuilt = 0;
+        while rebuilt < max_tiles && self.rebuild_next_tile(navmesh)? {
+            rebuilt += 1;
+        }
+        Ok(rebuilt)
+    }
+
+    /// Rebuilds tiles affected by added or removed obstacles until `budget` is used up, and replaces them in `navmesh`.
+    ///
+    /// At least one tile is rebuilt per call if any is affected, so that updates progress even with a tiny budget.
+    /// Returns the number of rebuilt tiles.
+    #[cfg(feature = "std")]
+    pub fn update_with_time_budget(
+        &mut self,
+        navmesh: &mut TiledNavmesh,
+        budget: core::time::Duration,
+    ) -> Result<usize, TiledNavmeshError> {
+        let start = std::time::Instant::now();
+        let mut rebuilt = 0;
+        while self.rebuild_next_tile(navmesh)? {
+            rebuilt += 1;
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+        Ok(rebuilt)
+    }
+
+    /// Rebuilds the next dirty tile. Returns `false` if there was none.
+    fn rebuild_next_tile(&mut self, navmesh: &mut TiledNavmesh) -> Result<bool, TiledNavmeshError> {
+        let Some(coord) = self.dirty_tiles.pop_first() else {
+            return Ok(false);
+        };
+        if let Some(tile) = self.build_tile(coord) {
+            navmesh.add_tile(tile?);
+        }
+        Ok(true)
+    }
+
+    fn mark_dirty(&mut self, obstacle: &Obstacle) {
+        let bounds = grow(obstacle.bounds(), self.obstacle_reach());
+        let dirty = self
+            .layers
+            .keys()
+            .filter(|&&coord| self.config.tile_aabb(coord).intersects(&bounds));
+        self.dirty_tiles.extend(dirty);
+    }
+
+    /// The distance by which an obstacle can affect the navmesh beyond its bounds, because of erosion.
+    fn obstacle_reach(&self) -> f32 {
+        let config = &self.config.config;
+        config.walkable_radius as f32 * config.cell_size
+    }
+}
+
+fn grow(aabb: Aabb3d, distance: f32) -> Aabb3d {
+    let distance = Vec3::new(distance, 0.0, distance);
+    Aabb3d {
+        min: aabb.min - distance,
+        max: aabb.max + distance,
+    }
+}
+
+/// The walkable spans of a tile, compressed with run-length encoding.
+#[derive(Debug, Clone)]
+struct CompressedLayer {
+    /// The heightfield without cells, spans and areas.
+    header: CompactHeightfield,
+    span_count: usize,
+    data: Vec<u8>,
+}
+
+impl CompressedLayer {
+    fn new(heightfield: CompactHeightfield) -> Self {
+        // Store each byte of the cells and spans in its own plane, as neighboring values of the same byte tend to be equal.
+        let mut bytes = Vec::with_capacity(heightfield.cells.len() + heightfield.spans.len() * 7);
+        bytes.extend(heightfield.cells.iter().map(|cell| cell.count()));
+        for shift in [0, 8] {
+            bytes.extend(heightfield.spans.iter().map(|span| (span.y >> shift) as u8));
+        }
+        for shift in [0, 8, 16, 24] {
+            bytes.extend(
+                heightfield
+                    .spans
+                    .iter()
+                    .map(|span| (span.data >> shift) as u8),
+            );
+        }
+        bytes.extend(heightfield.areas.iter().map(|area| area.0));
+
+        Self {
+            span_count: heightfield.spans.len(),
+            data: compress(&bytes),
+            header: CompactHeightfield {
+                cells: Vec::new(),
+                spans: Vec::new(),
+                areas: Vec::new(),
+                ..heightfield
+            },
+        }
+    }
+
+    fn decompress(&self) -> CompactHeightfield {
+        let bytes = decompress(&self.data).expect("Internal error: corrupt tile cache layer");
+        let cell_count = self.header.width as usize * self.header.height as usize;
+        let (counts, bytes) = bytes.split_at(cell_count);
+        let plane = |index: usize| &bytes[index * self.span_count..(index + 1) * self.span_count];
+
+        let mut heightfield = self.header.clone();
+        let mut index = 0;
+        heightfield.cells = counts
+            .iter()
+            .map(|&count| {
+                let mut cell = CompactCell::default();
+                cell.set_index(index);
+                cell.set_count(count);
+                index += count as u32;
+                cell
+            })
+            .collect();
+        heightfield.spans = (0..self.span_count)
+            .map(|i| CompactSpan {
+                y: u16::from_le_bytes([plane(0)[i], plane(1)[i]]),
+                data: u32::from_le_bytes([plane(2)[i], plane(3)[i], plane(4)[i], plane(5)[i]]),
+                ..Default::default()
+            })
+            .collect();
+        heightfield.areas = plane(6).iter().map(|&area| AreaType(area)).collect();
+        heightfield
+    }
+}
+
+/// Compresses `data` with the PackBits run-length encoding.
+fn compress(data: &[u8]) -> Vec<u8> {
+    const MAX_RUN: usize = 128;
+    let mut compressed = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let run = data[i..]
+            .iter()
+            .take(MAX_RUN)
+            .take_while(|&&byte| byte == data[i])
+            .count();
+        if run >= 2 {
+            compressed.push((257 - run) as u8);
+            compressed.push(data[i]);
+            i += run;
+            continue;
+        }
+        // Copy bytes literally until the next run starts.
+        let start = i;
+        while i < data.len() && i - start < MAX_RUN && data.get(i + 1) != Some(&data[i]) {
+            i += 1;
+        }
+        compressed.push((i - start - 1) as u8);
+        compressed.extend_from_slice(&data[start..i]);
+    }
+    compressed
+}
+
+/// Decompresses data compressed by [`compress`]. Returns `None` if the data is corrupt.
+fn decompress(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    while let Some((&header, rest)) = data.split_first() {
+        data = match header {
+            0..128 => {
+                let len = header as usize + 1;
+                decompressed.extend_from_slice(rest.get(..len)?);
+                &rest[len..]
+            }
+            128 => return None,
+            _ => {
+                let (&byte, rest) = rest.split_first()?;
+                decompressed.extend(iter::repeat_n(byte, 257 - header as usize));
+                rest
+            }
+        };
+    }
+    Some(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConfigBuilder, NavmeshQuery, QueryFilter, test_meshes};
+
+    #[test]
+    fn compression_round_trips() {
+        let data = [[0; 300].as_slice(), &[1, 2, 3, 3, 4], &[7; 5], &[9]].concat();
+        let compressed = compress(&data);
+        assert!(compressed.len() < 20);
+        assert_eq!(decompress(&compressed).unwrap(), data);
+        assert_eq!(decompress(&compress(&[])).unwrap(), [0_u8; 0]);
+    }
+
+    #[test]
+    fn obstacles_carve_into_rebuilt_tiles() {
+        let config = ConfigBuilder {
+            tiling: true,
+            tile_size: 32,
+            aabb: Aabb3d {
+                min: Vec3::new(0.0, -1.0, 0.0),
+                max: Vec3::new(19.2, 4.0, 9.6),
+            },
+            ..Default::default()
+        }
+        .build();
+        let trimesh = test_meshes::flat_ground(Aabb3d {
+            min: Vec3::ZERO,
+            max: Vec3::new(19.2, 0.0, 9.6),
+        });
+        let mut cache = TileCache::new(TiledNavmeshConfig::new(config).unwrap(), &trimesh).unwrap();
+        let mut navmesh = cache.build_navmesh().unwrap();
+        assert_eq!(navmesh.tile_count(), 2);
+        assert!(cache.compressed_size() > 0);
+        // Without obstacles, cached tiles are identical to freshly generated ones.
+        let generated = cache.config().generate_tiles(&trimesh).unwrap();
+        let cached = cache.build_tile(generated[0].coord).unwrap().unwrap();
+        assert_eq!(cached.poly_mesh, generated[0].poly_mesh);
+
+        let center = Vec3::new(4.8, 0.0, 4.8);
+        let walkable_at = |navmesh: &TiledNavmesh| {
+            let (_, tile) = navmesh.tiles().find(|(_, tile)| tile.coord.x == 0).unwrap();
+            let query = NavmeshQuery::new(&tile.poly_mesh);
+            let filter = QueryFilter::default();
+            query
+                .find_nearest_polygon(center, Vec3::new(0.1, 1.0, 0.1), &filter)
+                .is_some_and(|(_, nearest)| nearest.x == center.x && nearest.z == center.z)
+        };
+        assert!(walkable_at(&navmesh));
+
+        let obstacle = cache.add_obstacle(Obstacle::Cylinder {
+            position: center - Vec3::Y,
+            radius: 1.0,
+            height: 2.0,
+        });
+        assert!(!cache.is_up_to_date());
+        assert_eq!(cache.update(&mut navmesh, 1).unwrap(), 1);
+        assert!(cache.is_up_to_date());
+        assert!(!walkable_at(&navmesh));
+
+        cache.remove_obstacle(obstacle).unwrap();
+        assert_eq!(cache.update(&mut navmesh, 8).unwrap(), 1);
+        assert!(walkable_at(&navmesh));
+    }
+}
@@ -5,8 +5,9 @@
 //! in parallel, dramatically improving generation times for large environments.
 
 use crate::{
-    ops::ceil,
-    Aabb3d, Config, DetailNavmesh, HeightfieldBuilder, PolygonNavmesh, TriMesh,
+    ops::{ceil, floor},
+    Aabb3d, CompactHeightfield, Config, DetailNavmesh, Heightfield, HeightfieldBuilder,
+    NavmeshQuery, PolygonNavmesh, SnappedOffMeshConnection, TriMesh,
 };
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
@@ -17,7 +18,8 @@ use thiserror::Error;
 use rayon::prelude::*;
 
 /// A tile coordinate on the XZ plane.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileCoord {
     /// X coordinate of the tile
     pub x: u16,
@@ -37,7 +39,8 @@ pub struct TiledNavmeshConfig {
 }
 
 /// A single tile of a navmesh.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct NavmeshTile {
     /// The tile's coordinate
     pub coord: TileCoord,
@@ -45,6 +48,10 @@ pub struct NavmeshTile {
     pub poly_mesh: PolygonNavmesh,
     /// The tile's detail mesh
     pub detail_mesh: DetailNavmesh,
+    /// The off-mesh connections starting in this tile.
+    ///
+    /// Connection `i` is referenced like a polygon with the index `poly_mesh.polygon_count() + i`.
+    pub off_mesh_connections: Vec<SnappedOffMeshConnection>,
 }
 
 /// Errors that can occur during tiled navmesh generation.
@@ -120,6 +127,19 @@ impl TiledNavmeshConfig {
         (0..tiles_z).flat_map(move |z| (0..tiles_x).map(move |x| TileCoord { x, z }))
     }
 
+    /// Returns the coordinate of the tile containing `position` on the xz-plane, not counting the tile borders,
+    /// or `None` if the position lies outside of the tiled area.
+    pub fn tile_coord_at(&self, position: Vec3) -> Option<TileCoord> {
+        let tile_world_size = self.config.tile_size as f32 * self.config.cell_size;
+        let x = floor((position.x - self.config.aabb.min.x) / tile_world_size);
+        let z = floor((position.z - self.config.aabb.min.z) / tile_world_size);
+        let inside = |value: f32, count: u16| value >= 0.0 && value < f32::from(count);
+        (inside(x, self.tiles_x) && inside(z, self.tiles_z)).then_some(TileCoord {
+            x: x as u16,
+            z: z as u16,
+        })
+    }
+
     /// Calculates the AABB for a specific tile, including border.
     pub fn tile_aabb(&self, coord: TileCoord) -> Aabb3d {
         let tile_world_size = self.config.tile_size as f32 * self.config.cell_size;
@@ -142,6 +162,18 @@ impl TiledNavmeshConfig {
         coord: TileCoord,
         trimesh: &TriMesh,
     ) -> Result<NavmeshTile, TiledNavmeshError> {
+        let (compact_heightfield, solid_heightfield) = self.rasterize_tile(coord, trimesh)?;
+        self.build_tile(coord, compact_heightfield, solid_heightfield.as_ref())
+    }
+
+    /// Rasterizes the geometry of a single tile into a compact heightfield that has not been eroded yet.
+    ///
+    /// Also returns the solid heightfield if it is needed for generating jump links.
+    pub(crate) fn rasterize_tile(
+        &self,
+        coord: TileCoord,
+        trimesh: &TriMesh,
+    ) -> Result<(CompactHeightfield, Option<Heightfield>), TiledNavmeshError> {
         let tile_aabb = self.tile_aabb(coord);
 
         // Build heightfield for this tile
@@ -162,11 +194,26 @@ impl TiledNavmeshConfig {
             )
             .map_err(|e| TiledNavmeshError::Rasterization(e.to_string()))?;
 
+        // Keep the solid geometry around for generating jump links
+        let solid_heightfield = self.config.jump_links.as_ref().map(|_| heightfield.clone());
+
         // Build compact heightfield
-        let mut compact_heightfield = heightfield
+        let compact_heightfield = heightfield
             .into_compact(self.config.walkable_height, self.config.walkable_climb)
             .map_err(|e| TiledNavmeshError::CompactHeightfield(e.to_string()))?;
 
+        Ok((compact_heightfield, solid_heightfield))
+    }
+
+    /// Builds a single tile's navmesh from the compact heightfield returned by [`Self::rasterize_tile`].
+    pub(crate) fn build_tile(
+        &self,
+        coord: TileCoord,
+        mut compact_heightfield: CompactHeightfield,
+        solid_heightfield: Option<&Heightfield>,
+    ) -> Result<NavmeshTile, TiledNavmeshError> {
+        let tile_aabb = self.tile_aabb(coord);
+
         // Erode walkable area
         compact_heightfield.erode_walkable_area(self.config.walkable_radius);
 
@@ -208,10 +255,53 @@ impl TiledNavmeshConfig {
         )
         .map_err(|e| TiledNavmeshError::DetailMesh(e.to_string()))?;
 
+        // Generate jump links
+        let jump_links = match (&self.config.jump_links, solid_heightfield) {
+            (Some(config), Some(heightfield)) => poly_mesh.generate_jump_links(heightfield, config),
+            _ => Vec::new(),
+        };
+
+        // Snap the off-mesh connections starting in this tile, excluding its border.
+        let border_world_size = self.config.border_size as f32 * self.config.cell_size;
+        let (min, max) = (
+            tile_aabb.min + border_world_size,
+            tile_aabb.max - border_world_size,
+        );
+        let query = NavmeshQuery::new(&poly_mesh).with_detail_mesh(&detail_mesh);
+        let max_climb = self.config.walkable_climb as f32 * self.config.cell_height;
+        let off_mesh_connections = self
+            .config
+            .off_mesh_connections
+            .iter()
+            .chain(&jump_links)
+            .filter(|connection| {
+                let start = connection.start;
+                start.x >= min.x && start.x < max.x && start.z >= min.z && start.z < max.z
+            })
+            .filter_map(|connection| {
+                let end = connection.end;
+                if end.x >= min.x && end.x < max.x && end.z >= min.z && end.z < max.z {
+                    return connection.snap(&query, max_climb);
+                }
+                // Connections leading into other tiles are linked by `TiledNavmesh` once both tiles are added.
+                let Some(end_tile) = self.tile_coord_at(end) else {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        "Dropping off-mesh connection from {} to {}, which leaves the navmesh",
+                        connection.start,
+                        connection.end,
+                    );
+                    return None;
+                };
+                connection.snap_start(&query, max_climb, end_tile)
+            })
+            .collect();
+
         Ok(NavmeshTile {
             coord,
             poly_mesh,
             detail_mesh,
+            off_mesh_connections,
         })
     }
 
@@ -386,4 +476,43 @@ mod tests {
             assert!(tile.coord.z < tiled_config.tiles_z);
         }
     }
+
+    #[test]
+    fn links_off_mesh_connections_that_leave_their_tile() {
+        use crate::{OffMeshConnection, TiledNavmesh, test_meshes};
+
+        let mut trimesh = test_meshes::flat_ground(Aabb3d {
+            min: Vec3::ZERO,
+            max: Vec3::new(40.0, 0.0, 10.0),
+        });
+        let connection = OffMeshConnection {
+            start: Vec3::new(2.0, 0.0, 5.0),
+            end: Vec3::new(30.0, 0.0, 5.0),
+            ..Default::default()
+        };
+        let config = ConfigBuilder {
+            tiling: true,
+            tile_size: 32,
+            aabb: trimesh.compute_aabb().unwrap(),
+            off_mesh_connections: vec![connection.clone()],
+            ..Default::default()
+        }
+        .build();
+        trimesh.mark_walkable_triangles(config.walkable_slope_angle);
+        let tiled_config = TiledNavmeshConfig::new(config).unwrap();
+        let start_tile = tiled_config.tile_coord_at(connection.start).unwrap();
+        let end_tile = tiled_config.tile_coord_at(connection.end).unwrap();
+        assert_ne!(start_tile, end_tile);
+
+        let tiles = tiled_config.generate_tiles(&trimesh).unwrap();
+        let tile = tiles.iter().find(|tile| tile.coord == start_tile).unwrap();
+        assert_eq!(tile.off_mesh_connections.len(), 1);
+        assert_eq!(tile.off_mesh_connections[0].end_tile, Some(end_tile));
+
+        let navmesh = TiledNavmesh::from_tiles(tiles);
+        let query = navmesh.query();
+        let (_, linked) = query.off_mesh_connections().next().unwrap();
+        let end_polygon = query.poly_ref(linked.end_polygon);
+        assert_eq!(navmesh.tile_by_ref(end_polygon).unwrap().coord, end_tile);
+    }
 }
@@ -0,0 +1,223 @@
+//! Running the generation pipeline and reporting statistics about each stage.
+
+use std::time::Instant;
+
+use anyhow::Context as _;
+use rerecast::{
+    Aabb3d, BakedNavmesh, Config, ConfigBuilder, DetailNavmesh, HeightfieldBuilder,
+    TiledNavmeshConfig, TriMesh,
+};
+
+/// Generates a navmesh from `trimesh`, in tiles if [`ConfigBuilder::tiling`] is set.
+///
+/// If the builder has no AABB, the AABB of the geometry is used.
+pub(crate) fn bake(
+    mut builder: ConfigBuilder,
+    mut trimesh: TriMesh,
+) -> anyhow::Result<(Config, BakedNavmesh)> {
+    if builder.aabb == Aabb3d::default() {
+        builder.aabb = trimesh.compute_aabb().context("The geometry is empty")?;
+    }
+    let tiling = builder.tiling;
+    let config = builder.build();
+    trimesh.mark_walkable_triangles(config.walkable_slope_angle);
+
+    let navmesh = if tiling {
+        bake_tiled(&config, &trimesh)?
+    } else {
+        bake_single(&config, trimesh)?
+    };
+    Ok((config, navmesh))
+}
+
+fn bake_tiled(config: &Config, trimesh: &TriMesh) -> anyhow::Result<BakedNavmesh> {
+    let mut stages = Stages::new();
+    let tiled_config = TiledNavmeshConfig::new(config.clone())?;
+    let tiles = tiled_config.generate_tiles(trimesh)?;
+    stages.finish(
+        "tiles",
+        format!(
+            "{} tiles ({} x {}), {} polygons, {} detail triangles, {} off-mesh connections",
+            tiles.len(),
+            tiled_config.tiles_x,
+            tiled_config.tiles_z,
+            tiles
+                .iter()
+                .map(|tile| tile.poly_mesh.polygon_count())
+                .sum::<usize>(),
+            tiles
+                .iter()
+                .map(|tile| tile.detail_mesh.triangles.len())
+                .sum::<usize>(),
+            tiles
+                .iter()
+                .map(|tile| tile.off_mesh_connections.len())
+                .sum::<usize>(),
+        ),
+    );
+    stages.total();
+    Ok(BakedNavmesh::Tiled(tiles))
+}
+
+fn bake_single(config: &Config, trimesh: TriMesh) -> anyhow::Result<BakedNavmesh> {
+    if !config.off_mesh_connections.is_empty() || config.jump_links.is_some() {
+        eprintln!(
+            "Warning: off-mesh connections and jump links are only baked into tiled navmeshes, pass --tiled to include them"
+        );
+    }
+    let mut stages = Stages::new();
+
+    let mut heightfield = HeightfieldBuilder {
+        aabb: config.aabb,
+        cell_size: config.cell_size,
+        cell_height: config.cell_height,
+    }
+    .build()?;
+    heightfield.populate_from_trimesh(trimesh, config.walkable_height, config.walkable_climb)?;
+    stages.finish(
+        "heightfield",
+        format!(
+            "{} x {} cells, {} spans",
+            heightfield.width,
+            heightfield.height,
+            heightfield.allocated_spans.len()
+        ),
+    );
+
+    let mut compact_heightfield =
+        heightfield.into_compact(config.walkable_height, config.walkable_climb)?;
+    compact_heightfield.erode_walkable_area(config.walkable_radius);
+    for volume in &config.area_volumes {
+        compact_heightfield.mark_convex_poly_area(volume);
+    }
+    let walkable_spans = compact_heightfield
+        .areas
+        .iter()
+        .filter(|area| area.is_walkable())
+        .count();
+    stages.finish(
+        "compact heightfield",
+        format!(
+            "{walkable_spans} walkable spans of {}",
+            compact_heightfield.spans.len()
+        ),
+    );
+
+    compact_heightfield.build_distance_field();
+    compact_heightfield.build_regions(
+        config.border_size,
+        config.min_region_area,
+        config.merge_region_area,
+    )?;
+    stages.finish(
+        "regions",
+        format!("{} regions", compact_heightfield.max_region.bits()),
+    );
+
+    let contours = compact_heightfield.build_contours(
+        config.max_simplification_error,
+        config.max_edge_len,
+        config.contour_flags,
+    );
+    stages.finish("contours", format!("{} contours", contours.contours.len()));
+
+    let poly_mesh = contours.into_polygon_mesh(config.max_vertices_per_polygon)?;
+    stages.finish(
+        "polygon mesh",
+        format!(
+            "{} polygons, {} vertices",
+            poly_mesh.polygon_count(),
+            poly_mesh.vertices.len()
+        ),
+    );
+
+    let detail_mesh = DetailNavmesh::new(
+        &poly_mesh,
+        &compact_heightfield,
+        config.detail_sample_dist,
+        config.detail_sample_max_error,
+    )?;
+    stages.finish(
+        "detail mesh",
+        format!(
+            "{} triangles, {} vertices",
+            detail_mesh.triangles.len(),
+            detail_mesh.vertices.len()
+        ),
+    );
+    stages.total();
+
+    Ok(BakedNavmesh::Single {
+        poly_mesh,
+        detail_mesh,
+    })
+}
+
+/// Prints the time taken by each stage of the pipeline.
+struct Stages {
+    start: Instant,
+    last: Instant,
+}
+
+impl Stages {
+    fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            last: now,
+        }
+    }
+
+    fn finish(&mut self, name: &str, stats: String) {
+        let now = Instant::now();
+        let elapsed = now - self.last;
+        println!(
+            "{name:<20} {:>10.2} ms  {stats}",
+            elapsed.as_secs_f64() * 1000.0
+        );
+        self.last = now;
+    }
+
+    fn total(&self) {
+        println!(
+            "{:<20} {:>10.2} ms",
+            "total",
+            self.start.elapsed().as_secs_f64() * 1000.0
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+    use rerecast::Aabb3d;
+
+    fn ground() -> TriMesh {
+        test_utils::flat_ground(Aabb3d {
+            min: Vec3::ZERO,
+            max: Vec3::new(20.0, 0.0, 20.0),
+        })
+    }
+
+    #[test]
+    fn bakes_single_and_tiled_navmeshes() {
+        let (_, navmesh) = bake(ConfigBuilder::default(), ground()).unwrap();
+        let BakedNavmesh::Single { poly_mesh, .. } = navmesh else {
+            panic!("expected a single navmesh");
+        };
+        assert!(poly_mesh.polygon_count() > 0);
+
+        let builder = ConfigBuilder {
+            tiling: true,
+            ..Default::default()
+        };
+        let (config, navmesh) = bake(builder, ground()).unwrap();
+        assert_eq!(config.aabb, ground().compute_aabb().unwrap());
+        let BakedNavmesh::Tiled(tiles) = navmesh else {
+            panic!("expected a tiled navmesh");
+        };
+        assert!(tiles.len() > 1);
+        assert!(tiles.iter().any(|tile| tile.poly_mesh.polygon_count() > 0));
+    }
+}
@@ -0,0 +1,170 @@
+//! Loading of the geometry and data files passed on the command line.
+
+use std::{fs, path::Path};
+
+use anyhow::{Context as _, bail};
+use glam::{UVec3, Vec2, Vec3A};
+use rerecast::{AreaType, ConvexVolume, OffMeshConnection, TriMesh};
+use serde::{Deserialize, de::DeserializeOwned};
+
+/// The contents of the file passed with `--volumes`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct VolumesFile {
+    volumes: Vec<Volume>,
+}
+
+impl VolumesFile {
+    pub(crate) fn into_convex_volumes(self) -> Vec<ConvexVolume> {
+        self.volumes.into_iter().map(ConvexVolume::from).collect()
+    }
+}
+
+/// A volume either as a [`ConvexVolume`] or in the `convex_volumes.json` format exported from the Recast demo.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Volume {
+    Rerecast(ConvexVolume),
+    Recast {
+        verts: Vec<[f32; 3]>,
+        hmin: f32,
+        hmax: f32,
+        area: u8,
+    },
+}
+
+impl From<Volume> for ConvexVolume {
+    fn from(volume: Volume) -> Self {
+        match volume {
+            Volume::Rerecast(volume) => volume,
+            Volume::Recast {
+                verts,
+                hmin,
+                hmax,
+                area,
+            } => ConvexVolume {
+                vertices: verts.iter().map(|[x, _y, z]| Vec2::new(*x, *z)).collect(),
+                min_y: hmin,
+                max_y: hmax,
+                area: AreaType::from(area),
+            },
+        }
+    }
+}
+
+/// The contents of the file passed with `--off-mesh-connections`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct OffMeshConnectionsFile {
+    pub(crate) connections: Vec<OffMeshConnection>,
+}
+
+/// The JSON geometry format used by the test data, as exported from the Recast demo.
+#[derive(Debug, Deserialize)]
+struct JsonGeometry {
+    verts: Vec<[f32; 3]>,
+    tris: Vec<[u32; 3]>,
+}
+
+/// Returns the lowercase extension of `path`, or an empty string if it has none.
+pub(crate) fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Loads a JSON or TOML file, depending on its extension.
+pub(crate) fn load_data<T: DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let source = read(path)?;
+    let data = match extension(path).as_str() {
+        "json" => serde_json::from_str(&source).map_err(anyhow::Error::from),
+        "toml" => toml::from_str(&source).map_err(anyhow::Error::from),
+        other => bail!("Unsupported data format `.{other}`, expected `.json` or `.toml`"),
+    };
+    data.with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Loads level geometry from an OBJ or JSON file. Whether triangles are walkable is determined later by their slope.
+pub(crate) fn load_geometry(path: &Path) -> anyhow::Result<TriMesh> {
+    let source = read(path)?;
+    match extension(path).as_str() {
+        "obj" => TriMesh::from_obj(&source, &[])
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        "json" => parse_json_geometry(&source)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        other => bail!("Unsupported geometry format `.{other}`, expected `.obj` or `.json`"),
+    }
+}
+
+fn parse_json_geometry(source: &str) -> anyhow::Result<TriMesh> {
+    let geometry: JsonGeometry = serde_json::from_str(source)?;
+    if let Some(index) = geometry
+        .tris
+        .iter()
+        .flatten()
+        .find(|index| **index as usize >= geometry.verts.len())
+    {
+        bail!(
+            "Triangle index {index} is out of range for {} vertices",
+            geometry.verts.len()
+        );
+    }
+    Ok(TriMesh {
+        vertices: geometry.verts.iter().map(|v| Vec3A::from(*v)).collect(),
+        indices: geometry.tris.iter().map(|i| UVec3::from(*i)).collect(),
+        area_types: vec![AreaType::NOT_WALKABLE; geometry.tris.len()],
+    })
+}
+
+fn read(path: &Path) -> anyhow::Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rerecast::ConfigBuilder;
+
+    #[test]
+    fn parses_json_geometry() {
+        let trimesh = parse_json_geometry(
+            r#"{"verts": [[0, 0, 0], [1, 0, 0], [0, 0, 1]], "tris": [[0, 2, 1]]}"#,
+        )
+        .unwrap();
+        assert_eq!(trimesh.vertices.len(), 3);
+        assert_eq!(trimesh.indices, [UVec3::new(0, 2, 1)]);
+        assert_eq!(trimesh.area_types, [AreaType::NOT_WALKABLE]);
+
+        assert!(parse_json_geometry(r#"{"verts": [[0, 0, 0]], "tris": [[0, 1, 2]]}"#).is_err());
+    }
+
+    #[test]
+    fn parses_both_volume_formats() {
+        let volumes: VolumesFile = serde_json::from_str(
+            r#"{"volumes": [
+                {"vertices": [[0, 0], [1, 0], [0, 1]], "min_y": -1, "max_y": 2, "area": 3},
+                {"verts": [[0, 5, 0], [1, 5, 0], [0, 5, 1]], "hmin": -1, "hmax": 2, "area": 3}
+            ]}"#,
+        )
+        .unwrap();
+        let volumes = volumes.into_convex_volumes();
+        assert_eq!(volumes.len(), 2);
+        assert_eq!(volumes[0], volumes[1]);
+        assert_eq!(volumes[1].vertices[2], Vec2::new(0.0, 1.0));
+        assert_eq!(volumes[1].area, AreaType(3));
+    }
+
+    #[test]
+    fn partial_configs_keep_defaults() {
+        let builder: ConfigBuilder = toml::from_str("agent_radius = 0.3\ntiling = true").unwrap();
+        assert_eq!(
+            builder,
+            ConfigBuilder {
+                agent_radius: 0.3,
+                tiling: true,
+                ..Default::default()
+            }
+        );
+        let builder: ConfigBuilder = serde_json::from_str(r#"{"agent_height": 1.0}"#).unwrap();
+        assert_eq!(builder.agent_height, 1.0);
+        assert_eq!(builder.agent_radius, ConfigBuilder::default().agent_radius);
+    }
+}
@@ -0,0 +1,125 @@
+//! `rerecast`, a command-line tool for baking navmeshes without writing Rust.
+//!
+//! ```text
+//! rerecast level.obj --config agent.toml --output level.nav
+//! ```
+
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context as _, bail};
+use clap::Parser;
+use rerecast::{NavmeshFile, NavmeshFileCompression};
+
+mod bake;
+mod input;
+
+/// Bakes a navmesh from level geometry.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// The level geometry, either a Wavefront OBJ file or a JSON file with `verts` and `tris` arrays.
+    geometry: PathBuf,
+
+    /// The file to write the navmesh to. The format is chosen by the extension:
+    /// `.nav` for rerecast's binary navmesh format, `.obj` for Wavefront OBJ, `.glb` for binary glTF,
+    /// `.tres` for a Godot `NavigationMesh` resource or `.json` for a Unity-style triangulation.
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// A `ConfigBuilder` as JSON or TOML. Missing fields keep their default values.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Convex volumes marking areas, as JSON or TOML with a `volumes` list. Each volume has either
+    /// `vertices` ([x, z] pairs), `min_y`, `max_y` and `area`, or, like the Recast demo's
+    /// `convex_volumes.json`, `verts` ([x, y, z] triples), `hmin`, `hmax` and `area`.
+    #[arg(long)]
+    volumes: Option<PathBuf>,
+
+    /// Off-mesh connections, as JSON or TOML with a `connections` list.
+    #[arg(long)]
+    off_mesh_connections: Option<PathBuf>,
+
+    /// Generate the navmesh in tiles, regardless of the `tiling` setting of the config.
+    #[arg(long)]
+    tiled: bool,
+
+    /// Store the payload of `.nav` files without compression.
+    #[arg(long)]
+    uncompressed: bool,
+}
+
+/// The formats the navmesh can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Nav,
+    Obj,
+    Glb,
+    Tres,
+    Json,
+}
+
+impl OutputFormat {
+    fn from_path(path: &std::path::Path) -> anyhow::Result<Self> {
+        match input::extension(path).as_str() {
+            "nav" => Ok(Self::Nav),
+            "obj" => Ok(Self::Obj),
+            "glb" => Ok(Self::Glb),
+            "tres" => Ok(Self::Tres),
+            "json" => Ok(Self::Json),
+            other => bail!(
+                "Unsupported output format `.{other}`, expected `.nav`, `.obj`, `.glb`, `.tres` or `.json`"
+            ),
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    // Fail before baking if the output can't be written anyways.
+    let format = OutputFormat::from_path(&args.output)?;
+
+    let mut builder = match &args.config {
+        Some(path) => input::load_data(path)?,
+        None => rerecast::ConfigBuilder::default(),
+    };
+    if args.tiled {
+        builder.tiling = true;
+    }
+    if let Some(path) = &args.volumes {
+        let volumes: input::VolumesFile = input::load_data(path)?;
+        builder.area_volumes.extend(volumes.into_convex_volumes());
+    }
+    if let Some(path) = &args.off_mesh_connections {
+        let connections: input::OffMeshConnectionsFile = input::load_data(path)?;
+        builder.off_mesh_connections.extend(connections.connections);
+    }
+    let trimesh = input::load_geometry(&args.geometry)?;
+    println!(
+        "Loaded {} vertices and {} triangles from {}",
+        trimesh.vertices.len(),
+        trimesh.indices.len(),
+        args.geometry.display()
+    );
+
+    let (config, navmesh) = bake::bake(builder, trimesh)?;
+
+    let bytes = match format {
+        OutputFormat::Nav => {
+            let compression = if args.uncompressed {
+                NavmeshFileCompression::None
+            } else {
+                NavmeshFileCompression::Deflate
+            };
+            NavmeshFile { config, navmesh }.to_bytes(compression)?
+        }
+        OutputFormat::Obj => navmesh.into_tiled_navmesh().to_obj().into_bytes(),
+        OutputFormat::Glb => navmesh.into_tiled_navmesh().to_glb(),
+        OutputFormat::Tres => navmesh.into_tiled_navmesh().to_godot_tres().into_bytes(),
+        OutputFormat::Json => navmesh.into_tiled_navmesh().to_unity_json()?.into_bytes(),
+    };
+    fs::write(&args.output, &bytes)
+        .with_context(|| format!("Failed to write {}", args.output.display()))?;
+    println!("Wrote {} bytes to {}", bytes.len(), args.output.display());
+    Ok(())
+}
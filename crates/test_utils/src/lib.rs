@@ -41,6 +41,8 @@ impl NavmeshConfigTest for Config {
             detail_sample_dist: config.detail_sample_dist,
             detail_sample_max_error: config.detail_sample_max_error,
             area_volumes: Vec::new(),
+            off_mesh_connections: Vec::new(),
+            jump_links: None,
             contour_flags: BuildContoursFlags::default(),
         }
     }
@@ -762,6 +764,18 @@ pub struct CppConfig {
     pub detail_sample_max_error: f32,
 }
 
+/// A walkable quad of two triangles spanning `aabb` on the xz-plane at the height `aabb.min.y`.
+pub fn flat_ground(aabb: Aabb3d) -> TriMesh {
+    let (min, max) = (aabb.min, aabb.max);
+    TriMesh {
+        vertices: [(min.x, min.z), (max.x, min.z), (min.x, max.z), (max.x, max.z)]
+            .map(|(x, z)| Vec3A::new(x, min.y, z))
+            .to_vec(),
+        indices: vec![UVec3::new(0, 2, 1), UVec3::new(1, 2, 3)],
+        area_types: vec![AreaType::DEFAULT_WALKABLE; 2],
+    }
+}
+
 pub fn test_data_dir() -> PathBuf {
     env::current_dir()
         .unwrap()